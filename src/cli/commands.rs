@@ -1,13 +1,28 @@
-use clap::{Parser, Subcommand};
-use pcsc::ShareMode;
+use clap::{Args, Parser, Subcommand};
+use pcsc::{Disposition, Scope, ShareMode};
 use anyhow::{Result, Context, bail};
 use std::io::{self, BufRead, BufReader};
 use std::fs::File;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use crate::core::{
+    attributes::{attribute_def, parse_attribute, AttributeDef, AttributeFormat, AttributeValue, KNOWN_ATTRIBUTES},
+    backend::{BackendSpec, PcscBackend, DynBackend, ProtocolSelection},
+    cancel::CancelToken,
     reader::PcscReader,
-    commands::CommandExecutor,
-    utils::{format_hex_spaced, format_hex_dump, format_ascii, parse_control_code, describe_status_word},
+    events::ReaderEventKind,
+    features::{feature_def, ReaderProperties},
+    pinpad::{PinEntry, PinEncoding, PinFormat, PinModify, PinResult, PinVerify, VALIDATE_KEY, VALIDATE_MAX_LENGTH},
+    manager::{ReaderSession, SessionManager},
+    selector::ReaderSelector,
+    recovery::RecoveryPolicy,
+    commands::{check_status_word, CommandExecutor, CommandType, ControlResult},
+    error::{Error, ExitStatus},
+    contactless::{KeyType, StorageCommand, StorageResult},
+    escape::{escape_def, ESCAPE_COMMANDS},
+    utils::{format_hex_spaced, format_hex_dump, format_ascii, format_control_code, parse_control_code, parse_hex, describe_status_word, symbolic_control_code},
 };
 
 #[derive(Parser)]
@@ -25,6 +40,38 @@ pub struct Cli {
     /// Enable debug logging  
     #[arg(short, long, global = true)]
     pub debug: bool,
+    
+    /// PCSC backend: 'system', 'sim:<rules.json>' for the simulated card or 'replay:<session.json>' for a recorded session
+    #[arg(short, long, global = true, default_value = "system")]
+    pub backend: BackendSpec,
+    
+    /// Record all exchanges to a session file (replay with --backend replay:<file>)
+    #[arg(long, global = true, value_name = "FILE")]
+    pub record: Option<PathBuf>,
+    
+    /// PC/SC context scope: user or system
+    #[arg(long, global = true, default_value = "user")]
+    pub scope: ScopeArg,
+    
+    /// Only use readers of this reader group (listing and reader indexes)
+    #[arg(short, long, global = true, value_name = "GROUP")]
+    pub group: Option<String>,
+    
+    /// On card reset/removal or reader loss, reconnect and retry up to N times
+    #[arg(long, global = true, value_name = "N", default_value_t = 0)]
+    pub retries: u32,
+    
+    /// Delay before the first retry, doubled for each following one
+    #[arg(long, global = true, value_name = "MS", default_value_t = 200)]
+    pub retry_delay: u64,
+    
+    /// APDU to send after reconnecting, before retrying (repeatable), e.g. a SELECT
+    #[arg(long, global = true, value_name = "APDU")]
+    pub preamble: Vec<String>,
+    
+    /// Give up on a card or reader that has not answered a command within MS milliseconds
+    #[arg(long, global = true, value_name = "MS")]
+    pub command_timeout: Option<u64>,
 }
 
 #[derive(Subcommand)]
@@ -36,9 +83,23 @@ pub enum Commands {
         detailed: bool,
     },
     
+    /// List reader groups
+    Groups,
+    
+    /// Watch readers and print card/reader events as they happen
+    Monitor {
+        /// Print events as JSON lines
+        #[arg(long)]
+        json: bool,
+        
+        /// Stop after this many seconds (default: run until interrupted)
+        #[arg(short, long, value_name = "SECS")]
+        timeout: Option<u64>,
+    },
+    
     /// Send APDU command via transmit
     Transmit {
-        /// Reader name or index (use 'list' to see available readers)
+        /// Reader name, index or selector: name~TEXT, name:GLOB, re:REGEX, atr:HEX, first-with-card
         reader: String,
         
         /// APDU command in hex format (e.g., "00A40400")
@@ -48,17 +109,29 @@ pub enum Commands {
         #[arg(short, long, default_value = "shared")]
         mode: ShareModeArg,
         
+        /// Acceptable protocols: t0, t1, t0t1 or raw
+        #[arg(short, long, default_value = "t0t1")]
+        protocol: ProtocolSelection,
+        
         /// Show response in different formats
         #[arg(short, long)]
         format: Option<ResponseFormat>,
+        
+        /// Wait for a card before connecting, optionally for at most SECS seconds
+        #[arg(long, value_name = "SECS", num_args = 0..=1, require_equals = true)]
+        wait: Option<Option<u64>>,
+        
+        /// Fail with exit code 7 unless the status word matches, e.g. 9000 or 61XX
+        #[arg(long, value_name = "SW")]
+        expect_sw: Option<String>,
     },
     
     /// Send control command to reader
     Control {
-        /// Reader name or index  
+        /// Reader name, index or selector
         reader: String,
         
-        /// Control code (decimal or hex with 0x prefix)
+        /// Control code: decimal, hex with 0x prefix, or SCARD_CTL_CODE function as ctl:3500 or ctl:ESCAPE
         code: String,
         
         /// Data to send in hex format (optional)
@@ -69,9 +142,181 @@ pub enum Commands {
         #[arg(short, long, default_value = "direct")]
         mode: ShareModeArg,
         
+        /// Acceptable protocols: t0, t1, t0t1 or raw
+        #[arg(short, long, default_value = "t0t1")]
+        protocol: ProtocolSelection,
+        
         /// Show response in different formats
         #[arg(short, long)]
         format: Option<ResponseFormat>,
+        
+        /// Wait for a card before connecting, optionally for at most SECS seconds
+        #[arg(long, value_name = "SECS", num_args = 0..=1, require_equals = true)]
+        wait: Option<Option<u64>>,
+    },
+    
+    /// Send a named vendor escape command (firmware version, LEDs, RF field...)
+    Escape {
+        /// Reader name, index or selector
+        #[arg(required_unless_present = "list")]
+        reader: Option<String>,
+        
+        /// Escape command name, see --list
+        #[arg(required_unless_present = "list")]
+        name: Option<String>,
+        
+        /// Parameter values, decimal or hex with 0x prefix
+        args: Vec<String>,
+        
+        /// List the known escape commands
+        #[arg(long, conflicts_with_all = ["reader", "name"])]
+        list: bool,
+        
+        /// Connection share mode (use 'direct' for reader control)
+        #[arg(short, long, default_value = "direct")]
+        mode: ShareModeArg,
+        
+        /// Acceptable protocols: t0, t1, t0t1 or raw
+        #[arg(short, long, default_value = "t0t1")]
+        protocol: ProtocolSelection,
+        
+        /// Show response in different formats
+        #[arg(short, long)]
+        format: Option<ResponseFormat>,
+        
+        /// Wait for a card before connecting, optionally for at most SECS seconds
+        #[arg(long, value_name = "SECS", num_args = 0..=1, require_equals = true)]
+        wait: Option<Option<u64>>,
+    },
+    
+    /// Show or change reader attributes (vendor, serial number, protocol...)
+    Attrib {
+        /// Reader name, index or selector
+        reader: String,
+        
+        /// Attribute name, or ID in 0x-prefixed hex or decimal (default: every known attribute)
+        attribute: Option<String>,
+        
+        /// Write this hex value to the attribute instead of reading it
+        #[arg(long, value_name = "HEX", requires = "attribute")]
+        set: Option<String>,
+        
+        /// Also list attributes the reader does not support
+        #[arg(short, long)]
+        all: bool,
+        
+        /// Print attributes as JSON
+        #[arg(long)]
+        json: bool,
+        
+        /// Connection share mode
+        #[arg(short, long, default_value = "direct")]
+        mode: ShareModeArg,
+        
+        /// Acceptable protocols: t0, t1, t0t1 or raw
+        #[arg(short, long, default_value = "t0t1")]
+        protocol: ProtocolSelection,
+    },
+    
+    /// List the PC/SC Part 10 features of a reader (PIN pad, escape...) and their control codes
+    Features {
+        /// Reader name, index or selector
+        reader: String,
+        
+        /// Print features as JSON
+        #[arg(long)]
+        json: bool,
+        
+        /// Connection share mode
+        #[arg(short, long, default_value = "direct")]
+        mode: ShareModeArg,
+        
+        /// Acceptable protocols: t0, t1, t0t1 or raw
+        #[arg(short, long, default_value = "t0t1")]
+        protocol: ProtocolSelection,
+    },
+    
+    /// Verify a PIN entered on the reader's PIN pad (VERIFY_PIN_DIRECT)
+    PinVerify {
+        /// Reader name, index or selector
+        reader: String,
+        
+        /// VERIFY APDU the reader inserts the PIN into, e.g. "00200081 08 FFFFFFFFFFFFFFFF"
+        apdu: String,
+        
+        #[command(flatten)]
+        pin: PinArgs,
+    },
+    
+    /// Change a PIN, entered on the reader's PIN pad (MODIFY_PIN_DIRECT)
+    PinModify {
+        /// Reader name, index or selector
+        reader: String,
+        
+        /// CHANGE REFERENCE DATA APDU the reader inserts both PINs into, e.g. "00240081 10" and 16 bytes of FF
+        apdu: String,
+        
+        /// Offset in bytes of the current PIN in the APDU data
+        #[arg(long, value_name = "BYTES", default_value_t = 0)]
+        old_offset: u8,
+        
+        /// Offset in bytes of the new PIN in the APDU data (default: right after the current PIN block)
+        #[arg(long, value_name = "BYTES")]
+        new_offset: Option<u8>,
+        
+        /// Enter the new PIN once, without confirmation
+        #[arg(long)]
+        no_confirm: bool,
+        
+        /// Only enter the new PIN, not the current one
+        #[arg(long)]
+        new_only: bool,
+        
+        #[command(flatten)]
+        pin: PinArgs,
+    },
+    
+    /// Contactless storage card commands (PC/SC Part 3 pseudo-APDUs)
+    Contactless {
+        /// Reader name, index or selector
+        reader: String,
+        
+        #[command(subcommand)]
+        command: ContactlessCommand,
+        
+        /// Connection share mode
+        #[arg(short, long, default_value = "shared", global = true)]
+        mode: ShareModeArg,
+        
+        /// Acceptable protocols: t0, t1, t0t1 or raw
+        #[arg(short, long, default_value = "t0t1", global = true)]
+        protocol: ProtocolSelection,
+        
+        /// Wait for a card before connecting, optionally for at most SECS seconds
+        #[arg(long, value_name = "SECS", num_args = 0..=1, require_equals = true, global = true)]
+        wait: Option<Option<u64>>,
+    },
+    
+    /// Reset the card in a reader and show its new ATR
+    Reset {
+        /// Reader name, index or selector
+        reader: String,
+        
+        /// Cold reset: power the card down and up again instead of a warm reset
+        #[arg(long)]
+        cold: bool,
+        
+        /// Connection share mode
+        #[arg(short, long, default_value = "shared")]
+        mode: ShareModeArg,
+        
+        /// Acceptable protocols: t0, t1, t0t1 or raw
+        #[arg(short, long, default_value = "t0t1")]
+        protocol: ProtocolSelection,
+        
+        /// Wait for a card before connecting, optionally for at most SECS seconds
+        #[arg(long, value_name = "SECS", num_args = 0..=1, require_equals = true)]
+        wait: Option<Option<u64>>,
     },
     
     /// Execute commands from a script file
@@ -79,25 +324,179 @@ pub enum Commands {
         /// Script file path
         file: String,
         
-        /// Reader name or index to use for all commands
+        /// Reader name, index or selector to use for all commands
         reader: String,
         
         /// Connection share mode
         #[arg(short, long, default_value = "shared")]
         mode: ShareModeArg,
         
+        /// Acceptable protocols: t0, t1, t0t1 or raw
+        #[arg(short, long, default_value = "t0t1")]
+        protocol: ProtocolSelection,
+        
         /// Continue on errors
         #[arg(short, long)]
         continue_on_error: bool,
+        
+        /// Also connect to READER as session ALIAS (repeatable), e.g. --open sam=1
+        #[arg(short, long, value_name = "ALIAS=READER")]
+        open: Vec<String>,
+        
+        /// Wait for a card before connecting, optionally for at most SECS seconds
+        #[arg(long, value_name = "SECS", num_args = 0..=1, require_equals = true)]
+        wait: Option<Option<u64>>,
     },
     
     /// Interactive mode
     Interactive {
-        /// Reader name or index (optional, can select interactively)
+        /// Reader name, index or selector (optional, can select interactively)
         reader: Option<String>,
+        
+        /// Acceptable protocols: t0, t1, t0t1 or raw
+        #[arg(short, long, default_value = "t0t1")]
+        protocol: ProtocolSelection,
+        
+        /// Also connect to READER as session ALIAS (repeatable), e.g. --open sam=1
+        #[arg(short, long, value_name = "ALIAS=READER")]
+        open: Vec<String>,
     },
 }
 
+#[derive(Subcommand)]
+pub enum ContactlessCommand {
+    /// Show the card's UID
+    Uid,
+    
+    /// Show the historical bytes of the card's ATS
+    Ats,
+    
+    /// Read a block
+    Read {
+        /// Block number
+        block: u16,
+        
+        /// Bytes to read (0: 256)
+        #[arg(default_value_t = 16)]
+        length: u8,
+        
+        #[command(flatten)]
+        key: KeyArgs,
+        
+        /// Show the data in different formats
+        #[arg(short, long)]
+        format: Option<ResponseFormat>,
+    },
+    
+    /// Write a block
+    Write {
+        /// Block number
+        block: u16,
+        
+        /// Data to write in hex format
+        data: String,
+        
+        #[command(flatten)]
+        key: KeyArgs,
+    },
+    
+    /// Authenticate a block, with --key or a key already loaded in the reader
+    Auth {
+        /// Block number
+        block: u16,
+        
+        #[command(flatten)]
+        key: KeyArgs,
+    },
+}
+
+/// Authentication options of the `contactless` commands
+#[derive(Args)]
+pub struct KeyArgs {
+    /// Load this key into the reader (e.g. FFFFFFFFFFFF) and authenticate first
+    #[arg(long, value_name = "HEX")]
+    key: Option<String>,
+    
+    /// Authenticate with key A or B
+    #[arg(long, default_value = "a")]
+    key_type: KeyType,
+    
+    /// Reader key slot to load the key into and authenticate with
+    #[arg(long, default_value_t = 0)]
+    key_number: u8,
+}
+
+/// PIN entry options of `pin-verify` and `pin-modify`
+#[derive(Args)]
+pub struct PinArgs {
+    /// How the reader encodes PIN digits: ascii, bcd or binary
+    #[arg(long, default_value = "ascii")]
+    encoding: PinEncoding,
+    
+    /// Size in bytes of the PIN block in the APDU
+    #[arg(long, value_name = "BYTES", default_value_t = 8)]
+    block_size: u8,
+    
+    /// Use an ISO 9564 format 2 PIN block (BCD, PIN length in the first byte) instead of --encoding/--block-size
+    #[arg(long)]
+    format2: bool,
+    
+    /// Minimum PIN length in digits
+    #[arg(long, value_name = "DIGITS", default_value_t = 4)]
+    min_length: u8,
+    
+    /// Maximum PIN length in digits
+    #[arg(long, value_name = "DIGITS", default_value_t = 8)]
+    max_length: u8,
+    
+    /// Seconds the reader waits for PIN entry (0: reader default)
+    #[arg(long, value_name = "SECS", default_value_t = 30)]
+    entry_timeout: u8,
+    
+    /// Also end PIN entry when the maximum length is reached, without the OK key
+    #[arg(long)]
+    auto_submit: bool,
+    
+    /// Connection share mode
+    #[arg(short, long, default_value = "shared")]
+    mode: ShareModeArg,
+    
+    /// Acceptable protocols: t0, t1, t0t1 or raw
+    #[arg(short, long, default_value = "t0t1")]
+    protocol: ProtocolSelection,
+    
+    /// Wait for a card before connecting, optionally for at most SECS seconds
+    #[arg(long, value_name = "SECS", num_args = 0..=1, require_equals = true)]
+    wait: Option<Option<u64>>,
+}
+
+impl PinArgs {
+    fn connect_options(&self) -> ConnectOptions {
+        ConnectOptions { mode: self.mode.clone().into(), protocol: self.protocol, wait: self.wait }
+    }
+    
+    fn entry(&self) -> PinEntry {
+        let format = if self.format2 {
+            PinFormat::iso9564_format2()
+        } else {
+            PinFormat { encoding: self.encoding, ..PinFormat::ascii(self.block_size) }
+        };
+        let mut validation = VALIDATE_KEY;
+        if self.auto_submit {
+            validation |= VALIDATE_MAX_LENGTH;
+        }
+        PinEntry {
+            timeout: self.entry_timeout,
+            first_key_timeout: self.entry_timeout,
+            format,
+            min_length: self.min_length,
+            max_length: self.max_length,
+            validation,
+            ..Default::default()
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum ShareModeArg {
     Shared,
@@ -128,6 +527,33 @@ impl From<ShareModeArg> for ShareMode {
     }
 }
 
+#[derive(Clone, Copy, Debug)]
+pub enum ScopeArg {
+    User,
+    System,
+}
+
+impl std::str::FromStr for ScopeArg {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "user" => Ok(ScopeArg::User),
+            "system" => Ok(ScopeArg::System),
+            _ => Err(format!("Invalid scope: {} (expected user or system)", s)),
+        }
+    }
+}
+
+impl From<ScopeArg> for Scope {
+    fn from(scope: ScopeArg) -> Self {
+        match scope {
+            ScopeArg::User => Scope::User,
+            ScopeArg::System => Scope::System,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum ResponseFormat {
     Hex,
@@ -168,43 +594,215 @@ pub fn run_cli() -> Result<()> {
         .filter_level(log_level)
         .init();
     
+    let recovery = RecoveryPolicy {
+        max_retries: cli.retries,
+        backoff: Duration::from_millis(cli.retry_delay),
+        preamble: cli.preamble.iter()
+            .map(|apdu| parse_hex(apdu).with_context(|| format!("Invalid preamble APDU: {}", apdu)))
+            .collect::<Result<_>>()?,
+        ..Default::default()
+    };
+    let opts = GlobalOptions {
+        backend: cli.backend,
+        record: cli.record,
+        scope: cli.scope.into(),
+        group: cli.group,
+        recovery,
+        command_timeout: cli.command_timeout.map(Duration::from_millis),
+        cancel: CancelToken::new(),
+    };
+    install_ctrlc_handler(&opts.cancel);
     match cli.command {
-        Commands::List { detailed } => cmd_list(detailed),
-        Commands::Transmit { reader, apdu, mode, format } => {
-            cmd_transmit(&reader, &apdu, mode, format)
+        Commands::List { detailed } => cmd_list(&opts, detailed),
+        Commands::Groups => cmd_groups(&opts),
+        Commands::Monitor { json, timeout } => cmd_monitor(&opts, json, timeout),
+        Commands::Transmit { reader, apdu, mode, protocol, format, wait, expect_sw } => {
+            let conn = ConnectOptions { mode: mode.into(), protocol, wait };
+            cmd_transmit(&opts, &reader, &apdu, &conn, format, expect_sw.as_deref())
+        }
+        Commands::Control { reader, code, data, mode, protocol, format, wait } => {
+            let conn = ConnectOptions { mode: mode.into(), protocol, wait };
+            cmd_control(&opts, &reader, &code, &data, &conn, format)
+        }
+        Commands::Escape { reader, name, args, list, mode, protocol, format, wait } => {
+            match (reader, name) {
+                (Some(reader), Some(name)) if !list => {
+                    let conn = ConnectOptions { mode: mode.into(), protocol, wait };
+                    cmd_escape(&opts, &reader, &name, &args, &conn, format)
+                }
+                _ => cmd_escape_list(),
+            }
+        }
+        Commands::Attrib { reader, attribute, set, all, json, mode, protocol } => {
+            let conn = ConnectOptions { mode: mode.into(), protocol, wait: None };
+            cmd_attrib(&opts, &reader, &conn, attribute.as_deref(), set.as_deref(), all, json)
+        }
+        Commands::Features { reader, json, mode, protocol } => {
+            let conn = ConnectOptions { mode: mode.into(), protocol, wait: None };
+            cmd_features(&opts, &reader, &conn, json)
+        }
+        Commands::PinVerify { reader, apdu, pin } => {
+            let mut request = PinVerify::new(parse_hex(&apdu).context("Failed to parse APDU hex string")?);
+            request.entry = pin.entry();
+            cmd_pin_verify(&opts, &reader, &request, &pin)
+        }
+        Commands::PinModify { reader, apdu, old_offset, new_offset, no_confirm, new_only, pin } => {
+            let mut request = PinModify::new(parse_hex(&apdu).context("Failed to parse APDU hex string")?);
+            request.entry = pin.entry();
+            request.old_pin_offset = old_offset;
+            let after_old = old_offset.saturating_add(request.entry.format.block_size);
+            request.new_pin_offset = new_offset.unwrap_or(if new_only { 0 } else { after_old });
+            request.confirm = !no_confirm;
+            request.enter_old = !new_only;
+            cmd_pin_modify(&opts, &reader, &request, &pin)
+        }
+        Commands::Contactless { reader, command, mode, protocol, wait } => {
+            let conn = ConnectOptions { mode: mode.into(), protocol, wait };
+            cmd_contactless(&opts, &reader, &conn, command)
+        }
+        Commands::Reset { reader, cold, mode, protocol, wait } => {
+            let conn = ConnectOptions { mode: mode.into(), protocol, wait };
+            cmd_reset(&opts, &reader, &conn, cold)
+        }
+        Commands::Script { file, reader, mode, protocol, continue_on_error, open, wait } => {
+            let conn = ConnectOptions { mode: mode.into(), protocol, wait };
+            cmd_script(&opts, &file, &reader, &conn, &open, continue_on_error)
+        }
+        Commands::Interactive { reader, protocol, open } => {
+            cmd_interactive(&opts, reader.as_deref(), protocol, &open)
         }
-        Commands::Control { reader, code, data, mode, format } => {
-            cmd_control(&reader, &code, &data, mode, format)
+    }
+}
+
+/// Session alias of the reader given on the command line
+const DEFAULT_SESSION: &str = "default";
+
+/// Options shared by all subcommands
+struct GlobalOptions {
+    backend: BackendSpec,
+    record: Option<PathBuf>,
+    scope: Scope,
+    group: Option<String>,
+    recovery: RecoveryPolicy,
+    command_timeout: Option<Duration>,
+    /// Cancelled by Ctrl-C
+    cancel: CancelToken,
+}
+
+/// How a subcommand connects to its reader
+struct ConnectOptions {
+    mode: ShareMode,
+    protocol: ProtocolSelection,
+    /// `--wait[=secs]`: wait for a card first, optionally with a timeout
+    wait: Option<Option<u64>>,
+}
+
+impl GlobalOptions {
+    fn open_reader(&self) -> Result<PcscReader<DynBackend>> {
+        let mut reader = PcscReader::open_with_scope(&self.backend, self.scope)
+            .context("Failed to initialize PCSC")?;
+        reader.set_group(self.group.clone());
+        reader.set_cancel_token(self.cancel.clone());
+        Ok(reader)
+    }
+    
+    fn open_sessions(&self) -> Result<SessionManager<DynBackend>> {
+        let mut sessions = SessionManager::open_with_scope(&self.backend, self.scope)
+            .context("Failed to initialize PCSC")?;
+        sessions.readers_mut().set_group(self.group.clone());
+        sessions.set_cancel_token(self.cancel.clone());
+        Ok(sessions)
+    }
+    
+    /// Open the backend and connect to a reader given by name or index
+    fn connect(&self, reader_name: &str, conn: &ConnectOptions) -> Result<(PcscReader<DynBackend>, String)> {
+        let mut reader = self.open_reader()?;
+        
+        let reader_name = resolve_reader_name(&reader, reader_name)?;
+        wait_for_card(&reader, &reader_name, conn.wait)?;
+        reader.connect_with_protocol(&reader_name, conn.mode, conn.protocol)
+            .with_context(|| format!("Failed to connect to reader: {}", reader_name))?;
+        
+        Ok((reader, reader_name))
+    }
+    
+    fn new_executor(&self) -> CommandExecutor {
+        let mut executor = CommandExecutor::new();
+        executor.set_recovery_policy(self.recovery.clone());
+        executor.set_timeout(self.command_timeout);
+        if self.record.is_some() {
+            executor.start_recording();
         }
-        Commands::Script { file, reader, mode, continue_on_error } => {
-            cmd_script(&file, &reader, mode, continue_on_error)
+        executor
+    }
+    
+    /// Save the recorded session, if recording was requested
+    fn save_recording(&self, executor: &mut CommandExecutor) -> Result<()> {
+        if let (Some(path), Some(session)) = (&self.record, executor.stop_recording()) {
+            session.save(path)?;
+            log::info!("Recorded {} exchange(s) to {}", session.exchanges.len(), path.display());
         }
-        Commands::Interactive { reader } => {
-            cmd_interactive(reader.as_deref())
+        Ok(())
+    }
+}
+
+/// Make Ctrl-C cancel the operation in progress, and a second Ctrl-C exit
+/// right away in case the operation does not stop
+fn install_ctrlc_handler(cancel: &CancelToken) {
+    let cancel = cancel.clone();
+    let result = ctrlc::set_handler(move || {
+        if cancel.is_cancelled() {
+            std::process::exit(ExitStatus::Cancelled.code().into());
         }
+        eprintln!("Cancelling (press Ctrl-C again to quit immediately)");
+        cancel.cancel();
+    });
+    if let Err(e) = result {
+        log::warn!("Failed to install the Ctrl-C handler: {}", e);
     }
 }
 
-fn cmd_list(detailed: bool) -> Result<()> {
-    let reader = PcscReader::new()
-        .context("Failed to initialize PCSC")?;
+/// Check whether an error comes from a cancellation
+fn is_cancelled(error: &anyhow::Error) -> bool {
+    ExitStatus::of(error) == ExitStatus::Cancelled
+}
+
+fn cmd_list(opts: &GlobalOptions, detailed: bool) -> Result<()> {
+    let mut reader = opts.open_reader()?;
     
     let readers = reader.list_readers()
         .context("Failed to list readers")?;
     
     if readers.is_empty() {
-        println!("No PCSC readers found.");
+        match reader.group() {
+            Some(group) => println!("No PCSC readers found in group {}.", group),
+            None => println!("No PCSC readers found."),
+        }
         return Ok(());
     }
     
-    println!("Available PCSC readers:");
+    match reader.group() {
+        Some(group) => println!("Available PCSC readers in group {}:", group),
+        None => println!("Available PCSC readers:"),
+    }
     for (i, reader_info) in readers.iter().enumerate() {
         if detailed {
             println!("  [{}] {}", i, reader_info.name);
             println!("      Status: {}", if reader_info.is_connected { "Card present" } else { "No card" });
+            let flags = reader_info.status.flags();
+            println!("      State: {}", if flags.is_empty() { "unknown".to_string() } else { flags.join(", ") });
+            println!("      Events: {}", reader_info.status.event_count);
             if let Some(ref atr) = reader_info.atr {
                 println!("      ATR: {}", format_hex_spaced(atr));
             }
+            match read_properties(&mut reader, &reader_info.name) {
+                Ok(properties) => {
+                    for (label, value) in properties.describe() {
+                        println!("      {}: {}", label, value);
+                    }
+                }
+                Err(e) => log::debug!("No properties for {}: {:#}", reader_info.name, e),
+            }
         } else {
             if reader_info.is_connected {
                 if let Some(ref atr) = reader_info.atr {
@@ -221,18 +819,79 @@ fn cmd_list(detailed: bool) -> Result<()> {
     Ok(())
 }
 
-fn cmd_transmit(reader_name: &str, apdu_hex: &str, mode: ShareModeArg, format: Option<ResponseFormat>) -> Result<()> {
-    let mut reader = PcscReader::new()
-        .context("Failed to initialize PCSC")?;
+/// Connect directly to a reader, which works without a card, and ask for
+/// its properties
+fn read_properties(reader: &mut PcscReader<DynBackend>, reader_name: &str) -> Result<ReaderProperties> {
+    reader.connect(reader_name, ShareMode::Direct)?;
+    let properties = reader.properties();
+    reader.disconnect(Disposition::LeaveCard)?;
+    properties
+}
+
+fn cmd_groups(opts: &GlobalOptions) -> Result<()> {
+    let mut reader = opts.open_reader()?;
+    let groups = reader.list_reader_groups()?;
+    
+    if groups.is_empty() {
+        println!("No reader groups found.");
+        return Ok(());
+    }
+    
+    println!("Reader groups:");
+    for group in groups {
+        reader.set_group(Some(group.clone()));
+        let readers = reader.list_readers()?;
+        println!("  {} ({} reader{})", group, readers.len(), if readers.len() == 1 { "" } else { "s" });
+        for reader_info in readers {
+            println!("      {}", reader_info.name);
+        }
+    }
+    
+    Ok(())
+}
+
+fn cmd_monitor(opts: &GlobalOptions, json: bool, timeout: Option<u64>) -> Result<()> {
+    let reader = opts.open_reader()?;
     
-    let reader_name = resolve_reader_name(&reader, reader_name)?;
-    reader.connect(&reader_name, mode.into())
-        .with_context(|| format!("Failed to connect to reader: {}", reader_name))?;
+    if !json {
+        for reader_info in reader.list_readers().context("Failed to list readers")? {
+            match reader_info.atr {
+                Some(ref atr) if reader_info.is_connected => {
+                    println!("{}: card present (ATR: {})", reader_info.name, format_hex_spaced(atr));
+                }
+                _ => println!("{}: {}", reader_info.name,
+                              if reader_info.is_connected { "card present" } else { "no card" }),
+            }
+        }
+        println!("Monitoring reader events{}...", if timeout.is_some() { "" } else { " (Ctrl-C to stop)" });
+    }
+    
+    let result = reader.watch(timeout.map(Duration::from_secs), |event| {
+        if json {
+            println!("{}", event.to_json_line());
+        } else {
+            println!("{}", event);
+        }
+        true
+    });
+    match result {
+        // Ctrl-C is the normal way to stop monitoring
+        Err(e) if is_cancelled(&e) => Ok(()),
+        result => result,
+    }
+}
+
+fn cmd_transmit(opts: &GlobalOptions, reader_name: &str, apdu_hex: &str, conn: &ConnectOptions, format: Option<ResponseFormat>, expect_sw: Option<&str>) -> Result<()> {
+    let (mut reader, _) = opts.connect(reader_name, conn)?;
     
-    let mut executor = CommandExecutor::new();
-    let result = executor.transmit(&mut reader, apdu_hex)
-        .context("Failed to execute transmit command")?;
+    let mut executor = opts.new_executor();
+    let result = executor.transmit(&mut reader, apdu_hex);
+    opts.save_recording(&mut executor)?;
+    let result = result.context("Failed to execute transmit command")?;
     
+    if let Some(protocol) = reader.protocol() {
+        println!("Protocol: {}", protocol);
+    }
     println!("APDU: {}", format_hex_spaced(&result.apdu));
     
     let format = format.unwrap_or(ResponseFormat::HexSpaced);
@@ -243,25 +902,33 @@ fn cmd_transmit(reader_name: &str, apdu_hex: &str, mode: ShareModeArg, format: O
              describe_status_word(result.sw1, result.sw2));
     println!("Duration: {}ms", result.duration_ms);
     
+    if let Some(expected) = expect_sw {
+        result.check_status_word(expected)?;
+    }
+    
     Ok(())
 }
 
-fn cmd_control(reader_name: &str, code_str: &str, data_hex: &str, mode: ShareModeArg, format: Option<ResponseFormat>) -> Result<()> {
-    let mut reader = PcscReader::new()
-        .context("Failed to initialize PCSC")?;
-    
-    let reader_name = resolve_reader_name(&reader, reader_name)?;
-    reader.connect(&reader_name, mode.into())
-        .with_context(|| format!("Failed to connect to reader: {}", reader_name))?;
+fn cmd_control(opts: &GlobalOptions, reader_name: &str, code_str: &str, data_hex: &str, conn: &ConnectOptions, format: Option<ResponseFormat>) -> Result<()> {
+    let (mut reader, _) = opts.connect(reader_name, conn)?;
     
     let code = parse_control_code(code_str)
         .context("Failed to parse control code")?;
     
-    let mut executor = CommandExecutor::new();
-    let result = executor.control(&mut reader, code, data_hex)
-        .context("Failed to execute control command")?;
+    let mut executor = opts.new_executor();
+    let result = executor.control(&mut reader, code, data_hex);
+    opts.save_recording(&mut executor)?;
+    let result = result.context("Failed to execute control command")?;
     
-    println!("Control Code: 0x{:X} ({})", result.code, result.code);
+    print_control_result(&result, format);
+    Ok(())
+}
+
+fn print_control_result(result: &ControlResult, format: Option<ResponseFormat>) {
+    match symbolic_control_code(result.code) {
+        Some(symbolic) => println!("Control Code: 0x{:X} ({}, {})", result.code, symbolic, result.code),
+        None => println!("Control Code: 0x{:X} ({})", result.code, result.code),
+    }
     if !result.input.is_empty() {
         println!("Input: {}", format_hex_spaced(&result.input));
     }
@@ -270,28 +937,264 @@ fn cmd_control(reader_name: &str, code_str: &str, data_hex: &str, mode: ShareMod
     print_response(&result.output, &format);
     
     println!("Duration: {}ms", result.duration_ms);
+}
+
+fn cmd_escape(opts: &GlobalOptions, reader_name: &str, name: &str, args: &[String], conn: &ConnectOptions, format: Option<ResponseFormat>) -> Result<()> {
+    // Check the command before touching the reader
+    let command = escape_def(name)?;
+    command.encode(args)?;
+    let (mut reader, _) = opts.connect(reader_name, conn)?;
+    
+    println!("Escape: {} ({} {})", command.name, command.vendor, command.readers);
+    let mut executor = opts.new_executor();
+    let result = executor.escape(&mut reader, command, args);
+    opts.save_recording(&mut executor)?;
+    let result = result.with_context(|| format!("Failed to execute escape command {}", command.name))?;
+    
+    print_control_result(&result, format);
+    Ok(())
+}
+
+fn cmd_escape_list() -> Result<()> {
+    let mut vendor = "";
+    for command in ESCAPE_COMMANDS {
+        if command.vendor != vendor {
+            vendor = command.vendor;
+            println!("{}:", vendor);
+        }
+        println!("  {}", command.usage());
+        println!("      {} ({})", command.description, command.readers);
+        for param in command.params {
+            match param.default {
+                Some(default) => println!("      {}: {} (default 0x{:02X})", param.name, param.description, default),
+                None => println!("      {}: {}", param.name, param.description),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn cmd_attrib(opts: &GlobalOptions, reader_name: &str, conn: &ConnectOptions, attribute: Option<&str>, set: Option<&str>, all: bool, json: bool) -> Result<()> {
+    let (reader, reader_name) = opts.connect(reader_name, conn)?;
+    
+    let defs: Vec<&AttributeDef> = match attribute {
+        Some(name) => {
+            let attribute = parse_attribute(name)?;
+            if let Some(value) = set {
+                let data = parse_hex(value).context("Failed to parse attribute value")?;
+                reader.set_attribute(attribute, &data)?;
+                println!("Attribute {} set to {}", name, format_hex_spaced(&data));
+                return Ok(());
+            }
+            attribute_def(attribute).into_iter().collect()
+        }
+        None => KNOWN_ATTRIBUTES.iter().collect(),
+    };
+    
+    let mut values = Vec::new();
+    if !json {
+        println!("Reader attributes: {}", reader_name);
+    }
+    for def in defs {
+        match reader.get_attribute(def.attribute) {
+            Ok(raw) => {
+                let value = AttributeValue::new(def.attribute, raw);
+                if !json {
+                    if def.format == AttributeFormat::Bytes || value.raw.is_empty() {
+                        println!("  {}: {}", def.description, value.decoded);
+                    } else {
+                        println!("  {}: {} [{}]", def.description, value.decoded, format_hex_spaced(&value.raw));
+                    }
+                }
+                values.push(value);
+            }
+            Err(e) if attribute.is_some() => return Err(e),
+            Err(e) => {
+                if all && !json {
+                    println!("  {}: not supported ({})", def.description, e.root_cause());
+                }
+            }
+        }
+    }
+    
+    if json {
+        println!("{}", serde_json::to_string_pretty(&values)?);
+    } else if values.is_empty() {
+        println!("  (no attributes available)");
+    }
     
     Ok(())
 }
 
-fn cmd_script(file_path: &str, reader_name: &str, mode: ShareModeArg, continue_on_error: bool) -> Result<()> {
-    let mut reader = PcscReader::new()
-        .context("Failed to initialize PCSC")?;
+fn cmd_features(opts: &GlobalOptions, reader_name: &str, conn: &ConnectOptions, json: bool) -> Result<()> {
+    let (reader, reader_name) = opts.connect(reader_name, conn)?;
+    let features = reader.features()?;
     
-    let reader_name = resolve_reader_name(&reader, reader_name)?;
-    reader.connect(&reader_name, mode.into())
-        .with_context(|| format!("Failed to connect to reader: {}", reader_name))?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&features)?);
+        return Ok(());
+    }
+    
+    println!("Reader features: {}", reader_name);
+    for feature in features.iter() {
+        let description = feature_def(feature.tag).map_or("", |def| def.description);
+        println!("  0x{:02X} {:<26} 0x{:08X}  {}", feature.tag, feature.name, feature.control_code, description);
+    }
+    if features.is_empty() {
+        println!("  (no features reported)");
+    }
+    
+    Ok(())
+}
+
+fn cmd_contactless(opts: &GlobalOptions, reader_name: &str, conn: &ConnectOptions, command: ContactlessCommand) -> Result<()> {
+    let (mut reader, _) = opts.connect(reader_name, conn)?;
+    
+    let mut executor = opts.new_executor();
+    let result = run_contactless(&mut executor, &mut reader, command);
+    opts.save_recording(&mut executor)?;
+    result
+}
+
+fn run_contactless(executor: &mut CommandExecutor, reader: &mut PcscReader<DynBackend>, command: ContactlessCommand) -> Result<()> {
+    match command {
+        ContactlessCommand::Uid => {
+            let result = storage_command(executor, reader, StorageCommand::GetUid)?;
+            println!("UID: {}", format_hex_spaced(result.data()));
+        }
+        ContactlessCommand::Ats => {
+            let result = storage_command(executor, reader, StorageCommand::GetAts)?;
+            if result.data().is_empty() {
+                println!("ATS historical bytes: (none)");
+            } else {
+                println!("ATS historical bytes: {}", format_hex_spaced(result.data()));
+            }
+        }
+        ContactlessCommand::Read { block, length, key, format } => {
+            authenticate(executor, reader, &key, block, false)?;
+            let result = storage_command(executor, reader, StorageCommand::ReadBinary { block, length })?;
+            println!("Block {} ({} bytes)", block, result.data().len());
+            print_response(result.data(), &format.unwrap_or(ResponseFormat::HexSpaced));
+        }
+        ContactlessCommand::Write { block, data, key } => {
+            let data = parse_hex(&data).context("Failed to parse data hex string")?;
+            let length = data.len();
+            // Check the data before authenticating
+            StorageCommand::UpdateBinary { block, data: data.clone() }.encode()?;
+            authenticate(executor, reader, &key, block, false)?;
+            storage_command(executor, reader, StorageCommand::UpdateBinary { block, data })?;
+            println!("Wrote {} byte(s) to block {}", length, block);
+        }
+        ContactlessCommand::Auth { block, key } => {
+            authenticate(executor, reader, &key, block, true)?;
+            println!("Authenticated block {} with key {} (slot {})", block, key.key_type, key.key_number);
+        }
+    }
+    Ok(())
+}
+
+/// Load the key given with --key and authenticate with it; without --key,
+/// authenticate with the key already in the slot only if `always`
+fn authenticate(executor: &mut CommandExecutor, reader: &mut PcscReader<DynBackend>, args: &KeyArgs, block: u16, always: bool) -> Result<()> {
+    if let Some(key) = &args.key {
+        let key = parse_hex(key).context("Failed to parse key hex string")?;
+        storage_command(executor, reader, StorageCommand::LoadKey { key_number: args.key_number, key, non_volatile: false })?;
+    } else if !always {
+        return Ok(());
+    }
+    let command = StorageCommand::Authenticate { block, key_type: args.key_type, key_number: args.key_number };
+    storage_command(executor, reader, command)?;
+    Ok(())
+}
+
+/// Send a storage card command and require it to succeed
+fn storage_command(executor: &mut CommandExecutor, reader: &mut PcscReader<DynBackend>, command: StorageCommand) -> Result<StorageResult> {
+    let name = command.name();
+    let result = executor.storage(reader, command)?;
+    result.check().with_context(|| format!("{} failed", name))?;
+    Ok(result)
+}
+
+fn cmd_pin_verify(opts: &GlobalOptions, reader_name: &str, request: &PinVerify, pin: &PinArgs) -> Result<()> {
+    let (mut reader, reader_name) = opts.connect(reader_name, &pin.connect_options())?;
+    
+    println!("Reader: {}", reader_name);
+    println!("Enter the PIN on the reader's PIN pad...");
+    io::Write::flush(&mut io::stdout())?;
+    let mut executor = opts.new_executor();
+    let result = executor.verify_pin(&mut reader, request);
+    opts.save_recording(&mut executor)?;
+    print_pin_result(&result.context("PIN verification failed")?)
+}
+
+fn cmd_pin_modify(opts: &GlobalOptions, reader_name: &str, request: &PinModify, pin: &PinArgs) -> Result<()> {
+    let (mut reader, reader_name) = opts.connect(reader_name, &pin.connect_options())?;
+    
+    println!("Reader: {}", reader_name);
+    if request.enter_old {
+        println!("Enter the current PIN, then the new one, on the reader's PIN pad...");
+    } else {
+        println!("Enter the new PIN on the reader's PIN pad...");
+    }
+    io::Write::flush(&mut io::stdout())?;
+    let mut executor = opts.new_executor();
+    let result = executor.modify_pin(&mut reader, request);
+    opts.save_recording(&mut executor)?;
+    print_pin_result(&result.context("PIN modification failed")?)
+}
+
+/// Print the outcome of PIN entry, failing with exit code 7 unless the card
+/// accepted the PIN
+fn print_pin_result(result: &PinResult) -> Result<()> {
+    println!("Status: {:02X} {:02X} ({})", result.sw1, result.sw2, result.description());
+    println!("Duration: {}ms", result.control.duration_ms);
+    result.check()
+}
+
+fn cmd_reset(opts: &GlobalOptions, reader_name: &str, conn: &ConnectOptions, cold: bool) -> Result<()> {
+    let (mut reader, reader_name) = opts.connect(reader_name, conn)?;
+    
+    println!("Reader: {}", reader_name);
+    let atr = reader.reset(cold)
+        .context("Failed to reset card")?;
+    
+    println!("{} reset done", if cold { "Cold" } else { "Warm" });
+    println!("ATR: {}", format_hex_spaced(&atr));
+    if let Some(protocol) = reader.protocol() {
+        println!("Protocol: {}", protocol);
+    }
+    
+    Ok(())
+}
+
+fn cmd_script(opts: &GlobalOptions, file_path: &str, reader_name: &str, conn: &ConnectOptions, open: &[String], continue_on_error: bool) -> Result<()> {
+    let mut sessions = opts.open_sessions()?;
+    let reader_name = resolve_reader_name(sessions.readers(), reader_name)?;
+    wait_for_card(sessions.readers(), &reader_name, conn.wait)?;
+    sessions.connect(DEFAULT_SESSION, &reader_name, conn.mode, conn.protocol)?;
+    for spec in open {
+        let (alias, name) = parse_session_spec(spec)?;
+        open_session(&mut sessions, alias, name, conn.mode, conn.protocol)?;
+    }
     
     let file = File::open(file_path)
         .with_context(|| format!("Failed to open script file: {}", file_path))?;
     
     let reader_buf = BufReader::new(file);
-    let mut executor = CommandExecutor::new();
+    let mut executor = opts.new_executor();
+    let mut active = DEFAULT_SESSION.to_string();
     let mut line_number = 0;
     let mut errors = 0;
+    let mut stopped_at = None;
     
     println!("Executing script: {}", file_path);
     println!("Reader: {}", reader_name);
+    if let Some(protocol) = sessions.session(DEFAULT_SESSION)?.reader().protocol() {
+        println!("Protocol: {}", protocol);
+    }
+    for session in sessions.sessions().iter().skip(1) {
+        println!("Session {}: {}", session.alias(), session.reader_name());
+    }
     println!();
     
     for line in reader_buf.lines() {
@@ -306,51 +1209,92 @@ fn cmd_script(file_path: &str, reader_name: &str, mode: ShareModeArg, continue_o
         
         println!("Line {}: {}", line_number, line);
         
-        // Parse command (simple format: "transmit <apdu>" or "control <code> <data>")
-        let parts: Vec<&str> = line.split_whitespace().collect();
+        // Parse command (simple format: "transmit <apdu>" or "control <code> <data>"),
+        // optionally prefixed with "@alias" to target another session
+        let (target, command) = split_session_prefix(line);
+        let parts: Vec<&str> = command.split_whitespace().collect();
         if parts.is_empty() {
             continue;
         }
+        let target = target.unwrap_or(&active).to_string();
         
         let result = match parts[0].to_lowercase().as_str() {
-            "transmit" => {
-                if parts.len() < 2 { 
-                    Err(anyhow::anyhow!("Missing APDU for transmit command"))
-                } else {
-                    executor.transmit(&mut reader, parts[1])
-                        .map(|r| (r.response, r.duration_ms))
-                        .map_err(|e| e.into())
+            "use" => {
+                match parts.get(1) {
+                    Some(alias) if sessions.contains(alias) => {
+                        active = alias.to_string();
+                        println!("  Using session {}", alias);
+                        Ok(None)
+                    }
+                    Some(alias) => Err(Error::Script(format!("Unknown session: {}", alias)).into()),
+                    None => Err(Error::Script("Missing session alias".to_string()).into()),
                 }
             }
-            "control" => {
-                if parts.len() < 2 {
-                    Err(anyhow::anyhow!("Missing control code"))
+            "open" => {
+                if parts.len() < 3 {
+                    Err(Error::Script("Usage: open <alias> <reader>".to_string()).into())
                 } else {
-                    let code = parse_control_code(parts[1])?;
-                    let data = parts.get(2).unwrap_or(&"");
-                    executor.control(&mut reader, code, data)
-                        .map(|r| (r.output, r.duration_ms))
-                        .map_err(|e| e.into())
+                    let reader_name = parts[2..].join(" ");
+                    open_session(&mut sessions, parts[1], &reader_name, conn.mode, conn.protocol)
+                        .map(|name| {
+                            println!("  Session {} opened on {}", parts[1], name);
+                            None
+                        })
                 }
             }
-            _ => Err(anyhow::anyhow!("Unknown command: {}", parts[0]))
+            "close" => {
+                match parts.get(1) {
+                    Some(alias) if *alias == active => Err(Error::Script(format!("Cannot close the active session: {}", alias)).into()),
+                    Some(alias) => sessions.disconnect(alias, Disposition::LeaveCard)
+                        .map(|_| {
+                            println!("  Session {} closed", alias);
+                            None
+                        }),
+                    None => Err(Error::Script("Missing session alias".to_string()).into()),
+                }
+            }
+            "expect" if parts.len() > 1 => {
+                let expected = parts[1..].concat();
+                expect_status_word(&executor, &expected)
+                    .map(|_| {
+                        println!("  Status word matches {}", expected);
+                        None
+                    })
+            }
+            "expect" => Err(Error::Script("Missing status word".to_string()).into()),
+            _ => sessions.session_mut(&target)
+                .and_then(|session| run_script_command(session, &mut executor, &parts)),
         };
         
         match result {
-            Ok((response, duration)) => {
+            Ok(Some((response, duration))) => {
                 println!("  Response: {} ({}ms)", format_hex_spaced(&response), duration);
             }
+            Ok(None) => {}
             Err(e) => {
                 errors += 1;
-                println!("  ERROR: {}", e);
-                if !continue_on_error {
-                    bail!("Script execution stopped due to error on line {}", line_number);
+                println!("  ERROR: {:#}", e);
+                if !continue_on_error || is_cancelled(&e) {
+                    stopped_at = Some((line_number, e));
+                    break;
                 }
             }
         }
         println!();
     }
     
+    for session in sessions.sessions_mut() {
+        if session.reader().in_transaction() {
+            log::warn!("Script left a transaction open on session {}, ending it", session.alias());
+            session.reader_mut().end_transaction(Disposition::LeaveCard)?;
+        }
+    }
+    
+    opts.save_recording(&mut executor)?;
+    if let Some((line_number, error)) = stopped_at {
+        return Err(error.context(format!("Script execution stopped due to error on line {}", line_number)));
+    }
+    
     println!("Script execution completed.");
     println!("Total lines processed: {}", line_number);
     if errors > 0 {
@@ -360,86 +1304,221 @@ fn cmd_script(file_path: &str, reader_name: &str, mode: ShareModeArg, continue_o
     Ok(())
 }
 
-fn cmd_interactive(reader_name: Option<&str>) -> Result<()> {
-    let mut reader = PcscReader::new()
-        .context("Failed to initialize PCSC")?;
+/// Run one script command against a session, returning the response and
+/// duration of exchanges
+fn run_script_command<B: PcscBackend>(session: &mut ReaderSession<B>, executor: &mut CommandExecutor, parts: &[&str]) -> Result<Option<(Vec<u8>, u64)>> {
+    match parts[0].to_lowercase().as_str() {
+        "transmit" => {
+            if parts.len() < 2 { 
+                Err(Error::Script("Missing APDU for transmit command".to_string()).into())
+            } else {
+                executor.transmit(session.reader_mut(), parts[1])
+                    .map(|r| Some((r.response, r.duration_ms)))
+            }
+        }
+        "control" => {
+            if parts.len() < 2 {
+                Err(Error::Script("Missing control code".to_string()).into())
+            } else {
+                let data = parts.get(2).unwrap_or(&"");
+                parse_control_code(parts[1])
+                    .and_then(|code| executor.control(session.reader_mut(), code, data))
+                    .map(|r| Some((r.output, r.duration_ms)))
+            }
+        }
+        "wait-card" => {
+            parse_wait_timeout(parts.get(1).copied())
+                .and_then(|timeout| session.reader().wait_for_card(session.reader_name(), timeout))
+                .and_then(|atr| {
+                    println!("  Card present (ATR: {})", format_hex_spaced(&atr));
+                    // The previous connection does not survive a card swap
+                    session.reopen()
+                })
+                .map(|_| None)
+        }
+        "wait-removal" => {
+            parse_wait_timeout(parts.get(1).copied())
+                .and_then(|timeout| session.reader().wait_for_removal(session.reader_name(), timeout))
+                .and_then(|_| {
+                    println!("  Card removed");
+                    session.reader_mut().disconnect(Disposition::LeaveCard)
+                })
+                .map(|_| None)
+        }
+        "reset" => {
+            parse_reset_kind(parts.get(1).copied())
+                .and_then(|cold| session.reader_mut().reset(cold))
+                .map(|atr| {
+                    println!("  ATR: {}", format_hex_spaced(&atr));
+                    None
+                })
+        }
+        "reconnect" => {
+            let (share_mode, protocol) = (session.share_mode(), session.protocol_selection());
+            session.reader_mut().reconnect(share_mode, protocol, Disposition::LeaveCard)
+                .map(|atr| {
+                    println!("  ATR: {}", format_hex_spaced(&atr));
+                    None
+                })
+        }
+        "begin" => {
+            session.reader_mut().begin_transaction()
+                .map(|_| {
+                    println!("  Transaction started");
+                    None
+                })
+        }
+        "end" => {
+            parse_disposition(parts.get(1).copied())
+                .and_then(|disposition| session.reader_mut().end_transaction(disposition))
+                .map(|_| {
+                    println!("  Transaction ended");
+                    None
+                })
+        }
+        _ => Err(Error::Script(format!("Unknown command: {}", parts[0])).into())
+    }
+}
+
+fn cmd_interactive(opts: &GlobalOptions, reader_name: Option<&str>, protocol: ProtocolSelection, open: &[String]) -> Result<()> {
+    let mut sessions = opts.open_sessions()?;
     
     let reader_name = if let Some(name) = reader_name {
-        resolve_reader_name(&reader, name)?
+        resolve_reader_name(sessions.readers(), name)?
     } else {
         // Let user select reader interactively
-        select_reader_interactive(&reader)?
+        select_reader_interactive(sessions.readers())?
     };
     
-    reader.connect(&reader_name, ShareMode::Shared)
-        .with_context(|| format!("Failed to connect to reader: {}", reader_name))?;
+    sessions.connect(DEFAULT_SESSION, &reader_name, ShareMode::Shared, protocol)?;
+    for spec in open {
+        let (alias, name) = parse_session_spec(spec)?;
+        open_session(&mut sessions, alias, name, ShareMode::Shared, protocol)?;
+    }
     
-    let mut executor = CommandExecutor::new();
+    let mut executor = opts.new_executor();
+    let mut active = DEFAULT_SESSION.to_string();
     
     println!("PCSC Tester - Interactive Mode");
-    println!("Connected to: {}", reader_name);
-    println!("Commands: transmit <apdu>, control <code> [data], history, clear, help, quit");
+    match sessions.session(DEFAULT_SESSION)?.reader().protocol() {
+        Some(protocol) => println!("Connected to: {} ({})", reader_name, protocol),
+        None => println!("Connected to: {}", reader_name),
+    }
+    for session in sessions.sessions().iter().skip(1) {
+        println!("Session {}: {}", session.alias(), session.reader_name());
+    }
+    println!("Commands: transmit <apdu>, control <code> [data], reset [cold], reconnect, transaction, open, use, sessions, history, clear, help, quit");
     println!();
     
+    // Tell the user when the reader of a session is unplugged or comes back,
+    // without waiting for the next command to fail
+    let session_readers = Arc::new(Mutex::new(session_readers_of(&sessions)));
+    let watched = Arc::clone(&session_readers);
+    PcscReader::spawn_watch(&opts.backend, opts.scope, opts.group.clone(), move |event| {
+        let aliases: Vec<String> = watched.lock().unwrap().iter()
+            .filter(|(_, reader)| *reader == event.reader)
+            .map(|(alias, _)| alias.clone())
+            .collect();
+        match event.kind {
+            ReaderEventKind::ReaderDetached if !aliases.is_empty() => {
+                println!("\nNotice: reader {} disconnected (session {})", event.reader, aliases.join(", "));
+            }
+            ReaderEventKind::ReaderAttached if !aliases.is_empty() => {
+                println!("\nNotice: reader {} reattached (session {})", event.reader, aliases.join(", "));
+            }
+            _ => {}
+        }
+        true
+    });
+    
     let stdin = io::stdin();
     loop {
-        print!("> ");
+        *session_readers.lock().unwrap() = session_readers_of(&sessions);
+        if sessions.sessions().len() > 1 {
+            print!("{}> ", active);
+        } else {
+            print!("> ");
+        }
         io::Write::flush(&mut io::stdout()).unwrap();
         
         let mut input = String::new();
-        stdin.read_line(&mut input).context("Failed to read input")?;
+        if stdin.read_line(&mut input).context("Failed to read input")? == 0 {
+            // End of input
+            break;
+        }
         
         let input = input.trim();
         if input.is_empty() {
             continue;
         }
+        // Ctrl-C only cancels the command it interrupted
+        sessions.cancel_token().reset();
+        
+        let (target, command) = split_session_prefix(input);
+        let parts: Vec<&str> = command.split_whitespace().collect();
+        if parts.is_empty() {
+            continue;
+        }
+        let target = target.unwrap_or(&active).to_string();
         
-        let parts: Vec<&str> = input.split_whitespace().collect();
         match parts[0].to_lowercase().as_str() {
             "quit" | "exit" | "q" => break,
             "help" | "h" => {
                 println!("Available commands:");
                 println!("  transmit <apdu>     - Send APDU command");
                 println!("  control <code> [data] - Send control command");
+                println!("  reset [cold]        - Warm (or cold) reset the card");
+                println!("  reconnect           - Reconnect without resetting the card");
+                println!("  transaction begin   - Begin a transaction (no other application can use the card)");
+                println!("  transaction end [leave|reset|unpower|eject] - End the transaction");
+                println!("  open <alias> <reader> - Connect to another reader as session <alias>");
+                println!("  close <alias>       - Close a session");
+                println!("  use <alias>         - Send the following commands to session <alias>");
+                println!("  @<alias> <command>  - Send a single command to session <alias>");
+                println!("  sessions            - List open sessions");
                 println!("  history             - Show command history");
                 println!("  clear               - Clear command history");
                 println!("  help                - Show this help");
                 println!("  quit                - Exit interactive mode");
             }
-            "transmit" | "t" => {
-                if parts.len() < 2 {
-                    println!("Error: Missing APDU");
-                    continue;
-                }
-                
-                match executor.transmit(&mut reader, parts[1]) {
-                    Ok(result) => {
-                        println!("Response: {}", format_hex_spaced(&result.response));
-                        println!("Status: {:02X} {:02X} ({})", result.sw1, result.sw2,
-                                 describe_status_word(result.sw1, result.sw2));
-                        println!("Duration: {}ms", result.duration_ms);
+            "open" => {
+                if parts.len() < 3 {
+                    println!("Error: Usage: open <alias> <reader>");
+                } else {
+                    let reader_name = parts[2..].join(" ");
+                    match open_session(&mut sessions, parts[1], &reader_name, ShareMode::Shared, protocol) {
+                        Ok(name) => println!("Session {} opened on {}", parts[1], name),
+                        Err(e) => println!("Error: {:#}", e),
                     }
-                    Err(e) => println!("Error: {}", e),
                 }
             }
-            "control" | "c" => {
-                if parts.len() < 2 {
-                    println!("Error: Missing control code");
-                    continue;
+            "close" => {
+                match parts.get(1) {
+                    Some(alias) if *alias == active => println!("Error: Cannot close the active session: {}", alias),
+                    Some(alias) => match sessions.disconnect(alias, Disposition::LeaveCard) {
+                        Ok(()) => println!("Session {} closed", alias),
+                        Err(e) => println!("Error: {:#}", e),
+                    },
+                    None => println!("Error: Missing session alias"),
                 }
-                
-                match parse_control_code(parts[1]) {
-                    Ok(code) => {
-                        let data = parts.get(2).unwrap_or(&"");
-                        match executor.control(&mut reader, code, data) {
-                            Ok(result) => {
-                                println!("Response: {}", format_hex_spaced(&result.output));
-                                println!("Duration: {}ms", result.duration_ms);
-                            }
-                            Err(e) => println!("Error: {}", e),
-                        }
+            }
+            "use" => {
+                match parts.get(1) {
+                    Some(alias) if sessions.contains(alias) => {
+                        active = alias.to_string();
+                        println!("Using session {}", alias);
                     }
-                    Err(e) => println!("Error: {}", e),
+                    Some(alias) => println!("Error: Unknown session: {}", alias),
+                    None => println!("Error: Missing session alias"),
+                }
+            }
+            "sessions" => {
+                for session in sessions.sessions() {
+                    let marker = if session.alias() == active { "*" } else { " " };
+                    let protocol = session.reader().protocol()
+                        .map(|p| format!(" ({})", p))
+                        .unwrap_or_default();
+                    println!(" {} {}: {}{}", marker, session.alias(), session.reader_name(), protocol);
                 }
             }
             "history" => {
@@ -452,10 +1531,12 @@ fn cmd_interactive(reader_name: Option<&str>) -> Result<()> {
                         let status = if cmd.success { "OK" } else { "ERROR" };
                         let cmd_type = match &cmd.command_type {
                             crate::core::commands::CommandType::Transmit => "TRANSMIT",
-                            crate::core::commands::CommandType::Control { code } => &format!("CONTROL(0x{:X})", code),
+                            crate::core::commands::CommandType::Control { code } => &format!("CONTROL({})", format_control_code(*code)),
+                            crate::core::commands::CommandType::Recovery { attempt, cause } => &format!("RECOVERY #{} after {}", attempt, cause),
                         };
-                        println!("  [{}] {} {} - {} ({}ms)", i + 1, cmd.timestamp.format("%H:%M:%S"), 
-                                 cmd_type, status, cmd.duration_ms);
+                        let protocol = cmd.protocol.map(|p| format!("{}, ", p)).unwrap_or_default();
+                        println!("  [{}] {} {} - {} ({}{}ms)", i + 1, cmd.timestamp.format("%H:%M:%S"), 
+                                 cmd_type, status, protocol, cmd.duration_ms);
                     }
                 }
             }
@@ -463,38 +1544,202 @@ fn cmd_interactive(reader_name: Option<&str>) -> Result<()> {
                 executor.clear_history();
                 println!("Command history cleared");
             }
-            _ => {
-                println!("Unknown command: {}. Type 'help' for available commands.", parts[0]);
-            }
+            _ => match sessions.session_mut(&target) {
+                Ok(session) => run_interactive_command(session, &mut executor, &parts),
+                Err(e) => println!("Error: {:#}", e),
+            },
         }
         println!();
     }
     
+    opts.save_recording(&mut executor)?;
     println!("Goodbye!");
     Ok(())
 }
 
-fn resolve_reader_name(reader: &PcscReader, name_or_index: &str) -> Result<String> {
-    // Try to parse as index first
-    if let Ok(index) = name_or_index.parse::<usize>() {
-        let readers = reader.list_readers()?;
-        if index < readers.len() {
-            return Ok(readers[index].name.clone());
-        } else {
-            bail!("Reader index {} out of range (0-{})", index, readers.len() - 1);
+/// Run one interactive command against a session
+fn run_interactive_command<B: PcscBackend>(session: &mut ReaderSession<B>, executor: &mut CommandExecutor, parts: &[&str]) {
+    match parts[0].to_lowercase().as_str() {
+        "transmit" | "t" => {
+            if parts.len() < 2 {
+                println!("Error: Missing APDU");
+                return;
+            }
+            
+            match executor.transmit(session.reader_mut(), parts[1]) {
+                Ok(result) => {
+                    println!("Response: {}", format_hex_spaced(&result.response));
+                    println!("Status: {:02X} {:02X} ({})", result.sw1, result.sw2,
+                             describe_status_word(result.sw1, result.sw2));
+                    println!("Duration: {}ms", result.duration_ms);
+                }
+                Err(e) => println!("Error: {:#}", e),
+            }
+        }
+        "control" | "c" => {
+            if parts.len() < 2 {
+                println!("Error: Missing control code");
+                return;
+            }
+            
+            match parse_control_code(parts[1]) {
+                Ok(code) => {
+                    let data = parts.get(2).unwrap_or(&"");
+                    match executor.control(session.reader_mut(), code, data) {
+                        Ok(result) => {
+                            println!("Response: {}", format_hex_spaced(&result.output));
+                            println!("Duration: {}ms", result.duration_ms);
+                        }
+                        Err(e) => println!("Error: {:#}", e),
+                    }
+                }
+                Err(e) => println!("Error: {:#}", e),
+            }
+        }
+        "reset" | "reconnect" => {
+            let (share_mode, protocol) = (session.share_mode(), session.protocol_selection());
+            let reader = session.reader_mut();
+            let result = if parts[0].eq_ignore_ascii_case("reconnect") {
+                reader.reconnect(share_mode, protocol, Disposition::LeaveCard)
+            } else {
+                parse_reset_kind(parts.get(1).copied())
+                    .and_then(|cold| reader.reset(cold))
+            };
+            match result {
+                Ok(atr) => {
+                    println!("ATR: {}", format_hex_spaced(&atr));
+                    if let Some(protocol) = reader.protocol() {
+                        println!("Protocol: {}", protocol);
+                    }
+                }
+                Err(e) => println!("Error: {:#}", e),
+            }
+        }
+        "transaction" | "tx" => {
+            let reader = session.reader_mut();
+            let result = match parts.get(1).map(|s| s.to_lowercase()).as_deref() {
+                None => {
+                    println!("Transaction: {}", if reader.in_transaction() { "in progress" } else { "none" });
+                    Ok(())
+                }
+                Some("begin") => reader.begin_transaction()
+                    .map(|_| println!("Transaction started")),
+                Some("end") => parse_disposition(parts.get(2).copied())
+                    .and_then(|disposition| reader.end_transaction(disposition))
+                    .map(|_| println!("Transaction ended")),
+                Some(other) => Err(anyhow::anyhow!("Unknown transaction command: {} (expected begin or end)", other)),
+            };
+            if let Err(e) = result {
+                println!("Error: {:#}", e);
+            }
+        }
+        _ => {
+            println!("Unknown command: {}. Type 'help' for available commands.", parts[0]);
         }
     }
-    
-    // Use as reader name directly
-    Ok(name_or_index.to_string())
 }
 
-fn select_reader_interactive(reader: &PcscReader) -> Result<String> {
+/// Check the status word of the last APDU sent against a pattern
+fn expect_status_word(executor: &CommandExecutor, expected: &str) -> Result<()> {
+    let last = executor.history().iter().rev()
+        .find(|result| matches!(result.command_type, CommandType::Transmit))
+        .ok_or_else(|| Error::Script("No APDU sent before expect".to_string()))?;
+    match last.output.as_slice() {
+        [.., sw1, sw2] if last.success => check_status_word(*sw1, *sw2, expected),
+        _ => bail!(Error::Script("The last APDU got no status word".to_string())),
+    }
+}
+
+/// Alias and reader name of each open session
+fn session_readers_of<B: PcscBackend>(sessions: &SessionManager<B>) -> Vec<(String, String)> {
+    sessions.sessions().iter()
+        .map(|session| (session.alias().to_string(), session.reader_name().to_string()))
+        .collect()
+}
+
+/// Split an optional leading `@alias` off a script or interactive line
+fn split_session_prefix(line: &str) -> (Option<&str>, &str) {
+    match line.strip_prefix('@') {
+        Some(rest) => {
+            let (alias, command) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            (Some(alias), command.trim_start())
+        }
+        None => (None, line),
+    }
+}
+
+/// Parse an `ALIAS=READER` session option
+fn parse_session_spec(spec: &str) -> Result<(&str, &str)> {
+    spec.split_once('=')
+        .filter(|(alias, reader)| !alias.is_empty() && !reader.is_empty())
+        .ok_or_else(|| Error::Parse(format!("Invalid session: {} (expected ALIAS=READER)", spec)).into())
+}
+
+/// Connect a reader given by name or index as a new session, returning the
+/// reader name
+fn open_session<B: PcscBackend>(sessions: &mut SessionManager<B>, alias: &str, reader_name: &str, share_mode: ShareMode, protocol: ProtocolSelection) -> Result<String> {
+    let reader_name = resolve_reader_name(sessions.readers(), reader_name)?;
+    sessions.connect(alias, &reader_name, share_mode, protocol)?;
+    Ok(reader_name)
+}
+
+/// Wait for a card as requested by a `--wait[=secs]` option
+fn wait_for_card<B: PcscBackend>(reader: &PcscReader<B>, reader_name: &str, wait: Option<Option<u64>>) -> Result<()> {
+    if let Some(secs) = wait {
+        println!("Waiting for card in {}...", reader_name);
+        reader.wait_for_card(reader_name, secs.map(Duration::from_secs))?;
+    }
+    Ok(())
+}
+
+/// Parse the optional timeout (in seconds) of a script wait directive
+fn parse_wait_timeout(arg: Option<&str>) -> Result<Option<Duration>> {
+    arg.map(|secs| {
+        secs.parse::<u64>()
+            .map(Duration::from_secs)
+            .map_err(|e| Error::Parse(format!("Invalid timeout: {}: {}", secs, e)).into())
+    }).transpose()
+}
+
+/// Parse the optional `warm`/`cold` argument of a reset command, returning
+/// whether a cold reset was requested
+fn parse_reset_kind(arg: Option<&str>) -> Result<bool> {
+    match arg.map(str::to_lowercase).as_deref() {
+        None | Some("warm") => Ok(false),
+        Some("cold") => Ok(true),
+        Some(other) => bail!(Error::Parse(format!("Invalid reset type: {} (expected warm or cold)", other))),
+    }
+}
+
+/// Parse the optional disposition of a transaction end, leaving the card
+/// as is by default
+fn parse_disposition(arg: Option<&str>) -> Result<Disposition> {
+    match arg.map(str::to_lowercase).as_deref() {
+        None | Some("leave") => Ok(Disposition::LeaveCard),
+        Some("reset") => Ok(Disposition::ResetCard),
+        Some("unpower") => Ok(Disposition::UnpowerCard),
+        Some("eject") => Ok(Disposition::EjectCard),
+        Some(other) => bail!(Error::Parse(format!("Invalid disposition: {} (expected leave, reset, unpower or eject)", other))),
+    }
+}
+
+/// Resolve a reader argument (index, name or selector) to a reader name
+fn resolve_reader_name<B: PcscBackend>(reader: &PcscReader<B>, selector: &str) -> Result<String> {
+    let selector: ReaderSelector = selector.parse()?;
+    let readers = if selector.needs_reader_list() {
+        reader.list_readers()?
+    } else {
+        Vec::new()
+    };
+    selector.resolve(&readers)
+}
+
+fn select_reader_interactive<B: PcscBackend>(reader: &PcscReader<B>) -> Result<String> {
     let readers = reader.list_readers()
         .context("Failed to list readers")?;
     
     if readers.is_empty() {
-        bail!("No PCSC readers found");
+        bail!(Error::NoReaders);
     }
     
     println!("Available readers:");
@@ -511,10 +1756,10 @@ fn select_reader_interactive(reader: &PcscReader) -> Result<String> {
     stdin.read_line(&mut input).context("Failed to read input")?;
     
     let index: usize = input.trim().parse()
-        .context("Invalid reader index")?;
+        .map_err(|_| Error::Parse(format!("Invalid reader index: {}", input.trim())))?;
     
     if index >= readers.len() {
-        bail!("Reader index {} out of range", index);
+        bail!(Error::ReaderNotFound(format!("Reader index {} out of range", index)));
     }
     
     Ok(readers[index].name.clone())
@@ -1,3 +1,3 @@
-/// CLI interface module
+//! CLI interface module
 
-pub mod commands;
\ No newline at end of file
+pub mod commands;
@@ -0,0 +1,235 @@
+//! Command APDU structure (ISO/IEC 7816-4)
+//!
+//! A command APDU is a 4-byte header followed by optional Lc, data and Le
+//! fields. Short APDUs carry up to 255 data bytes and expect up to 256
+//! response bytes; extended-length APDUs, flagged by a `00` byte after the
+//! header, carry up to 65535 bytes and expect up to 65536. The four cases:
+//!
+//! | Case | Short             | Extended                  |
+//! |------|-------------------|---------------------------|
+//! | 1    | `CLA INS P1 P2`   |                           |
+//! | 2    | header `Le`       | header `00 Le Le`         |
+//! | 3    | header `Lc data`  | header `00 Lc Lc data`    |
+//! | 4    | header `Lc data Le` | header `00 Lc Lc data Le Le` |
+//!
+//! A zero Le means the maximum: 256 for short APDUs and 65536 for extended
+//! ones.
+
+use anyhow::{Result, bail};
+
+use crate::core::error::Error;
+use crate::core::utils::format_hex;
+
+/// Largest data field of a short APDU
+pub const MAX_SHORT_DATA: usize = 255;
+
+/// Largest data field of an extended-length APDU
+pub const MAX_EXTENDED_DATA: usize = 65535;
+
+/// Largest response data of an extended-length APDU, status word excluded
+pub const MAX_EXTENDED_RESPONSE: usize = 65536;
+
+/// Layout of a command APDU: its case, whether it is extended and the
+/// lengths it announces
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApduLayout {
+    /// ISO 7816-4 case, 1 to 4
+    pub case: u8,
+    /// Extended-length encoding
+    pub extended: bool,
+    /// Length of the data field
+    pub lc: usize,
+    /// Expected response length, if the APDU has an Le field
+    pub le: Option<usize>,
+}
+
+impl ApduLayout {
+    /// Decode the layout of an APDU, checking that the Lc and Le fields are
+    /// consistent with its length
+    pub fn parse(apdu: &[u8]) -> Result<Self> {
+        let invalid = |message: String| -> Result<Self> { bail!(Error::Parse(message)) };
+        if apdu.len() < 4 {
+            return invalid(format!("APDU too short: {} bytes, the header takes 4", apdu.len()));
+        }
+        let body = &apdu[4..];
+
+        let layout = match body {
+            [] => Self { case: 1, extended: false, lc: 0, le: None },
+            [le] => Self { case: 2, extended: false, lc: 0, le: Some(short_le(*le)) },
+            [0, rest @ ..] if rest.len() >= 2 => {
+                let length = u16::from_be_bytes([rest[0], rest[1]]) as usize;
+                let rest = &rest[2..];
+                match rest.len() {
+                    0 => Self { case: 2, extended: true, lc: 0, le: Some(extended_le(length)) },
+                    _ if length == 0 => return invalid("Extended Lc must not be 0000".to_string()),
+                    n if n == length => Self { case: 3, extended: true, lc: length, le: None },
+                    n if n == length + 2 => Self {
+                        case: 4,
+                        extended: true,
+                        lc: length,
+                        le: Some(extended_le(u16::from_be_bytes([rest[length], rest[length + 1]]) as usize)),
+                    },
+                    n => return invalid(format!(
+                        "Extended Lc announces {} data bytes but the APDU carries {} bytes after it \
+                         (expected {} or {} with a 2-byte Le)", length, n, length, length + 2)),
+                }
+            }
+            [0, ..] => return invalid(format!(
+                "Truncated extended length field: {}", format_hex(body))),
+            [lc, rest @ ..] => {
+                let lc = *lc as usize;
+                match rest.len() {
+                    n if n == lc => Self { case: 3, extended: false, lc, le: None },
+                    n if n == lc + 1 => Self { case: 4, extended: false, lc, le: Some(short_le(rest[lc])) },
+                    n => return invalid(format!(
+                        "Lc announces {} data bytes but the APDU carries {} bytes after it \
+                         (expected {} or {} with Le)", lc, n, lc, lc + 1)),
+                }
+            }
+        };
+        Ok(layout)
+    }
+
+    /// Size of the buffer needed for the response: expected data plus the
+    /// status word
+    pub fn response_capacity(&self) -> usize {
+        self.le.unwrap_or(0) + 2
+    }
+}
+
+/// Check whether an APDU uses the extended-length encoding
+///
+/// Only the `00` marker after the header is looked at; use
+/// [`ApduLayout::parse`] to check the whole encoding.
+pub fn is_extended(apdu: &[u8]) -> bool {
+    apdu.len() > 5 && apdu[4] == 0
+}
+
+/// Check the encoding of an extended-length APDU
+///
+/// Short APDUs are left alone so that malformed commands can still be sent
+/// on purpose; an extended one with inconsistent lengths is almost always a
+/// typo, and readers reject it with an unhelpful error.
+pub fn validate_extended(apdu: &[u8]) -> Result<()> {
+    if is_extended(apdu) {
+        ApduLayout::parse(apdu)?;
+    }
+    Ok(())
+}
+
+/// Check whether a PC/SC error is how readers and drivers without
+/// extended-length support typically reject an extended APDU
+pub fn is_extended_rejection(error: &pcsc::Error) -> bool {
+    matches!(error,
+        pcsc::Error::InsufficientBuffer
+        | pcsc::Error::InvalidParameter
+        | pcsc::Error::InvalidValue
+        | pcsc::Error::NotTransacted
+        | pcsc::Error::CommError
+        | pcsc::Error::UnsupportedFeature)
+}
+
+/// Encode a command APDU, choosing the extended encoding only when the data
+/// or the expected response does not fit a short one
+///
+/// `le` is the expected response length, `None` for no Le field; 256 and
+/// 65536 are encoded as a zero Le.
+pub fn encode(cla: u8, ins: u8, p1: u8, p2: u8, data: &[u8], le: Option<usize>) -> Result<Vec<u8>> {
+    if data.len() > MAX_EXTENDED_DATA {
+        bail!(Error::Parse(format!("APDU data too long: {} bytes, at most {}", data.len(), MAX_EXTENDED_DATA)));
+    }
+    if let Some(le) = le {
+        if le == 0 || le > MAX_EXTENDED_RESPONSE {
+            bail!(Error::Parse(format!("Invalid Le: {}, expected 1 to {}", le, MAX_EXTENDED_RESPONSE)));
+        }
+    }
+    let extended = data.len() > MAX_SHORT_DATA || le.is_some_and(|le| le > 256);
+
+    let mut apdu = vec![cla, ins, p1, p2];
+    if extended {
+        apdu.push(0);
+        if !data.is_empty() {
+            apdu.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            apdu.extend_from_slice(data);
+        }
+        if let Some(le) = le {
+            apdu.extend_from_slice(&((le % 65536) as u16).to_be_bytes());
+        }
+    } else {
+        if !data.is_empty() {
+            apdu.push(data.len() as u8);
+            apdu.extend_from_slice(data);
+        }
+        if let Some(le) = le {
+            apdu.push((le % 256) as u8);
+        }
+    }
+    Ok(apdu)
+}
+
+fn short_le(le: u8) -> usize {
+    if le == 0 { 256 } else { le as usize }
+}
+
+fn extended_le(le: usize) -> usize {
+    if le == 0 { MAX_EXTENDED_RESPONSE } else { le }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_layouts() {
+        let layout = |apdu: &[u8]| ApduLayout::parse(apdu).unwrap();
+        assert_eq!(layout(&[0x00, 0x84, 0x00, 0x00]), ApduLayout { case: 1, extended: false, lc: 0, le: None });
+        assert_eq!(layout(&[0x00, 0xB0, 0x00, 0x00, 0x00]).le, Some(256));
+        assert_eq!(layout(&[0x00, 0xA4, 0x04, 0x00, 0x02, 0x3F, 0x00]).case, 3);
+        assert_eq!(layout(&[0x00, 0xA4, 0x04, 0x00, 0x02, 0x3F, 0x00, 0x10]),
+                   ApduLayout { case: 4, extended: false, lc: 2, le: Some(16) });
+        assert!(ApduLayout::parse(&[0x00, 0xA4, 0x04, 0x00, 0x03, 0x3F, 0x00]).is_err());
+        assert!(ApduLayout::parse(&[0x00, 0xA4, 0x04]).is_err());
+    }
+
+    #[test]
+    fn test_extended_layouts() {
+        let apdu = [0x00, 0xCB, 0x3F, 0xFF, 0x00, 0x00, 0x00];
+        assert_eq!(ApduLayout::parse(&apdu).unwrap(), ApduLayout { case: 2, extended: true, lc: 0, le: Some(65536) });
+        assert_eq!(ApduLayout::parse(&apdu).unwrap().response_capacity(), 65538);
+
+        let mut apdu = vec![0x00, 0xDB, 0x3F, 0xFF, 0x00, 0x01, 0x2C];
+        apdu.extend(vec![0xAB; 300]);
+        assert_eq!(ApduLayout::parse(&apdu).unwrap(), ApduLayout { case: 3, extended: true, lc: 300, le: None });
+        apdu.extend([0x08, 0x00]);
+        assert_eq!(ApduLayout::parse(&apdu).unwrap().le, Some(2048));
+
+        apdu.pop();
+        let error = ApduLayout::parse(&apdu).unwrap_err().to_string();
+        assert!(error.contains("Extended Lc announces 300 data bytes"), "{}", error);
+        assert!(ApduLayout::parse(&[0x00, 0xCB, 0x3F, 0xFF, 0x00, 0x00, 0x00, 0x01]).is_err());
+        assert!(ApduLayout::parse(&[0x00, 0xCB, 0x3F, 0xFF, 0x00, 0x01]).is_err());
+    }
+
+    #[test]
+    fn test_validate_extended() {
+        assert!(is_extended(&[0x00, 0xCB, 0x3F, 0xFF, 0x00, 0x00, 0x00]));
+        assert!(!is_extended(&[0x00, 0xB0, 0x00, 0x00, 0x00]));
+        // Malformed short APDUs are sent as given
+        assert!(validate_extended(&[0x00, 0xA4, 0x04, 0x00, 0x05, 0x3F]).is_ok());
+        assert!(validate_extended(&[0x00, 0xCB, 0x3F, 0xFF, 0x00, 0x00, 0x05, 0x01]).is_err());
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode(0x00, 0xB0, 0x00, 0x00, &[], Some(256)).unwrap(), vec![0x00, 0xB0, 0x00, 0x00, 0x00]);
+        assert_eq!(encode(0x00, 0xA4, 0x04, 0x00, &[0x3F, 0x00], None).unwrap(),
+                   vec![0x00, 0xA4, 0x04, 0x00, 0x02, 0x3F, 0x00]);
+        assert_eq!(encode(0x00, 0xCB, 0x3F, 0xFF, &[0x5C, 0x01, 0x7E], Some(65536)).unwrap(),
+                   vec![0x00, 0xCB, 0x3F, 0xFF, 0x00, 0x00, 0x03, 0x5C, 0x01, 0x7E, 0x00, 0x00]);
+
+        let apdu = encode(0x00, 0xDB, 0x3F, 0xFF, &[0x11; 1000], None).unwrap();
+        assert_eq!(ApduLayout::parse(&apdu).unwrap(), ApduLayout { case: 3, extended: true, lc: 1000, le: None });
+        assert!(encode(0x00, 0xB0, 0x00, 0x00, &[], Some(0)).is_err());
+        assert!(encode(0x00, 0xB0, 0x00, 0x00, &[0; 65536], None).is_err());
+    }
+}
@@ -0,0 +1,287 @@
+//! Reader attributes (SCardGetAttrib/SCardSetAttrib) and their decoding
+
+use anyhow::{Result, bail};
+use pcsc::Attribute;
+use serde::{Deserialize, Serialize};
+
+use crate::core::error::Error;
+use crate::core::utils::format_hex_spaced;
+
+/// How the value of an attribute is interpreted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttributeFormat {
+    /// Text, possibly NUL terminated
+    Text,
+    /// Vendor version, DWORD 0xMMmmbbbb
+    Version,
+    /// Channel type and number, DWORD 0xDDDDCCCC
+    Channel,
+    /// Protocol mask
+    Protocols,
+    /// Frequency in kHz
+    Clock,
+    /// Data rate in bps
+    DataRate,
+    /// Plain integer
+    Number,
+    /// Power management support flag
+    PowerManagement,
+    /// Swallowing/eject/capture mechanics
+    Characteristics,
+    /// ICC presence state
+    Presence,
+    /// ICC contacts state
+    InterfaceStatus,
+    /// ICC type determined from the ATR
+    IccType,
+    /// Raw bytes
+    Bytes,
+}
+
+/// A known reader attribute
+#[derive(Debug, Clone, Copy)]
+pub struct AttributeDef {
+    pub attribute: Attribute,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub format: AttributeFormat,
+}
+
+impl AttributeDef {
+    const fn new(attribute: Attribute, name: &'static str, description: &'static str, format: AttributeFormat) -> Self {
+        Self { attribute, name, description, format }
+    }
+}
+
+/// Known attributes, in the order they are reported
+pub const KNOWN_ATTRIBUTES: &[AttributeDef] = &[
+    AttributeDef::new(Attribute::VendorName, "vendor_name", "Vendor name", AttributeFormat::Text),
+    AttributeDef::new(Attribute::VendorIfdType, "vendor_ifd_type", "IFD type", AttributeFormat::Text),
+    AttributeDef::new(Attribute::VendorIfdVersion, "vendor_ifd_version", "IFD version", AttributeFormat::Version),
+    AttributeDef::new(Attribute::VendorIfdSerialNo, "vendor_ifd_serial_no", "IFD serial number", AttributeFormat::Text),
+    AttributeDef::new(Attribute::ChannelId, "channel_id", "Channel ID", AttributeFormat::Channel),
+    AttributeDef::new(Attribute::DeviceFriendlyName, "device_friendly_name", "Device friendly name", AttributeFormat::Text),
+    AttributeDef::new(Attribute::DeviceSystemName, "device_system_name", "Device system name", AttributeFormat::Text),
+    AttributeDef::new(Attribute::DeviceUnit, "device_unit", "Device unit", AttributeFormat::Number),
+    AttributeDef::new(Attribute::AsyncProtocolTypes, "async_protocol_types", "Asynchronous protocols", AttributeFormat::Protocols),
+    AttributeDef::new(Attribute::SyncProtocolTypes, "sync_protocol_types", "Synchronous protocols", AttributeFormat::Protocols),
+    AttributeDef::new(Attribute::CurrentProtocolType, "current_protocol_type", "Current protocol", AttributeFormat::Protocols),
+    AttributeDef::new(Attribute::DefaultClk, "default_clk", "Default clock", AttributeFormat::Clock),
+    AttributeDef::new(Attribute::MaxClk, "max_clk", "Maximum clock", AttributeFormat::Clock),
+    AttributeDef::new(Attribute::CurrentClk, "current_clk", "Current clock", AttributeFormat::Clock),
+    AttributeDef::new(Attribute::DefaultDataRate, "default_data_rate", "Default data rate", AttributeFormat::DataRate),
+    AttributeDef::new(Attribute::MaxDataRate, "max_data_rate", "Maximum data rate", AttributeFormat::DataRate),
+    AttributeDef::new(Attribute::CurrentF, "current_f", "Current F (clock conversion)", AttributeFormat::Number),
+    AttributeDef::new(Attribute::CurrentD, "current_d", "Current D (bit rate adjustment)", AttributeFormat::Number),
+    AttributeDef::new(Attribute::CurrentN, "current_n", "Current N (guard time)", AttributeFormat::Number),
+    AttributeDef::new(Attribute::CurrentW, "current_w", "Current W (work waiting time)", AttributeFormat::Number),
+    AttributeDef::new(Attribute::MaxIfsd, "max_ifsd", "Maximum IFSD", AttributeFormat::Number),
+    AttributeDef::new(Attribute::CurrentIfsc, "current_ifsc", "Current IFSC", AttributeFormat::Number),
+    AttributeDef::new(Attribute::CurrentIfsd, "current_ifsd", "Current IFSD", AttributeFormat::Number),
+    AttributeDef::new(Attribute::CurrentBwt, "current_bwt", "Current BWT", AttributeFormat::Number),
+    AttributeDef::new(Attribute::CurrentCwt, "current_cwt", "Current CWT", AttributeFormat::Number),
+    AttributeDef::new(Attribute::ExtendedBwt, "extended_bwt", "Extended BWT", AttributeFormat::Number),
+    AttributeDef::new(Attribute::CurrentEbcEncoding, "current_ebc_encoding", "Current EBC encoding", AttributeFormat::Number),
+    AttributeDef::new(Attribute::Maxinput, "maxinput", "Maximum input", AttributeFormat::Number),
+    AttributeDef::new(Attribute::PowerMgmtSupport, "power_mgmt_support", "Power management", AttributeFormat::PowerManagement),
+    AttributeDef::new(Attribute::Characteristics, "characteristics", "Mechanical characteristics", AttributeFormat::Characteristics),
+    AttributeDef::new(Attribute::UserToCardAuthDevice, "user_to_card_auth_device", "User to card authentication device", AttributeFormat::Bytes),
+    AttributeDef::new(Attribute::UserAuthInputDevice, "user_auth_input_device", "User authentication input device", AttributeFormat::Bytes),
+    AttributeDef::new(Attribute::IccPresence, "icc_presence", "Card presence", AttributeFormat::Presence),
+    AttributeDef::new(Attribute::IccInterfaceStatus, "icc_interface_status", "Card contacts", AttributeFormat::InterfaceStatus),
+    AttributeDef::new(Attribute::CurrentIoState, "current_io_state", "Current I/O state", AttributeFormat::Bytes),
+    AttributeDef::new(Attribute::AtrString, "atr_string", "ATR", AttributeFormat::Bytes),
+    AttributeDef::new(Attribute::IccTypePerAtr, "icc_type_per_atr", "Card type", AttributeFormat::IccType),
+    AttributeDef::new(Attribute::DeviceInUse, "device_in_use", "Device in use", AttributeFormat::Number),
+    AttributeDef::new(Attribute::SupressT1IfsRequest, "supress_t1_ifs_request", "Suppress T=1 IFS request", AttributeFormat::Number),
+];
+
+/// Look up the definition of an attribute
+pub fn attribute_def(attribute: Attribute) -> Option<&'static AttributeDef> {
+    KNOWN_ATTRIBUTES.iter().find(|def| def.attribute == attribute)
+}
+
+/// Parse an attribute given by name (e.g. `vendor_name`) or numeric ID,
+/// hex with a `0x` prefix (e.g. `0x10100`) or decimal (e.g. `65792`)
+pub fn parse_attribute(s: &str) -> Result<Attribute> {
+    let name = s.trim().to_lowercase().replace('-', "_");
+    if let Some(def) = KNOWN_ATTRIBUTES.iter().find(|def| def.name == name) {
+        return Ok(def.attribute);
+    }
+    if let Some(id) = parse_attribute_id(&name) {
+        if let Some(def) = KNOWN_ATTRIBUTES.iter().find(|def| def.attribute as u32 == id) {
+            return Ok(def.attribute);
+        }
+        bail!(Error::Parse(format!("Unsupported attribute ID: 0x{:X}", id)));
+    }
+    bail!(Error::Parse(format!("Unknown attribute: {}", s)))
+}
+
+fn parse_attribute_id(s: &str) -> Option<u32> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => s.parse::<u32>().ok(),
+    }
+}
+
+/// Value of an attribute as read from a reader
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttributeValue {
+    pub name: String,
+    pub id: u32,
+    pub raw: Vec<u8>,
+    pub decoded: String,
+}
+
+impl AttributeValue {
+    pub fn new(attribute: Attribute, raw: Vec<u8>) -> Self {
+        let name = attribute_def(attribute).map_or("unknown", |def| def.name);
+        Self {
+            name: name.to_string(),
+            id: attribute as u32,
+            decoded: decode_attribute(attribute, &raw),
+            raw,
+        }
+    }
+}
+
+/// Decode an attribute value for display
+pub fn decode_attribute(attribute: Attribute, data: &[u8]) -> String {
+    let format = attribute_def(attribute).map_or(AttributeFormat::Bytes, |def| def.format);
+    if data.is_empty() {
+        return "(empty)".to_string();
+    }
+
+    let number = read_dword(data);
+    match (format, number) {
+        (AttributeFormat::Text, _) => decode_text(data),
+        (AttributeFormat::Version, Some(v)) => {
+            format!("{}.{}.{}", v >> 24, (v >> 16) & 0xFF, v & 0xFFFF)
+        }
+        (AttributeFormat::Channel, Some(v)) => {
+            let channel_type = match v >> 16 {
+                0x01 => "serial",
+                0x02 => "parallel",
+                0x04 => "PS/2",
+                0x08 => "SCSI",
+                0x10 => "IDE",
+                0x20 => "USB",
+                0xF0..=0xFF => "vendor defined",
+                _ => "unknown",
+            };
+            format!("{} (type 0x{:02X}, channel {})", channel_type, v >> 16, v & 0xFFFF)
+        }
+        (AttributeFormat::Protocols, Some(v)) => decode_protocols(v),
+        (AttributeFormat::Clock, Some(v)) => format!("{} kHz", v),
+        (AttributeFormat::DataRate, Some(v)) => format!("{} bps", v),
+        (AttributeFormat::Number, Some(v)) => format!("{} (0x{:X})", v, v),
+        (AttributeFormat::PowerManagement, Some(v)) => {
+            if v == 0 { "not supported" } else { "supported" }.to_string()
+        }
+        (AttributeFormat::Characteristics, Some(v)) => {
+            let flags: Vec<&str> = [(0x1, "swallowing"), (0x2, "eject"), (0x4, "capture")]
+                .into_iter()
+                .filter_map(|(bit, label)| (v & bit != 0).then_some(label))
+                .collect();
+            if flags.is_empty() { "none".to_string() } else { flags.join(", ") }
+        }
+        (AttributeFormat::Presence, Some(v)) => match v {
+            0 => "not present".to_string(),
+            1 => "present, not swallowed".to_string(),
+            2 => "present and swallowed".to_string(),
+            4 => "confiscated".to_string(),
+            _ => format!("unknown ({})", v),
+        },
+        (AttributeFormat::InterfaceStatus, Some(v)) => {
+            if v == 0 { "contacts inactive" } else { "contacts active" }.to_string()
+        }
+        (AttributeFormat::IccType, Some(v)) => match v {
+            0 => "unknown".to_string(),
+            1 => "ISO 7816 asynchronous".to_string(),
+            2 => "ISO 7816 synchronous (other)".to_string(),
+            3..=6 => format!("ISO 7816-10 synchronous (type {})", v - 2),
+            7 => "ISO 14443 type A".to_string(),
+            8 => "ISO 14443 type B".to_string(),
+            9 => "ISO 15693".to_string(),
+            _ => format!("unknown ({})", v),
+        },
+        _ => format_hex_spaced(data),
+    }
+}
+
+/// Read a little-endian DWORD of up to 4 bytes
+fn read_dword(data: &[u8]) -> Option<u32> {
+    if data.is_empty() || data.len() > 4 {
+        return None;
+    }
+    Some(data.iter().rev().fold(0, |acc, &b| (acc << 8) | b as u32))
+}
+
+fn decode_text(data: &[u8]) -> String {
+    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).trim().to_string()
+}
+
+fn decode_protocols(mask: u32) -> String {
+    let protocols: Vec<&str> = [(0x1, "T=0"), (0x2, "T=1"), (0x4, "RAW"), (0x10000, "RAW"), (0x8, "T=15")]
+        .into_iter()
+        .filter_map(|(bit, label)| (mask & bit != 0).then_some(label))
+        .collect();
+    if protocols.is_empty() {
+        "none".to_string()
+    } else {
+        protocols.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_attribute() {
+        assert_eq!(parse_attribute("vendor_name").unwrap(), Attribute::VendorName);
+        assert_eq!(parse_attribute("Vendor-IFD-Serial-No").unwrap(), Attribute::VendorIfdSerialNo);
+        assert_eq!(parse_attribute("0x00090303").unwrap(), Attribute::AtrString);
+        assert!(parse_attribute("firmware").is_err());
+        assert!(parse_attribute("0x12345678").is_err());
+        assert_eq!(parse_attribute("65792").unwrap(), Attribute::VendorName);
+        assert_eq!(parse_attribute("0X10100").unwrap(), Attribute::VendorName);
+        assert!(parse_attribute("10100").is_err());
+        assert_eq!(parse_attribute("ctl:65792").unwrap_err().to_string(), "Unknown attribute: ctl:65792");
+    }
+
+    #[test]
+    fn test_decode_text_and_version() {
+        assert_eq!(decode_attribute(Attribute::VendorName, b"ACME Corp\0"), "ACME Corp");
+        assert_eq!(decode_attribute(Attribute::VendorIfdVersion, &[0x34, 0x12, 0x05, 0x02]), "2.5.4660");
+        assert_eq!(decode_attribute(Attribute::VendorIfdSerialNo, &[]), "(empty)");
+    }
+
+    #[test]
+    fn test_decode_numbers() {
+        assert_eq!(decode_attribute(Attribute::ChannelId, &[0x00, 0x00, 0x20, 0x00]), "USB (type 0x20, channel 0)");
+        assert_eq!(decode_attribute(Attribute::CurrentProtocolType, &[0x02, 0x00, 0x00, 0x00]), "T=1");
+        assert_eq!(decode_attribute(Attribute::AsyncProtocolTypes, &[0x03, 0x00, 0x00, 0x00]), "T=0, T=1");
+        assert_eq!(decode_attribute(Attribute::DefaultClk, &[0xA0, 0x0F, 0x00, 0x00]), "4000 kHz");
+        assert_eq!(decode_attribute(Attribute::MaxIfsd, &[0xFE]), "254 (0xFE)");
+        assert_eq!(decode_attribute(Attribute::IccPresence, &[0x01]), "present, not swallowed");
+        assert_eq!(decode_attribute(Attribute::IccInterfaceStatus, &[0x00]), "contacts inactive");
+        assert_eq!(decode_attribute(Attribute::Characteristics, &[0x00, 0x00, 0x00, 0x00]), "none");
+    }
+
+    #[test]
+    fn test_decode_bytes() {
+        assert_eq!(decode_attribute(Attribute::AtrString, &[0x3B, 0x02, 0x14, 0x50]), "3B 02 14 50");
+        // Values too long for a DWORD are shown as bytes
+        assert_eq!(decode_attribute(Attribute::CurrentClk, &[1, 2, 3, 4, 5]), "01 02 03 04 05");
+    }
+
+    #[test]
+    fn test_attribute_value() {
+        let value = AttributeValue::new(Attribute::VendorName, b"ACME".to_vec());
+        assert_eq!(value.name, "vendor_name");
+        assert_eq!(value.id, 0x10100);
+        assert_eq!(value.decoded, "ACME");
+    }
+}
@@ -0,0 +1,580 @@
+//! Pluggable PC/SC backends
+//!
+//! `PcscReader` and `CommandExecutor` talk to smart cards exclusively through
+//! the [`PcscBackend`] and [`CardBackend`] traits. The platform PC/SC service
+//! is one implementation ([`SystemBackend`]); the rule-driven simulator
+//! ([`SimBackend`]) and the session replayer ([`ReplayBackend`]) are others,
+//! and test doubles can provide more.
+
+pub mod system;
+pub mod sim;
+pub mod replay;
+
+use anyhow::{Result, Context};
+use pcsc::{Attribute, Disposition, Protocol, Protocols, Scope, ShareMode, State};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::core::cancel::CancelToken;
+
+pub use system::SystemBackend;
+pub use sim::SimBackend;
+pub use replay::{ReplayBackend, ReplayMode};
+
+/// Reader group every reader belongs to unless configured otherwise
+pub const DEFAULT_READER_GROUP: &str = "SCard$DefaultReaders";
+
+/// Pseudo-group containing all readers
+pub const ALL_READERS_GROUP: &str = "SCard$AllReaders";
+
+/// Pseudo-reader whose state changes when a reader is attached or detached
+///
+/// Passing it to `get_status_change` along with real readers makes the call
+/// return on hot-plug as well. Services without plug-and-play notification
+/// report it as an unknown reader.
+pub const PNP_NOTIFICATION: &str = r"\\?PnP?\Notification";
+
+/// Result type returned by backend operations.
+///
+/// Backends report failures with the PC/SC error codes so that callers can
+/// react to conditions like a removed or reset card the same way regardless
+/// of where the card actually lives.
+pub type BackendResult<T> = std::result::Result<T, pcsc::Error>;
+
+/// Handle interrupting a blocking backend or card call from another thread
+pub type Canceller = Arc<dyn Fn() + Send + Sync>;
+
+/// Card communication protocol, in a serializable form
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CardProtocol {
+    T0,
+    T1,
+    Raw,
+}
+
+impl From<Protocol> for CardProtocol {
+    fn from(protocol: Protocol) -> Self {
+        match protocol {
+            Protocol::T0 => CardProtocol::T0,
+            Protocol::T1 => CardProtocol::T1,
+            Protocol::RAW => CardProtocol::Raw,
+        }
+    }
+}
+
+impl From<CardProtocol> for Protocol {
+    fn from(protocol: CardProtocol) -> Self {
+        match protocol {
+            CardProtocol::T0 => Protocol::T0,
+            CardProtocol::T1 => Protocol::T1,
+            CardProtocol::Raw => Protocol::RAW,
+        }
+    }
+}
+
+impl std::fmt::Display for CardProtocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CardProtocol::T0 => write!(f, "T=0"),
+            CardProtocol::T1 => write!(f, "T=1"),
+            CardProtocol::Raw => write!(f, "RAW"),
+        }
+    }
+}
+
+/// Protocols acceptable when connecting, as given on the command line
+/// (`t0`, `t1`, `t0t1` or `raw`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProtocolSelection {
+    T0,
+    T1,
+    /// Either T=0 or T=1, whichever the card and reader negotiate
+    #[default]
+    T0T1,
+    Raw,
+}
+
+impl ProtocolSelection {
+    /// The PC/SC protocol mask for this selection
+    pub fn protocols(self) -> Protocols {
+        match self {
+            ProtocolSelection::T0 => Protocols::T0,
+            ProtocolSelection::T1 => Protocols::T1,
+            ProtocolSelection::T0T1 => Protocols::T0 | Protocols::T1,
+            ProtocolSelection::Raw => Protocols::RAW,
+        }
+    }
+}
+
+impl std::str::FromStr for ProtocolSelection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace(['=', '/', ','], "").as_str() {
+            "t0" => Ok(ProtocolSelection::T0),
+            "t1" => Ok(ProtocolSelection::T1),
+            "t0t1" | "any" => Ok(ProtocolSelection::T0T1),
+            "raw" => Ok(ProtocolSelection::Raw),
+            _ => Err(format!("Invalid protocol: {} (expected t0, t1, t0t1 or raw)", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for ProtocolSelection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProtocolSelection::T0 => write!(f, "t0"),
+            ProtocolSelection::T1 => write!(f, "t1"),
+            ProtocolSelection::T0T1 => write!(f, "t0t1"),
+            ProtocolSelection::Raw => write!(f, "raw"),
+        }
+    }
+}
+
+/// Status of a connected card
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CardStatus {
+    pub reader_name: String,
+    pub protocol: Option<Protocol>,
+    pub atr: Vec<u8>,
+}
+
+/// Tracked state of a reader, mirroring `SCARD_READERSTATE`
+///
+/// `current_state`/`current_count` is what the caller believes; the backend
+/// fills in `event_state`, `event_count` and `atr` with what it observed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReaderState {
+    pub name: String,
+    pub current_state: State,
+    pub current_count: u32,
+    pub event_state: State,
+    pub event_count: u32,
+    pub atr: Vec<u8>,
+}
+
+impl ReaderState {
+    /// Track a reader whose state is not known yet
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            current_state: State::UNAWARE,
+            current_count: 0,
+            event_state: State::UNAWARE,
+            event_count: 0,
+            atr: Vec::new(),
+        }
+    }
+
+    /// Whether the last status change call reported a change for this reader
+    pub fn changed(&self) -> bool {
+        self.event_state.contains(State::CHANGED)
+    }
+
+    /// Accept the reported state as the currently known state
+    pub fn sync_current_state(&mut self) {
+        self.current_state = self.event_state - State::CHANGED;
+        self.current_count = self.event_count;
+    }
+}
+
+/// A source of readers and card connections
+pub trait PcscBackend {
+    /// Handle to a connected card
+    type Card: CardBackend;
+
+    /// List the names of all readers known to the backend
+    fn list_readers(&self) -> BackendResult<Vec<String>>;
+
+    /// List the reader groups known to the backend
+    fn list_reader_groups(&self) -> BackendResult<Vec<String>>;
+
+    /// List the names of the readers belonging to a group
+    fn list_readers_in_group(&self, group: &str) -> BackendResult<Vec<String>>;
+
+    /// Connect to the card in the given reader
+    fn connect(&self, reader_name: &str, share_mode: ShareMode, protocols: Protocols) -> BackendResult<Self::Card>;
+
+    /// Block until the state of one of the readers differs from its
+    /// `current_state`, or until the timeout elapses (`pcsc::Error::Timeout`)
+    fn get_status_change(&self, timeout: Option<Duration>, readers: &mut [ReaderState]) -> BackendResult<()>;
+
+    /// Handle making a `get_status_change` call blocked on another thread
+    /// fail with `pcsc::Error::Cancelled` (`SCardCancel`); calls starting
+    /// afterwards are not affected
+    fn canceller(&self) -> Canceller;
+}
+
+/// A connection to a card (or to the reader itself in direct mode)
+pub trait CardBackend {
+    /// Get the current status of the card
+    fn status(&self) -> BackendResult<CardStatus>;
+
+    /// Send an APDU and return the full response including SW1/SW2
+    fn transmit(&self, apdu: &[u8]) -> BackendResult<Vec<u8>>;
+
+    /// Send a control command to the reader
+    fn control(&self, code: u32, data: &[u8]) -> BackendResult<Vec<u8>>;
+
+    /// Read a reader attribute
+    fn get_attribute(&self, attribute: Attribute) -> BackendResult<Vec<u8>>;
+
+    /// Write a reader attribute
+    fn set_attribute(&self, attribute: Attribute, data: &[u8]) -> BackendResult<()>;
+
+    /// Re-establish the connection, resetting or unpowering the card
+    /// according to `initialization`
+    fn reconnect(&mut self, share_mode: ShareMode, protocols: Protocols, initialization: Disposition) -> BackendResult<()>;
+
+    /// Begin a transaction: until it ends, no other connection can use the
+    /// card. Transactions do not nest.
+    fn begin_transaction(&mut self) -> BackendResult<()>;
+
+    /// End the current transaction, leaving, resetting, unpowering or
+    /// ejecting the card according to `disposition`
+    fn end_transaction(&mut self, disposition: Disposition) -> BackendResult<()>;
+
+    /// Disconnect from the card
+    fn disconnect(self: Box<Self>, disposition: Disposition) -> BackendResult<()>;
+
+    /// Handle making the card operation in progress on another thread fail
+    /// with `pcsc::Error::Cancelled`
+    ///
+    /// PC/SC cannot interrupt a command once sent to the card: the system
+    /// backend stops waiting for the response and resets the card when the
+    /// connection is released.
+    fn canceller(&self) -> Canceller;
+}
+
+/// Type-erased card handle, used by [`DynBackend`]
+pub type DynCard = Box<dyn CardBackend + Send>;
+
+/// Type-erased backend, for choosing the backend at runtime
+///
+/// It is `Sync` so that sessions sharing it can move between threads.
+pub type DynBackend = Box<dyn PcscBackend<Card = DynCard> + Send + Sync>;
+
+/// Backend selection, as given on the command line
+///
+/// Accepted forms are `system` (the platform PC/SC service),
+/// `sim:<rules.json>` (the simulator), and `replay:<session.json>` or
+/// `replay-lookup:<session.json>` (a recorded session, answered in order or
+/// by lookup).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum BackendSpec {
+    #[default]
+    System,
+    Sim(PathBuf),
+    Replay(PathBuf, ReplayMode),
+}
+
+impl std::str::FromStr for BackendSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, arg) = match s.split_once(':') {
+            Some((kind, arg)) => (kind, Some(arg)),
+            None => (s, None),
+        };
+
+        match (kind.to_lowercase().as_str(), arg) {
+            ("system" | "pcsc", None) => Ok(BackendSpec::System),
+            ("sim", Some(path)) if !path.is_empty() => Ok(BackendSpec::Sim(PathBuf::from(path))),
+            ("sim", _) => Err("Simulator backend requires a rules file: sim:<rules.json>".to_string()),
+            ("replay", Some(path)) if !path.is_empty() => {
+                Ok(BackendSpec::Replay(PathBuf::from(path), ReplayMode::Sequential))
+            }
+            ("replay-lookup", Some(path)) if !path.is_empty() => {
+                Ok(BackendSpec::Replay(PathBuf::from(path), ReplayMode::Lookup))
+            }
+            ("replay" | "replay-lookup", _) => {
+                Err("Replay backend requires a session file: replay:<session.json>".to_string())
+            }
+            _ => Err(format!(
+                "Invalid backend: {} (expected 'system', 'sim:<rules.json>' or 'replay:<session.json>')", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for BackendSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackendSpec::System => write!(f, "system"),
+            BackendSpec::Sim(path) => write!(f, "sim:{}", path.display()),
+            BackendSpec::Replay(path, ReplayMode::Sequential) => write!(f, "replay:{}", path.display()),
+            BackendSpec::Replay(path, ReplayMode::Lookup) => write!(f, "replay-lookup:{}", path.display()),
+        }
+    }
+}
+
+impl BackendSpec {
+    /// Instantiate the selected backend, in the user scope
+    pub fn open(&self) -> Result<DynBackend> {
+        self.open_with_scope(Scope::User)
+    }
+
+    /// Instantiate the selected backend; the scope only applies to the
+    /// system backend
+    pub fn open_with_scope(&self, scope: Scope) -> Result<DynBackend> {
+        match self {
+            BackendSpec::System => {
+                let backend = SystemBackend::establish(scope)
+                    .context("Failed to establish PCSC context")?;
+                Ok(boxed(backend))
+            }
+            BackendSpec::Sim(path) => Ok(boxed(SimBackend::from_file(path)?)),
+            BackendSpec::Replay(path, mode) => Ok(boxed(ReplayBackend::from_file(path, *mode)?)),
+        }
+    }
+}
+
+/// Erase the concrete type of a backend
+pub fn boxed<B>(backend: B) -> DynBackend
+where
+    B: PcscBackend + Send + Sync + 'static,
+    B::Card: Send + 'static,
+{
+    Box::new(Boxed(backend))
+}
+
+/// Adapter boxing the card handles of a concrete backend
+struct Boxed<B>(B);
+
+impl<B> PcscBackend for Boxed<B>
+where
+    B: PcscBackend,
+    B::Card: Send + 'static,
+{
+    type Card = DynCard;
+
+    fn list_readers(&self) -> BackendResult<Vec<String>> {
+        self.0.list_readers()
+    }
+
+    fn list_reader_groups(&self) -> BackendResult<Vec<String>> {
+        self.0.list_reader_groups()
+    }
+
+    fn list_readers_in_group(&self, group: &str) -> BackendResult<Vec<String>> {
+        self.0.list_readers_in_group(group)
+    }
+
+    fn connect(&self, reader_name: &str, share_mode: ShareMode, protocols: Protocols) -> BackendResult<Self::Card> {
+        let card = self.0.connect(reader_name, share_mode, protocols)?;
+        Ok(Box::new(card))
+    }
+
+    fn get_status_change(&self, timeout: Option<Duration>, readers: &mut [ReaderState]) -> BackendResult<()> {
+        self.0.get_status_change(timeout, readers)
+    }
+
+    fn canceller(&self) -> Canceller {
+        self.0.canceller()
+    }
+}
+
+impl<B: PcscBackend + ?Sized> PcscBackend for Box<B> {
+    type Card = B::Card;
+
+    fn list_readers(&self) -> BackendResult<Vec<String>> {
+        (**self).list_readers()
+    }
+
+    fn list_reader_groups(&self) -> BackendResult<Vec<String>> {
+        (**self).list_reader_groups()
+    }
+
+    fn list_readers_in_group(&self, group: &str) -> BackendResult<Vec<String>> {
+        (**self).list_readers_in_group(group)
+    }
+
+    fn connect(&self, reader_name: &str, share_mode: ShareMode, protocols: Protocols) -> BackendResult<Self::Card> {
+        (**self).connect(reader_name, share_mode, protocols)
+    }
+
+    fn get_status_change(&self, timeout: Option<Duration>, readers: &mut [ReaderState]) -> BackendResult<()> {
+        (**self).get_status_change(timeout, readers)
+    }
+
+    fn canceller(&self) -> Canceller {
+        (**self).canceller()
+    }
+}
+
+/// Shared backend, letting several reader managers use one context
+impl<B: PcscBackend + ?Sized> PcscBackend for Arc<B> {
+    type Card = B::Card;
+
+    fn list_readers(&self) -> BackendResult<Vec<String>> {
+        (**self).list_readers()
+    }
+
+    fn list_reader_groups(&self) -> BackendResult<Vec<String>> {
+        (**self).list_reader_groups()
+    }
+
+    fn list_readers_in_group(&self, group: &str) -> BackendResult<Vec<String>> {
+        (**self).list_readers_in_group(group)
+    }
+
+    fn connect(&self, reader_name: &str, share_mode: ShareMode, protocols: Protocols) -> BackendResult<Self::Card> {
+        (**self).connect(reader_name, share_mode, protocols)
+    }
+
+    fn get_status_change(&self, timeout: Option<Duration>, readers: &mut [ReaderState]) -> BackendResult<()> {
+        (**self).get_status_change(timeout, readers)
+    }
+
+    fn canceller(&self) -> Canceller {
+        (**self).canceller()
+    }
+}
+
+/// Status change implementation for backends that can observe their reader
+/// states directly, such as the simulator: the states are polled until one
+/// differs from what the caller knows.
+///
+/// `observe` returns the state and ATR of a reader, or `None` if the reader
+/// does not exist. These backends have a fixed set of readers, so the
+/// [`PNP_NOTIFICATION`] pseudo-reader never changes. Cancelling `interrupt`
+/// while polling makes the call fail with `pcsc::Error::Cancelled`.
+pub(crate) fn poll_status_change<F>(timeout: Option<Duration>, readers: &mut [ReaderState], interrupt: &CancelToken, observe: F) -> BackendResult<()>
+where
+    F: Fn(&str) -> Option<(State, Vec<u8>)>,
+{
+    const POLL_INTERVAL: Duration = Duration::from_millis(50);
+    let deadline = timeout.map(|t| Instant::now() + t);
+    // Like SCardCancel, only calls in progress are cancelled
+    interrupt.reset();
+
+    loop {
+        let mut any_changed = false;
+        for reader in readers.iter_mut() {
+            let (state, atr) = if reader.name == PNP_NOTIFICATION {
+                (State::EMPTY, Vec::new())
+            } else {
+                observe(&reader.name).unwrap_or((State::UNKNOWN | State::UNAVAILABLE, Vec::new()))
+            };
+
+            reader.atr = atr;
+            reader.event_count = reader.current_count;
+            if reader.current_state.contains(State::IGNORE) {
+                reader.event_state = State::IGNORE;
+            } else if state != reader.current_state - State::CHANGED {
+                if reader.current_state != State::UNAWARE
+                    && state.contains(State::PRESENT) != reader.current_state.contains(State::PRESENT) {
+                    reader.event_count = reader.current_count.wrapping_add(1);
+                }
+                reader.event_state = state | State::CHANGED;
+                any_changed = true;
+            } else {
+                reader.event_state = state;
+            }
+        }
+
+        if any_changed {
+            return Ok(());
+        }
+
+        let sleep = match deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    return Err(pcsc::Error::Timeout);
+                }
+                remaining.min(POLL_INTERVAL)
+            }
+            None => POLL_INTERVAL,
+        };
+        interrupt.sleep(sleep).map_err(|_| pcsc::Error::Cancelled)?;
+    }
+}
+
+impl CardBackend for DynCard {
+    fn status(&self) -> BackendResult<CardStatus> {
+        (**self).status()
+    }
+
+    fn transmit(&self, apdu: &[u8]) -> BackendResult<Vec<u8>> {
+        (**self).transmit(apdu)
+    }
+
+    fn control(&self, code: u32, data: &[u8]) -> BackendResult<Vec<u8>> {
+        (**self).control(code, data)
+    }
+
+    fn get_attribute(&self, attribute: Attribute) -> BackendResult<Vec<u8>> {
+        (**self).get_attribute(attribute)
+    }
+
+    fn set_attribute(&self, attribute: Attribute, data: &[u8]) -> BackendResult<()> {
+        (**self).set_attribute(attribute, data)
+    }
+
+    fn reconnect(&mut self, share_mode: ShareMode, protocols: Protocols, initialization: Disposition) -> BackendResult<()> {
+        (**self).reconnect(share_mode, protocols, initialization)
+    }
+
+    fn begin_transaction(&mut self) -> BackendResult<()> {
+        (**self).begin_transaction()
+    }
+
+    fn end_transaction(&mut self, disposition: Disposition) -> BackendResult<()> {
+        (**self).end_transaction(disposition)
+    }
+
+    fn disconnect(self: Box<Self>, disposition: Disposition) -> BackendResult<()> {
+        (*self).disconnect(disposition)
+    }
+
+    fn canceller(&self) -> Canceller {
+        (**self).canceller()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backend_spec_parsing() {
+        assert_eq!("system".parse::<BackendSpec>().unwrap(), BackendSpec::System);
+        assert_eq!("PCSC".parse::<BackendSpec>().unwrap(), BackendSpec::System);
+        assert_eq!("sim:rules.json".parse::<BackendSpec>().unwrap(),
+                   BackendSpec::Sim(PathBuf::from("rules.json")));
+
+        assert_eq!("replay:s.json".parse::<BackendSpec>().unwrap(),
+                   BackendSpec::Replay(PathBuf::from("s.json"), ReplayMode::Sequential));
+        assert_eq!("replay-lookup:s.json".parse::<BackendSpec>().unwrap(),
+                   BackendSpec::Replay(PathBuf::from("s.json"), ReplayMode::Lookup));
+
+        assert!("sim".parse::<BackendSpec>().is_err());
+        assert!("replay".parse::<BackendSpec>().is_err());
+        assert!("sim:".parse::<BackendSpec>().is_err());
+        assert!("other".parse::<BackendSpec>().is_err());
+    }
+
+    #[test]
+    fn test_backend_spec_display() {
+        assert_eq!(BackendSpec::System.to_string(), "system");
+        assert_eq!(BackendSpec::Sim(PathBuf::from("a.json")).to_string(), "sim:a.json");
+        assert_eq!(BackendSpec::Replay(PathBuf::from("s.json"), ReplayMode::Lookup).to_string(),
+                   "replay-lookup:s.json");
+    }
+
+    #[test]
+    fn test_protocol_selection() {
+        assert_eq!("t0".parse::<ProtocolSelection>().unwrap(), ProtocolSelection::T0);
+        assert_eq!("T=1".parse::<ProtocolSelection>().unwrap(), ProtocolSelection::T1);
+        assert_eq!("t0t1".parse::<ProtocolSelection>().unwrap(), ProtocolSelection::T0T1);
+        assert_eq!("T=0/T=1".parse::<ProtocolSelection>().unwrap(), ProtocolSelection::T0T1);
+        assert_eq!("raw".parse::<ProtocolSelection>().unwrap(), ProtocolSelection::Raw);
+        assert!("t2".parse::<ProtocolSelection>().is_err());
+
+        assert_eq!(ProtocolSelection::default().protocols(), Protocols::ANY);
+        assert_eq!(ProtocolSelection::T1.protocols(), Protocols::T1);
+        assert_eq!(ProtocolSelection::T0T1.to_string(), "t0t1");
+    }
+}
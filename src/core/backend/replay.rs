@@ -0,0 +1,319 @@
+//! Backend replaying a recorded session
+//!
+//! The recorded reader is presented as a virtual reader holding a card with
+//! the recorded ATR. Exchanges are answered either strictly in the recorded
+//! order, or by looking up the recorded command with the same input.
+
+use anyhow::Result;
+use pcsc::{Attribute, Disposition, Protocols, ShareMode, State};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::{poll_status_change, ALL_READERS_GROUP, DEFAULT_READER_GROUP, BackendResult, Canceller, CardBackend, CardProtocol, CardStatus, PcscBackend, ReaderState};
+use crate::core::cancel::CancelToken;
+use crate::core::commands::{CommandResult, CommandType};
+use crate::core::session::Session;
+use crate::core::utils::{control_function, format_hex};
+
+/// How recorded exchanges are matched against incoming commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReplayMode {
+    /// Commands must arrive in the recorded order
+    #[default]
+    Sequential,
+    /// Commands are answered with the next recorded exchange with the same input
+    Lookup,
+}
+
+/// Replay state shared by the backend and its cards
+#[derive(Debug)]
+struct ReplayState {
+    session: Session,
+    mode: ReplayMode,
+    cursor: Mutex<usize>,
+}
+
+impl ReplayState {
+    fn is_same_command(exchange: &CommandResult, command_type: &CommandType, input: &[u8]) -> bool {
+        let same_type = match (&exchange.command_type, command_type) {
+            (CommandType::Transmit, CommandType::Transmit) => true,
+            // Sessions recorded on another platform have the other
+            // SCARD_CTL_CODE encoding
+            (CommandType::Control { code: a }, CommandType::Control { code: b }) => {
+                a == b || control_function(*a).is_some_and(|function| control_function(*b) == Some(function))
+            }
+            _ => false,
+        };
+        same_type && exchange.input == input
+    }
+
+    fn answer(&self, command_type: CommandType, input: &[u8]) -> BackendResult<Vec<u8>> {
+        let exchanges = &self.session.exchanges;
+        let mut cursor = self.cursor.lock().unwrap_or_else(|e| e.into_inner());
+
+        let index = match self.mode {
+            ReplayMode::Sequential => {
+                let Some(exchange) = exchanges.get(*cursor) else {
+                    log::warn!("Replay session exhausted after {} exchanges", exchanges.len());
+                    return Err(pcsc::Error::RemovedCard);
+                };
+                if !Self::is_same_command(exchange, &command_type, input) {
+                    log::warn!("Replay mismatch at exchange {}: expected {}, got {}",
+                               *cursor + 1, format_hex(&exchange.input), format_hex(input));
+                    return Err(pcsc::Error::InvalidValue);
+                }
+                *cursor
+            }
+            ReplayMode::Lookup => {
+                // Search from the cursor onwards, wrapping around, so repeated
+                // commands get their recorded responses in order
+                let count = exchanges.len();
+                let Some(index) = (0..count)
+                    .map(|offset| (*cursor + offset) % count)
+                    .find(|&i| Self::is_same_command(&exchanges[i], &command_type, input)) else {
+                    log::warn!("No recorded exchange for {}", format_hex(input));
+                    return Err(pcsc::Error::InvalidValue);
+                };
+                index
+            }
+        };
+
+        *cursor = index + 1;
+        let exchange = &exchanges[index];
+        if exchange.success {
+            Ok(exchange.output.clone())
+        } else {
+            log::warn!("Replaying recorded failure: {}", exchange.error.as_deref().unwrap_or("unknown error"));
+            Err(pcsc::Error::UnknownError)
+        }
+    }
+}
+
+/// Backend exposing a recorded session as a virtual reader
+#[derive(Debug, Clone)]
+pub struct ReplayBackend {
+    state: Arc<ReplayState>,
+    /// Interrupts `get_status_change`
+    interrupt: CancelToken,
+}
+
+impl ReplayBackend {
+    /// Replay a session
+    pub fn new(session: Session, mode: ReplayMode) -> Self {
+        Self {
+            state: Arc::new(ReplayState {
+                session,
+                mode,
+                cursor: Mutex::new(0),
+            }),
+            interrupt: CancelToken::new(),
+        }
+    }
+
+    /// Load a session file and replay it
+    pub fn from_file<P: AsRef<Path>>(path: P, mode: ReplayMode) -> Result<Self> {
+        Ok(Self::new(Session::load(path)?, mode))
+    }
+}
+
+/// Connection to the replayed card
+#[derive(Debug)]
+pub struct ReplayCard {
+    state: Arc<ReplayState>,
+}
+
+impl PcscBackend for ReplayBackend {
+    type Card = ReplayCard;
+
+    fn list_readers(&self) -> BackendResult<Vec<String>> {
+        Ok(vec![self.state.session.reader_name.clone()])
+    }
+
+    fn list_reader_groups(&self) -> BackendResult<Vec<String>> {
+        Ok(vec![DEFAULT_READER_GROUP.to_string()])
+    }
+
+    fn list_readers_in_group(&self, group: &str) -> BackendResult<Vec<String>> {
+        match group {
+            DEFAULT_READER_GROUP | ALL_READERS_GROUP => self.list_readers(),
+            _ => Err(pcsc::Error::NoReadersAvailable),
+        }
+    }
+
+    fn connect(&self, reader_name: &str, _share_mode: ShareMode, _protocols: Protocols) -> BackendResult<ReplayCard> {
+        if reader_name != self.state.session.reader_name {
+            return Err(pcsc::Error::UnknownReader);
+        }
+        Ok(ReplayCard { state: Arc::clone(&self.state) })
+    }
+
+    fn get_status_change(&self, timeout: Option<Duration>, readers: &mut [ReaderState]) -> BackendResult<()> {
+        let session = &self.state.session;
+        poll_status_change(timeout, readers, &self.interrupt, |name| {
+            (name == session.reader_name).then(|| (State::PRESENT, session.atr.clone()))
+        })
+    }
+
+    fn canceller(&self) -> Canceller {
+        self.interrupt.canceller()
+    }
+}
+
+impl CardBackend for ReplayCard {
+    fn status(&self) -> BackendResult<CardStatus> {
+        let session = &self.state.session;
+        Ok(CardStatus {
+            reader_name: session.reader_name.clone(),
+            protocol: session.protocol.map(Into::into),
+            atr: session.atr.clone(),
+        })
+    }
+
+    fn transmit(&self, apdu: &[u8]) -> BackendResult<Vec<u8>> {
+        self.state.answer(CommandType::Transmit, apdu)
+    }
+
+    fn control(&self, code: u32, data: &[u8]) -> BackendResult<Vec<u8>> {
+        self.state.answer(CommandType::Control { code }, data)
+    }
+
+    fn get_attribute(&self, attribute: Attribute) -> BackendResult<Vec<u8>> {
+        // Only what the session captured about the card is known
+        let session = &self.state.session;
+        match attribute {
+            Attribute::AtrString => Ok(session.atr.clone()),
+            Attribute::CurrentProtocolType => {
+                let mask: u32 = match session.protocol {
+                    Some(CardProtocol::T0) => 0x1,
+                    Some(CardProtocol::T1) => 0x2,
+                    Some(CardProtocol::Raw) => 0x4,
+                    None => 0,
+                };
+                Ok(mask.to_le_bytes().to_vec())
+            }
+            _ => Err(pcsc::Error::UnsupportedFeature),
+        }
+    }
+
+    fn set_attribute(&self, _attribute: Attribute, _data: &[u8]) -> BackendResult<()> {
+        Err(pcsc::Error::UnsupportedFeature)
+    }
+
+    fn reconnect(&mut self, _share_mode: ShareMode, _protocols: Protocols, _initialization: Disposition) -> BackendResult<()> {
+        // The recorded card keeps its ATR and protocol across resets
+        Ok(())
+    }
+
+    fn begin_transaction(&mut self) -> BackendResult<()> {
+        // Nothing else can reach the replayed card
+        Ok(())
+    }
+
+    fn end_transaction(&mut self, _disposition: Disposition) -> BackendResult<()> {
+        Ok(())
+    }
+
+    fn disconnect(self: Box<Self>, _disposition: Disposition) -> BackendResult<()> {
+        Ok(())
+    }
+
+    fn canceller(&self) -> Canceller {
+        // Recorded exchanges are answered at once
+        Arc::new(|| {})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn exchange(command_type: CommandType, input: &[u8], output: &[u8]) -> CommandResult {
+        CommandResult {
+            timestamp: Utc::now(),
+            command_type,
+            input: input.to_vec(),
+            output: output.to_vec(),
+            success: true,
+            error: None,
+            duration_ms: 5,
+            protocol: None,
+        }
+    }
+
+    fn session() -> Session {
+        let mut session = Session::new();
+        session.reader_name = "Field Reader".to_string();
+        session.atr = vec![0x3B, 0x02, 0x14, 0x50];
+        session.protocol = Some(CardProtocol::T0);
+        session.exchanges = vec![
+            exchange(CommandType::Transmit, &[0x00, 0xA4, 0x04, 0x00], &[0x90, 0x00]),
+            exchange(CommandType::Transmit, &[0x00, 0xB0, 0x00, 0x00], &[0x01, 0x90, 0x00]),
+            exchange(CommandType::Control { code: 0x42000C00 }, &[], &[0xAA]),
+            exchange(CommandType::Transmit, &[0x00, 0xB0, 0x00, 0x00], &[0x02, 0x90, 0x00]),
+        ];
+        session
+    }
+
+    fn connect(mode: ReplayMode) -> ReplayCard {
+        ReplayBackend::new(session(), mode)
+            .connect("Field Reader", ShareMode::Shared, Protocols::ANY)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_replay_status() {
+        let card = connect(ReplayMode::Sequential);
+        let status = card.status().unwrap();
+        assert_eq!(status.atr, vec![0x3B, 0x02, 0x14, 0x50]);
+        assert_eq!(status.protocol, Some(pcsc::Protocol::T0));
+        assert_eq!(card.get_attribute(Attribute::AtrString).unwrap(), vec![0x3B, 0x02, 0x14, 0x50]);
+        assert_eq!(card.get_attribute(Attribute::VendorName), Err(pcsc::Error::UnsupportedFeature));
+    }
+
+    #[test]
+    fn test_replay_sequential() {
+        let card = connect(ReplayMode::Sequential);
+        assert_eq!(card.transmit(&[0x00, 0xA4, 0x04, 0x00]).unwrap(), vec![0x90, 0x00]);
+        assert_eq!(card.transmit(&[0x00, 0xB0, 0x00, 0x00]).unwrap(), vec![0x01, 0x90, 0x00]);
+        assert_eq!(card.transmit(&[0x00, 0xB0, 0x00, 0x00]), Err(pcsc::Error::InvalidValue));
+        assert_eq!(card.control(0x42000C00, &[]).unwrap(), vec![0xAA]);
+        assert_eq!(card.transmit(&[0x00, 0xB0, 0x00, 0x00]).unwrap(), vec![0x02, 0x90, 0x00]);
+        assert_eq!(card.transmit(&[0x00, 0xB0, 0x00, 0x00]), Err(pcsc::Error::RemovedCard));
+    }
+
+    #[test]
+    fn test_replay_lookup() {
+        let card = connect(ReplayMode::Lookup);
+        assert_eq!(card.transmit(&[0x00, 0xB0, 0x00, 0x00]).unwrap(), vec![0x01, 0x90, 0x00]);
+        assert_eq!(card.transmit(&[0x00, 0xB0, 0x00, 0x00]).unwrap(), vec![0x02, 0x90, 0x00]);
+        assert_eq!(card.transmit(&[0x00, 0xA4, 0x04, 0x00]).unwrap(), vec![0x90, 0x00]);
+        assert_eq!(card.control(0x42000C00, &[]).unwrap(), vec![0xAA]);
+        assert_eq!(card.control(0x1234, &[]), Err(pcsc::Error::InvalidValue));
+    }
+
+    #[test]
+    fn test_replay_other_platform_control_codes() {
+        // SCARD_CTL_CODE(3500) as recorded on Windows, replayed with the
+        // pcsc-lite encoding, and the other way round
+        let mut session = session();
+        session.exchanges = vec![
+            exchange(CommandType::Control { code: 0x003136B0 }, &[0x01], &[0xAA]),
+            exchange(CommandType::Control { code: 0x42000DAC }, &[0x02], &[0xBB]),
+        ];
+        let card = ReplayBackend::new(session, ReplayMode::Lookup)
+            .connect("Field Reader", ShareMode::Shared, Protocols::ANY)
+            .unwrap();
+        assert_eq!(card.control(0x42000DAC, &[0x01]).unwrap(), vec![0xAA]);
+        assert_eq!(card.control(0x003136B0, &[0x02]).unwrap(), vec![0xBB]);
+        assert_eq!(card.control(0x42000D48, &[0x01]), Err(pcsc::Error::InvalidValue));
+    }
+
+    #[test]
+    fn test_replay_unknown_reader() {
+        let backend = ReplayBackend::new(session(), ReplayMode::Lookup);
+        assert_eq!(backend.list_readers().unwrap(), vec!["Field Reader".to_string()]);
+        assert!(matches!(backend.connect("Other", ShareMode::Shared, Protocols::ANY), Err(pcsc::Error::UnknownReader)));
+    }
+}
@@ -0,0 +1,728 @@
+//! Rule-driven simulated reader and card
+//!
+//! The simulator exposes a single virtual reader holding a virtual card. The
+//! card answers APDUs according to a list of rules loaded from a JSON file:
+//!
+//! ```json
+//! {
+//!   "reader_name": "Virtual Reader",
+//!   "groups": ["Station 1"],
+//!   "atr": "3B 8F 80 01 80 4F 0C A0 00 00 03 06 03 00 01 00 00 00 00 6A",
+//!   "rules": [
+//!     { "apdu": "00A40400 07 A0000000031010", "sw": "9000" },
+//!     { "apdu": "00B0 ???? *", "response": "48656C6C6F", "sw": "9000" },
+//!     { "apdu": "80CA 0000 00", "mask": "F0FFFFFFFF", "sw": "6A88" },
+//!     { "apdu": "0088 *", "sw": "9000", "delay_ms": 3000 }
+//!   ],
+//!   "controls": [
+//!     { "code": "0x42000C00", "response": "01020304" }
+//!   ],
+//!   "attributes": {
+//!     "vendor_ifd_serial_no": "SN-0001",
+//!     "vendor_ifd_version": "00000201"
+//!   },
+//!   "default_sw": "6D00",
+//!   "extended_length": true
+//! }
+//! ```
+//!
+//! APDU patterns are hex strings where `?` or `X` stands for any nibble and a
+//! trailing `*` matches any number of remaining bytes. An optional `mask` is
+//! ANDed with both the APDU and the pattern before comparing. Rules are tried
+//! in order and the first match wins; unmatched APDUs get `default_sw`. A
+//! rule's `delay_ms` makes the card take that long to answer, e.g. to
+//! exercise timeouts and cancellation.
+//! With `extended_length` set to false, the reader rejects extended-length
+//! APDUs the way CCID drivers do for readers that lack support for them.
+//!
+//! Reader attributes are keyed by name (see `core::attributes`); text
+//! attributes are given as text, all others as hex. The ATR, current protocol
+//! and card presence attributes are derived from the simulated card.
+//!
+//! The reader belongs to `SCard$DefaultReaders` and to the listed `groups`.
+
+use anyhow::{Result, Context, bail};
+use pcsc::{Attribute, Disposition, Protocol, Protocols, ShareMode, State};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::{poll_status_change, ALL_READERS_GROUP, DEFAULT_READER_GROUP, BackendResult, Canceller, CardBackend, CardStatus, PcscBackend, ReaderState};
+use crate::core::apdu;
+use crate::core::attributes::{attribute_def, parse_attribute, AttributeFormat};
+use crate::core::cancel::CancelToken;
+use crate::core::error::Error;
+use crate::core::utils::{parse_hex, parse_control_code};
+
+/// Default name of the virtual reader
+pub const DEFAULT_SIM_READER: &str = "PCSC Tester Virtual Reader";
+
+/// Default ATR of the virtual card
+const DEFAULT_SIM_ATR: &str = "3B 8F 80 01 80 4F 0C A0 00 00 03 06 03 00 01 00 00 00 00 6A";
+
+/// Simulator configuration, as found in a rules file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SimConfig {
+    #[serde(default = "default_reader_name")]
+    pub reader_name: String,
+    #[serde(default)]
+    pub groups: Vec<String>,
+    #[serde(default = "default_atr")]
+    pub atr: String,
+    #[serde(default = "default_card_present")]
+    pub card_present: bool,
+    #[serde(default)]
+    pub rules: Vec<SimRule>,
+    #[serde(default)]
+    pub controls: Vec<SimControlRule>,
+    #[serde(default)]
+    pub attributes: BTreeMap<String, String>,
+    #[serde(default = "default_sw")]
+    pub default_sw: String,
+    #[serde(default = "default_extended_length")]
+    pub extended_length: bool,
+}
+
+/// APDU rule: pattern to match and the response to return
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SimRule {
+    pub apdu: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mask: Option<String>,
+    #[serde(default)]
+    pub response: String,
+    #[serde(default = "default_success_sw")]
+    pub sw: String,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub delay_ms: u64,
+}
+
+/// Control rule: control code and the reader's response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SimControlRule {
+    pub code: String,
+    #[serde(default)]
+    pub response: String,
+}
+
+fn default_reader_name() -> String {
+    DEFAULT_SIM_READER.to_string()
+}
+
+fn default_atr() -> String {
+    DEFAULT_SIM_ATR.to_string()
+}
+
+fn default_card_present() -> bool {
+    true
+}
+
+fn default_sw() -> String {
+    "6D00".to_string()
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+fn default_extended_length() -> bool {
+    true
+}
+
+fn default_success_sw() -> String {
+    "9000".to_string()
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            reader_name: default_reader_name(),
+            groups: Vec::new(),
+            atr: default_atr(),
+            card_present: default_card_present(),
+            rules: Vec::new(),
+            controls: Vec::new(),
+            attributes: BTreeMap::new(),
+            default_sw: default_sw(),
+            extended_length: default_extended_length(),
+        }
+    }
+}
+
+/// APDU pattern with per-nibble wildcards
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApduPattern {
+    value: Vec<u8>,
+    mask: Vec<u8>,
+    open_ended: bool,
+}
+
+impl ApduPattern {
+    /// Parse a pattern such as `00A4 04?? *`
+    pub fn parse(pattern: &str) -> Result<Self> {
+        let mut nibbles: Vec<char> = pattern.chars()
+            .filter(|c| !c.is_whitespace() && !matches!(c, ':' | '-' | ','))
+            .collect();
+
+        let open_ended = nibbles.last() == Some(&'*');
+        if open_ended {
+            nibbles.pop();
+        }
+
+        if nibbles.contains(&'*') {
+            bail!(Error::Parse(format!("'*' is only allowed at the end of an APDU pattern: '{}'", pattern)));
+        }
+        if !nibbles.len().is_multiple_of(2) {
+            bail!(Error::Parse(format!("APDU pattern must have an even number of nibbles: '{}'", pattern)));
+        }
+
+        let mut value = Vec::with_capacity(nibbles.len() / 2);
+        let mut mask = Vec::with_capacity(nibbles.len() / 2);
+        for pair in nibbles.chunks(2) {
+            let mut byte = 0u8;
+            let mut byte_mask = 0u8;
+            for &c in pair {
+                byte <<= 4;
+                byte_mask <<= 4;
+                match c {
+                    '?' | 'x' | 'X' => {}
+                    _ => {
+                        let digit = c.to_digit(16)
+                            .ok_or_else(|| Error::Parse(format!("Invalid character '{}' in APDU pattern: '{}'", c, pattern)))?;
+                        byte |= digit as u8;
+                        byte_mask |= 0x0F;
+                    }
+                }
+            }
+            value.push(byte);
+            mask.push(byte_mask);
+        }
+
+        Ok(Self { value, mask, open_ended })
+    }
+
+    /// Restrict the pattern further with an explicit bit mask
+    pub fn with_mask(mut self, mask: &[u8]) -> Self {
+        for (i, m) in self.mask.iter_mut().enumerate() {
+            *m &= mask.get(i).copied().unwrap_or(0xFF);
+        }
+        self
+    }
+
+    /// Check whether an APDU matches this pattern
+    pub fn matches(&self, apdu: &[u8]) -> bool {
+        if apdu.len() < self.value.len() || (!self.open_ended && apdu.len() != self.value.len()) {
+            return false;
+        }
+
+        self.value.iter()
+            .zip(&self.mask)
+            .zip(apdu)
+            .all(|((&v, &m), &b)| b & m == v & m)
+    }
+}
+
+/// Compiled APDU rule
+#[derive(Debug, Clone)]
+struct CompiledRule {
+    pattern: ApduPattern,
+    response: Vec<u8>,
+    delay: Duration,
+}
+
+/// Compiled simulator state shared by the backend and its cards
+#[derive(Debug)]
+struct SimCardModel {
+    reader_name: String,
+    groups: Vec<String>,
+    atr: Vec<u8>,
+    card_present: bool,
+    rules: Vec<CompiledRule>,
+    controls: Vec<(u32, Vec<u8>)>,
+    attributes: Mutex<HashMap<Attribute, Vec<u8>>>,
+    default_sw: Vec<u8>,
+    extended_length: bool,
+}
+
+impl SimCardModel {
+    fn compile(config: &SimConfig) -> Result<Self> {
+        let atr = parse_hex(&config.atr).context("Invalid simulator ATR")?;
+        let default_sw = parse_status_word(&config.default_sw)?;
+
+        let mut rules = Vec::with_capacity(config.rules.len());
+        for (i, rule) in config.rules.iter().enumerate() {
+            let mut pattern = ApduPattern::parse(&rule.apdu)
+                .with_context(|| format!("Invalid APDU pattern in rule {}", i + 1))?;
+            if let Some(ref mask) = rule.mask {
+                let mask = parse_hex(mask)
+                    .with_context(|| format!("Invalid mask in rule {}", i + 1))?;
+                pattern = pattern.with_mask(&mask);
+            }
+
+            let mut response = parse_hex(&rule.response)
+                .with_context(|| format!("Invalid response in rule {}", i + 1))?;
+            response.extend(parse_status_word(&rule.sw)
+                .with_context(|| format!("Invalid status word in rule {}", i + 1))?);
+
+            rules.push(CompiledRule { pattern, response, delay: Duration::from_millis(rule.delay_ms) });
+        }
+
+        let mut controls = Vec::with_capacity(config.controls.len());
+        for (i, control) in config.controls.iter().enumerate() {
+            let code = parse_control_code(&control.code)
+                .with_context(|| format!("Invalid control code in control rule {}", i + 1))?;
+            let response = parse_hex(&control.response)
+                .with_context(|| format!("Invalid response in control rule {}", i + 1))?;
+            controls.push((code, response));
+        }
+
+        let mut attributes = HashMap::from([
+            (Attribute::VendorName, b"PCSC Tester".to_vec()),
+            (Attribute::VendorIfdType, b"Virtual Reader".to_vec()),
+        ]);
+        for (name, value) in &config.attributes {
+            let attribute = parse_attribute(name)
+                .with_context(|| format!("Invalid attribute: {}", name))?;
+            let value = match attribute_def(attribute).map(|def| def.format) {
+                Some(AttributeFormat::Text) => value.as_bytes().to_vec(),
+                _ => parse_hex(value)
+                    .with_context(|| format!("Invalid value for attribute {}", name))?,
+            };
+            attributes.insert(attribute, value);
+        }
+
+        Ok(Self {
+            reader_name: config.reader_name.clone(),
+            groups: config.groups.clone(),
+            atr,
+            card_present: config.card_present,
+            rules,
+            controls,
+            attributes: Mutex::new(attributes),
+            default_sw,
+            extended_length: config.extended_length,
+        })
+    }
+
+    /// Pick the protocol of a new connection; direct connections have none
+    fn negotiate(&self, share_mode: ShareMode, protocols: Protocols) -> BackendResult<Option<Protocol>> {
+        if share_mode == ShareMode::Direct {
+            return Ok(None);
+        }
+        if !self.card_present {
+            return Err(pcsc::Error::NoSmartcard);
+        }
+
+        if protocols.contains(Protocols::T1) {
+            Ok(Some(Protocol::T1))
+        } else if protocols.contains(Protocols::T0) {
+            Ok(Some(Protocol::T0))
+        } else {
+            Err(pcsc::Error::ProtoMismatch)
+        }
+    }
+
+    /// Response to an APDU and how long the card takes to send it
+    fn respond(&self, apdu: &[u8]) -> (Vec<u8>, Duration) {
+        self.rules.iter()
+            .find(|rule| rule.pattern.matches(apdu))
+            .map(|rule| (rule.response.clone(), rule.delay))
+            .unwrap_or_else(|| (self.default_sw.clone(), Duration::ZERO))
+    }
+}
+
+fn parse_status_word(sw: &str) -> Result<Vec<u8>> {
+    let sw_bytes = parse_hex(sw)?;
+    if sw_bytes.len() != 2 {
+        bail!(Error::Parse(format!("Status word must be exactly 2 bytes: '{}'", sw)));
+    }
+    Ok(sw_bytes)
+}
+
+/// Backend exposing one virtual reader driven by a [`SimConfig`]
+#[derive(Debug, Clone)]
+pub struct SimBackend {
+    model: Arc<SimCardModel>,
+    /// Interrupts `get_status_change`
+    interrupt: CancelToken,
+}
+
+impl SimBackend {
+    /// Create a simulator from a configuration
+    pub fn new(config: &SimConfig) -> Result<Self> {
+        Ok(Self {
+            model: Arc::new(SimCardModel::compile(config)?),
+            interrupt: CancelToken::new(),
+        })
+    }
+
+    /// Load a simulator rules file (JSON)
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read simulator rules: {}", path.display()))?;
+        let config: SimConfig = serde_json::from_str(&json)
+            .with_context(|| format!("Failed to parse simulator rules: {}", path.display()))?;
+        Self::new(&config)
+            .with_context(|| format!("Invalid simulator rules: {}", path.display()))
+    }
+}
+
+/// Connection to the simulated card
+#[derive(Debug)]
+pub struct SimCard {
+    model: Arc<SimCardModel>,
+    protocol: Option<Protocol>,
+    in_transaction: bool,
+    /// Interrupts a delayed response
+    interrupt: CancelToken,
+}
+
+impl PcscBackend for SimBackend {
+    type Card = SimCard;
+
+    fn list_readers(&self) -> BackendResult<Vec<String>> {
+        Ok(vec![self.model.reader_name.clone()])
+    }
+
+    fn list_reader_groups(&self) -> BackendResult<Vec<String>> {
+        let mut groups = vec![DEFAULT_READER_GROUP.to_string()];
+        groups.extend(self.model.groups.iter().filter(|g| *g != DEFAULT_READER_GROUP).cloned());
+        Ok(groups)
+    }
+
+    fn list_readers_in_group(&self, group: &str) -> BackendResult<Vec<String>> {
+        if group == ALL_READERS_GROUP || self.list_reader_groups()?.iter().any(|g| g == group) {
+            Ok(vec![self.model.reader_name.clone()])
+        } else {
+            Err(pcsc::Error::NoReadersAvailable)
+        }
+    }
+
+    fn connect(&self, reader_name: &str, share_mode: ShareMode, protocols: Protocols) -> BackendResult<SimCard> {
+        if reader_name != self.model.reader_name {
+            return Err(pcsc::Error::UnknownReader);
+        }
+        let protocol = self.model.negotiate(share_mode, protocols)?;
+        Ok(SimCard { model: Arc::clone(&self.model), protocol, in_transaction: false, interrupt: CancelToken::new() })
+    }
+
+    fn get_status_change(&self, timeout: Option<Duration>, readers: &mut [ReaderState]) -> BackendResult<()> {
+        poll_status_change(timeout, readers, &self.interrupt, |name| {
+            if name != self.model.reader_name {
+                None
+            } else if self.model.card_present {
+                Some((State::PRESENT, self.model.atr.clone()))
+            } else {
+                Some((State::EMPTY, Vec::new()))
+            }
+        })
+    }
+
+    fn canceller(&self) -> Canceller {
+        self.interrupt.canceller()
+    }
+}
+
+impl CardBackend for SimCard {
+    fn status(&self) -> BackendResult<CardStatus> {
+        if !self.model.card_present {
+            return Err(pcsc::Error::NoSmartcard);
+        }
+        Ok(CardStatus {
+            reader_name: self.model.reader_name.clone(),
+            protocol: self.protocol,
+            atr: self.model.atr.clone(),
+        })
+    }
+
+    fn transmit(&self, apdu: &[u8]) -> BackendResult<Vec<u8>> {
+        if self.protocol.is_none() {
+            return Err(pcsc::Error::NotTransacted);
+        }
+        if apdu.len() < 4 {
+            return Err(pcsc::Error::InvalidParameter);
+        }
+        if !self.model.extended_length && apdu::is_extended(apdu) {
+            return Err(pcsc::Error::NotTransacted);
+        }
+        let (response, delay) = self.model.respond(apdu);
+        if !delay.is_zero() {
+            self.interrupt.reset();
+            self.interrupt.sleep(delay).map_err(|_| pcsc::Error::Cancelled)?;
+        }
+        Ok(response)
+    }
+
+    fn control(&self, code: u32, _data: &[u8]) -> BackendResult<Vec<u8>> {
+        self.model.controls.iter()
+            .find(|(c, _)| *c == code)
+            .map(|(_, response)| response.clone())
+            .ok_or(pcsc::Error::UnsupportedFeature)
+    }
+
+    fn get_attribute(&self, attribute: Attribute) -> BackendResult<Vec<u8>> {
+        let card_present = self.model.card_present;
+        match attribute {
+            Attribute::AtrString if card_present => Ok(self.model.atr.clone()),
+            Attribute::CurrentProtocolType if card_present => {
+                let mask: u32 = match self.protocol {
+                    Some(Protocol::T0) => 0x1,
+                    Some(Protocol::T1) => 0x2,
+                    _ => 0,
+                };
+                Ok(mask.to_le_bytes().to_vec())
+            }
+            Attribute::IccPresence => Ok(vec![if card_present { 1 } else { 0 }]),
+            _ => self.model.attributes.lock().unwrap_or_else(|e| e.into_inner())
+                .get(&attribute)
+                .cloned()
+                .ok_or(pcsc::Error::UnsupportedFeature),
+        }
+    }
+
+    fn set_attribute(&self, attribute: Attribute, data: &[u8]) -> BackendResult<()> {
+        self.model.attributes.lock().unwrap_or_else(|e| e.into_inner())
+            .insert(attribute, data.to_vec());
+        Ok(())
+    }
+
+    fn reconnect(&mut self, share_mode: ShareMode, protocols: Protocols, _initialization: Disposition) -> BackendResult<()> {
+        // The simulated card answers with the same ATR after any reset
+        if self.in_transaction {
+            return Err(pcsc::Error::SharingViolation);
+        }
+        self.protocol = self.model.negotiate(share_mode, protocols)?;
+        Ok(())
+    }
+
+    fn begin_transaction(&mut self) -> BackendResult<()> {
+        if self.in_transaction {
+            return Err(pcsc::Error::InvalidValue);
+        }
+        self.in_transaction = true;
+        Ok(())
+    }
+
+    fn end_transaction(&mut self, _disposition: Disposition) -> BackendResult<()> {
+        if !self.in_transaction {
+            return Err(pcsc::Error::NotTransacted);
+        }
+        self.in_transaction = false;
+        Ok(())
+    }
+
+    fn disconnect(self: Box<Self>, _disposition: Disposition) -> BackendResult<()> {
+        Ok(())
+    }
+
+    fn canceller(&self) -> Canceller {
+        self.interrupt.canceller()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::backend::PNP_NOTIFICATION;
+
+    fn config() -> SimConfig {
+        serde_json::from_str(r#"{
+            "atr": "3B 02 14 50",
+            "rules": [
+                { "apdu": "00A40400 02 3F00", "sw": "9000" },
+                { "apdu": "00B0 ???? *", "response": "48656C6C6F" },
+                { "apdu": "80CA0000", "mask": "F0FFFFFF", "sw": "6A88" }
+            ],
+            "controls": [
+                { "code": "0x42000C00", "response": "0102" }
+            ],
+            "attributes": {
+                "vendor_ifd_serial_no": "SN-42",
+                "max_ifsd": "FE000000"
+            }
+        }"#).unwrap()
+    }
+
+    #[test]
+    fn test_pattern_exact() {
+        let pattern = ApduPattern::parse("00A40400").unwrap();
+        assert!(pattern.matches(&[0x00, 0xA4, 0x04, 0x00]));
+        assert!(!pattern.matches(&[0x00, 0xA4, 0x04, 0x00, 0x00]));
+        assert!(!pattern.matches(&[0x00, 0xA4, 0x04]));
+    }
+
+    #[test]
+    fn test_pattern_wildcards() {
+        let pattern = ApduPattern::parse("00 B0 ?? x1 *").unwrap();
+        assert!(pattern.matches(&[0x00, 0xB0, 0x12, 0x31]));
+        assert!(pattern.matches(&[0x00, 0xB0, 0xFF, 0x01, 0x10]));
+        assert!(!pattern.matches(&[0x00, 0xB0, 0x12, 0x32]));
+        assert!(!pattern.matches(&[0x00, 0xB0, 0x12]));
+    }
+
+    #[test]
+    fn test_pattern_mask() {
+        let pattern = ApduPattern::parse("80CA").unwrap().with_mask(&[0xF0, 0xFF]);
+        assert!(pattern.matches(&[0x80, 0xCA]));
+        assert!(pattern.matches(&[0x84, 0xCA]));
+        assert!(!pattern.matches(&[0x00, 0xCA]));
+    }
+
+    #[test]
+    fn test_pattern_invalid() {
+        assert!(ApduPattern::parse("00A").is_err());
+        assert!(ApduPattern::parse("00*A4").is_err());
+        assert!(ApduPattern::parse("00G4").is_err());
+    }
+
+    #[test]
+    fn test_sim_transmit_rules() {
+        let backend = SimBackend::new(&config()).unwrap();
+        let card = backend.connect(DEFAULT_SIM_READER, ShareMode::Shared, Protocols::ANY).unwrap();
+
+        assert_eq!(card.transmit(&[0x00, 0xA4, 0x04, 0x00, 0x02, 0x3F, 0x00]).unwrap(), vec![0x90, 0x00]);
+        assert_eq!(card.transmit(&[0x00, 0xB0, 0x00, 0x00, 0x05]).unwrap(),
+                   vec![0x48, 0x65, 0x6C, 0x6C, 0x6F, 0x90, 0x00]);
+        assert_eq!(card.transmit(&[0x84, 0xCA, 0x00, 0x00]).unwrap(), vec![0x6A, 0x88]);
+        assert_eq!(card.transmit(&[0x00, 0x84, 0x00, 0x00]).unwrap(), vec![0x6D, 0x00]);
+    }
+
+    #[test]
+    fn test_sim_extended_length() {
+        let mut config = config();
+        config.rules.push(SimRule {
+            apdu: "00CB3FFF *".to_string(),
+            mask: None,
+            response: "30".repeat(2048),
+            sw: "9000".to_string(),
+            delay_ms: 0,
+        });
+        let apdu = [0x00, 0xCB, 0x3F, 0xFF, 0x00, 0x00, 0x03, 0x5C, 0x01, 0x7E, 0x00, 0x00];
+
+        let backend = SimBackend::new(&config).unwrap();
+        let card = backend.connect(DEFAULT_SIM_READER, ShareMode::Shared, Protocols::ANY).unwrap();
+        assert_eq!(card.transmit(&apdu).unwrap().len(), 2050);
+
+        config.extended_length = false;
+        let backend = SimBackend::new(&config).unwrap();
+        let card = backend.connect(DEFAULT_SIM_READER, ShareMode::Shared, Protocols::ANY).unwrap();
+        assert_eq!(card.transmit(&apdu), Err(pcsc::Error::NotTransacted));
+        assert_eq!(card.transmit(&[0x00, 0xB0, 0x00, 0x00, 0x00]).unwrap().len(), 7);
+    }
+
+    #[test]
+    fn test_sim_status_and_control() {
+        let backend = SimBackend::new(&config()).unwrap();
+        let card = backend.connect(DEFAULT_SIM_READER, ShareMode::Shared, Protocols::ANY).unwrap();
+
+        let status = card.status().unwrap();
+        assert_eq!(status.atr, vec![0x3B, 0x02, 0x14, 0x50]);
+        assert_eq!(status.protocol, Some(Protocol::T1));
+
+        assert_eq!(card.control(0x42000C00, &[]).unwrap(), vec![0x01, 0x02]);
+        assert_eq!(card.control(0x1234, &[]), Err(pcsc::Error::UnsupportedFeature));
+    }
+
+    #[test]
+    fn test_sim_connect_errors() {
+        let mut config = config();
+        config.card_present = false;
+        let backend = SimBackend::new(&config).unwrap();
+
+        assert!(matches!(backend.connect("Other", ShareMode::Shared, Protocols::ANY), Err(pcsc::Error::UnknownReader)));
+        assert!(matches!(backend.connect(DEFAULT_SIM_READER, ShareMode::Shared, Protocols::ANY), Err(pcsc::Error::NoSmartcard)));
+        assert!(backend.connect(DEFAULT_SIM_READER, ShareMode::Direct, Protocols::UNDEFINED).is_ok());
+    }
+
+    #[test]
+    fn test_sim_reconnect() {
+        let backend = SimBackend::new(&config()).unwrap();
+        let mut card = backend.connect(DEFAULT_SIM_READER, ShareMode::Direct, Protocols::UNDEFINED).unwrap();
+        assert_eq!(card.transmit(&[0x00, 0xA4, 0x04, 0x00]), Err(pcsc::Error::NotTransacted));
+
+        card.reconnect(ShareMode::Shared, Protocols::T0, Disposition::UnpowerCard).unwrap();
+        assert_eq!(card.status().unwrap().protocol, Some(Protocol::T0));
+        assert_eq!(card.transmit(&[0x00, 0xA4, 0x04, 0x00, 0x02, 0x3F, 0x00]).unwrap(), vec![0x90, 0x00]);
+        assert_eq!(card.reconnect(ShareMode::Shared, Protocols::RAW, Disposition::ResetCard),
+                   Err(pcsc::Error::ProtoMismatch));
+    }
+
+    #[test]
+    fn test_sim_transactions() {
+        let backend = SimBackend::new(&config()).unwrap();
+        let mut card = backend.connect(DEFAULT_SIM_READER, ShareMode::Shared, Protocols::ANY).unwrap();
+        assert_eq!(card.end_transaction(Disposition::LeaveCard), Err(pcsc::Error::NotTransacted));
+
+        card.begin_transaction().unwrap();
+        assert_eq!(card.begin_transaction(), Err(pcsc::Error::InvalidValue));
+        assert_eq!(card.reconnect(ShareMode::Shared, Protocols::ANY, Disposition::ResetCard),
+                   Err(pcsc::Error::SharingViolation));
+        card.end_transaction(Disposition::ResetCard).unwrap();
+        card.reconnect(ShareMode::Shared, Protocols::ANY, Disposition::ResetCard).unwrap();
+    }
+
+    #[test]
+    fn test_sim_reader_groups() {
+        let mut config = config();
+        config.groups = vec!["Station 1".to_string()];
+        let backend = SimBackend::new(&config).unwrap();
+
+        assert_eq!(backend.list_reader_groups().unwrap(), vec![DEFAULT_READER_GROUP, "Station 1"]);
+        assert_eq!(backend.list_readers_in_group("Station 1").unwrap(), vec![DEFAULT_SIM_READER]);
+        assert_eq!(backend.list_readers_in_group(ALL_READERS_GROUP).unwrap(), vec![DEFAULT_SIM_READER]);
+        assert_eq!(backend.list_readers_in_group("Station 2"), Err(pcsc::Error::NoReadersAvailable));
+    }
+
+    #[test]
+    fn test_sim_status_change() {
+        let backend = SimBackend::new(&config()).unwrap();
+        let mut readers = vec![ReaderState::new(DEFAULT_SIM_READER), ReaderState::new("Other"),
+                               ReaderState::new(PNP_NOTIFICATION)];
+
+        backend.get_status_change(Some(Duration::ZERO), &mut readers).unwrap();
+        assert!(readers[0].changed());
+        assert!(readers[0].event_state.contains(State::PRESENT));
+        assert_eq!(readers[0].atr, vec![0x3B, 0x02, 0x14, 0x50]);
+        assert!(readers[1].event_state.contains(State::UNKNOWN));
+        assert!(!readers[2].event_state.contains(State::UNKNOWN));
+
+        readers.iter_mut().for_each(ReaderState::sync_current_state);
+        assert_eq!(backend.get_status_change(Some(Duration::from_millis(10)), &mut readers),
+                   Err(pcsc::Error::Timeout));
+    }
+
+    #[test]
+    fn test_sim_attributes() {
+        let backend = SimBackend::new(&config()).unwrap();
+        let card = backend.connect(DEFAULT_SIM_READER, ShareMode::Shared, Protocols::T0).unwrap();
+
+        assert_eq!(card.get_attribute(Attribute::VendorName).unwrap(), b"PCSC Tester".to_vec());
+        assert_eq!(card.get_attribute(Attribute::VendorIfdSerialNo).unwrap(), b"SN-42".to_vec());
+        assert_eq!(card.get_attribute(Attribute::MaxIfsd).unwrap(), vec![0xFE, 0x00, 0x00, 0x00]);
+        assert_eq!(card.get_attribute(Attribute::AtrString).unwrap(), vec![0x3B, 0x02, 0x14, 0x50]);
+        assert_eq!(card.get_attribute(Attribute::CurrentProtocolType).unwrap(), vec![0x01, 0x00, 0x00, 0x00]);
+        assert_eq!(card.get_attribute(Attribute::ChannelId), Err(pcsc::Error::UnsupportedFeature));
+
+        card.set_attribute(Attribute::ChannelId, &[0x00, 0x00, 0x20, 0x00]).unwrap();
+        assert_eq!(card.get_attribute(Attribute::ChannelId).unwrap(), vec![0x00, 0x00, 0x20, 0x00]);
+    }
+
+    #[test]
+    fn test_sim_invalid_config() {
+        let mut config = config();
+        config.rules[0].sw = "90".to_string();
+        assert!(SimBackend::new(&config).is_err());
+
+        let mut config = self::config();
+        config.attributes.insert("firmware".to_string(), "01".to_string());
+        assert!(SimBackend::new(&config).is_err());
+
+        assert!(serde_json::from_str::<SimConfig>(r#"{ "unknown": 1 }"#).is_err());
+    }
+}
@@ -0,0 +1,449 @@
+//! Backend using the platform PC/SC service (pcsc-lite, WinSCard, PCSC.framework)
+
+use pcsc::{Attribute, Card, Context, Disposition, Protocols, Scope, ShareMode, State, Transaction, MAX_BUFFER_SIZE_EXTENDED};
+use std::ffi::CString;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use super::{BackendResult, CardBackend, CardStatus, Canceller, PcscBackend, ReaderState};
+
+/// Backend backed by a real `pcsc::Context`
+pub struct SystemBackend {
+    context: Context,
+    scope: Scope,
+}
+
+impl SystemBackend {
+    /// Establish a PC/SC context with the given scope
+    pub fn establish(scope: Scope) -> BackendResult<Self> {
+        let context = Context::establish(scope)?;
+        Ok(Self { context, scope })
+    }
+
+    /// Access the underlying PC/SC context
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+}
+
+impl PcscBackend for SystemBackend {
+    type Card = SystemCard;
+
+    fn list_readers(&self) -> BackendResult<Vec<String>> {
+        let readers = self.context.list_readers_owned()?;
+        Ok(readers.iter()
+            .map(|name| name.to_string_lossy().to_string())
+            .collect())
+    }
+
+    fn list_reader_groups(&self) -> BackendResult<Vec<String>> {
+        groups::list_reader_groups(self.scope)
+    }
+
+    fn list_readers_in_group(&self, group: &str) -> BackendResult<Vec<String>> {
+        groups::list_readers_in_group(self.scope, group)
+    }
+
+    fn connect(&self, reader_name: &str, share_mode: ShareMode, protocols: Protocols) -> BackendResult<SystemCard> {
+        let reader_cstr = CString::new(reader_name).map_err(|_| pcsc::Error::UnknownReader)?;
+        let card = self.context.connect(&reader_cstr, share_mode, protocols)?;
+        Ok(SystemCard::spawn(card))
+    }
+
+    fn get_status_change(&self, timeout: Option<Duration>, readers: &mut [ReaderState]) -> BackendResult<()> {
+        let mut states = Vec::with_capacity(readers.len());
+        for reader in readers.iter() {
+            let name = CString::new(reader.name.as_str()).map_err(|_| pcsc::Error::UnknownReader)?;
+            // The card event count lives in the upper 16 bits of the state
+            let current = reader.current_state.bits() | (pcsc::ffi::DWORD::from(reader.current_count) << 16);
+            states.push(pcsc::ReaderState::new(name, State::from_bits_retain(current)));
+        }
+
+        self.context.get_status_change(timeout, &mut states)?;
+
+        for (reader, state) in readers.iter_mut().zip(&states) {
+            reader.event_state = state.event_state();
+            reader.event_count = state.event_count();
+            reader.atr = state.atr().to_vec();
+        }
+        Ok(())
+    }
+
+    fn canceller(&self) -> Canceller {
+        let context = self.context.clone();
+        Arc::new(move || {
+            let _ = context.cancel();
+        })
+    }
+}
+
+/// Connection to a card through the platform PC/SC service
+///
+/// The card is owned by a worker thread that runs the card operations, so
+/// that a caller can stop waiting for a card that does not answer (see
+/// [`CardBackend::canceller`]). The worker also holds transactions open:
+/// `pcsc::Transaction` borrows the card for as long as the transaction
+/// lasts, which does not fit begin/end calls made at different times.
+pub struct SystemCard {
+    jobs: mpsc::Sender<Job>,
+    /// Abandons the wait for the operation in progress, if any
+    abort: Arc<Mutex<Option<Abort>>>,
+    /// Set once an operation was abandoned while the card was busy with it
+    abandoned: AtomicBool,
+}
+
+/// Reply channel of a card job
+type Reply<T> = mpsc::Sender<BackendResult<T>>;
+
+/// Makes the pending `SystemCard::call` return `pcsc::Error::Cancelled`
+type Abort = Box<dyn FnOnce() + Send>;
+
+/// How long `disconnect` waits for a worker still busy with an abandoned
+/// operation
+const DISCONNECT_GRACE: Duration = Duration::from_secs(1);
+
+/// Card operation run by the card worker
+enum Job {
+    Run(Box<dyn FnOnce(&Card) + Send>),
+    Reconnect(ShareMode, Protocols, Disposition, Reply<()>),
+    Begin(Reply<()>),
+    End(Disposition, Reply<()>),
+    Disconnect(Disposition, Reply<()>),
+}
+
+impl SystemCard {
+    /// Hand the card to a new worker thread
+    fn spawn(card: Card) -> Self {
+        let (jobs, job_rx) = mpsc::channel();
+        thread::spawn(move || serve_card(card, job_rx));
+        Self { jobs, abort: Arc::default(), abandoned: AtomicBool::new(false) }
+    }
+
+    /// Send a job to the worker and wait for its reply, unless cancelled
+    fn call<T: Send + 'static>(&self, job: impl FnOnce(Reply<T>) -> Job) -> BackendResult<T> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+        let abort_tx = reply_tx.clone();
+        *self.abort.lock().unwrap() = Some(Box::new(move || {
+            let _ = abort_tx.send(Err(pcsc::Error::Cancelled));
+        }));
+        if self.jobs.send(job(reply_tx)).is_err() {
+            self.abort.lock().unwrap().take();
+            return Err(pcsc::Error::InvalidHandle);
+        }
+
+        let result = reply_rx.recv().unwrap_or(Err(pcsc::Error::InternalError));
+        self.abort.lock().unwrap().take();
+        if matches!(result, Err(pcsc::Error::Cancelled)) {
+            self.abandoned.store(true, Ordering::SeqCst);
+        }
+        result
+    }
+
+    /// Run a card operation on the worker
+    fn with_card<T, F>(&self, f: F) -> BackendResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Card) -> BackendResult<T> + Send + 'static,
+    {
+        self.call(|reply| Job::Run(Box::new(move |card| {
+            let _ = reply.send(f(card));
+        })))
+    }
+}
+
+/// Body of the card worker thread
+fn serve_card(mut card: Card, jobs: mpsc::Receiver<Job>) {
+    let mut disconnect = None;
+    while let Ok(job) = jobs.recv() {
+        match job {
+            Job::Run(f) => f(&card),
+            Job::Reconnect(share_mode, protocols, initialization, reply) => {
+                let _ = reply.send(card.reconnect(share_mode, protocols, initialization));
+            }
+            Job::Begin(reply) => match card.transaction() {
+                Ok(transaction) => {
+                    let _ = reply.send(Ok(()));
+                    disconnect = serve_transaction(transaction, &jobs);
+                    if disconnect.is_some() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    let _ = reply.send(Err(e));
+                }
+            },
+            Job::End(_, reply) => {
+                let _ = reply.send(Err(pcsc::Error::NotTransacted));
+            }
+            Job::Disconnect(disposition, reply) => {
+                disconnect = Some((disposition, reply));
+                break;
+            }
+        }
+    }
+
+    // Without an explicit disconnect, dropping the card resets it
+    if let Some((disposition, reply)) = disconnect {
+        let _ = reply.send(card.disconnect(disposition).map_err(|(_card, e)| e));
+    }
+}
+
+/// Serve jobs while a transaction is open, until it ends; returns the
+/// disconnect request that ended it, if any
+fn serve_transaction(transaction: Transaction<'_>, jobs: &mpsc::Receiver<Job>) -> Option<(Disposition, Reply<()>)> {
+    for job in jobs.iter() {
+        match job {
+            Job::Run(f) => f(&transaction),
+            Job::Reconnect(_, _, _, reply) => {
+                let _ = reply.send(Err(pcsc::Error::SharingViolation));
+            }
+            Job::Begin(reply) => {
+                let _ = reply.send(Err(pcsc::Error::InvalidValue));
+            }
+            Job::End(disposition, reply) => {
+                let _ = reply.send(transaction.end(disposition).map_err(|(_transaction, e)| e));
+                return None;
+            }
+            Job::Disconnect(disposition, reply) => {
+                let _ = transaction.end(Disposition::LeaveCard);
+                return Some((disposition, reply));
+            }
+        }
+    }
+    // The card is left as is if the connection goes away mid-transaction
+    None
+}
+
+impl CardBackend for SystemCard {
+    fn status(&self) -> BackendResult<CardStatus> {
+        self.with_card(CardBackend::status)
+    }
+
+    fn transmit(&self, apdu: &[u8]) -> BackendResult<Vec<u8>> {
+        let apdu = apdu.to_vec();
+        self.with_card(move |card| CardBackend::transmit(card, &apdu))
+    }
+
+    fn control(&self, code: u32, data: &[u8]) -> BackendResult<Vec<u8>> {
+        let data = data.to_vec();
+        self.with_card(move |card| CardBackend::control(card, code, &data))
+    }
+
+    fn get_attribute(&self, attribute: Attribute) -> BackendResult<Vec<u8>> {
+        self.with_card(move |card| CardBackend::get_attribute(card, attribute))
+    }
+
+    fn set_attribute(&self, attribute: Attribute, data: &[u8]) -> BackendResult<()> {
+        let data = data.to_vec();
+        self.with_card(move |card| CardBackend::set_attribute(card, attribute, &data))
+    }
+
+    fn reconnect(&mut self, share_mode: ShareMode, protocols: Protocols, initialization: Disposition) -> BackendResult<()> {
+        self.call(|reply| Job::Reconnect(share_mode, protocols, initialization, reply))
+    }
+
+    fn begin_transaction(&mut self) -> BackendResult<()> {
+        self.call(Job::Begin)
+    }
+
+    fn end_transaction(&mut self, disposition: Disposition) -> BackendResult<()> {
+        self.call(|reply| Job::End(disposition, reply))
+    }
+
+    fn disconnect(self: Box<Self>, disposition: Disposition) -> BackendResult<()> {
+        // An abandoned command may have left the card in any state
+        let abandoned = self.abandoned.load(Ordering::SeqCst);
+        let disposition = if abandoned { Disposition::ResetCard } else { disposition };
+
+        let (reply_tx, reply_rx) = mpsc::channel();
+        if self.jobs.send(Job::Disconnect(disposition, reply_tx)).is_err() {
+            return Ok(());
+        }
+        if abandoned {
+            // Do not hang on a card that still does not answer: the worker
+            // disconnects on its own once the command completes
+            return match reply_rx.recv_timeout(DISCONNECT_GRACE) {
+                Ok(result) => result,
+                Err(_) => {
+                    log::warn!("Card still busy with a cancelled command, not waiting for the disconnection");
+                    Ok(())
+                }
+            };
+        }
+        reply_rx.recv().unwrap_or(Err(pcsc::Error::InternalError))
+    }
+
+    fn canceller(&self) -> Canceller {
+        let abort = Arc::clone(&self.abort);
+        Arc::new(move || {
+            if let Some(abort) = abort.lock().unwrap().take() {
+                abort();
+            }
+        })
+    }
+}
+
+impl CardBackend for Card {
+    fn status(&self) -> BackendResult<CardStatus> {
+        let status = self.status2_owned()?;
+        Ok(CardStatus {
+            reader_name: status.reader_names().first()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            protocol: status.protocol2(),
+            atr: status.atr().to_vec(),
+        })
+    }
+
+    fn transmit(&self, apdu: &[u8]) -> BackendResult<Vec<u8>> {
+        // Large enough for an extended-length response (64 KB and the status
+        // word); pcsc-lite only accepts extended APDUs with such a buffer
+        let mut response_buf = vec![0; MAX_BUFFER_SIZE_EXTENDED];
+        let response = Card::transmit(self, apdu, &mut response_buf)?;
+        Ok(response.to_vec())
+    }
+
+    fn control(&self, code: u32, data: &[u8]) -> BackendResult<Vec<u8>> {
+        let mut response_buf = vec![0; MAX_BUFFER_SIZE_EXTENDED];
+        // DWORD is 64 bits wide on pcsc-lite and 32 bits on Windows/macOS
+        #[allow(clippy::useless_conversion)]
+        let response = Card::control(self, code.into(), data, &mut response_buf)?;
+        Ok(response.to_vec())
+    }
+
+    fn get_attribute(&self, attribute: Attribute) -> BackendResult<Vec<u8>> {
+        self.get_attribute_owned(attribute)
+    }
+
+    fn set_attribute(&self, attribute: Attribute, data: &[u8]) -> BackendResult<()> {
+        Card::set_attribute(self, attribute, data)
+    }
+
+    fn reconnect(&mut self, share_mode: ShareMode, protocols: Protocols, initialization: Disposition) -> BackendResult<()> {
+        Card::reconnect(self, share_mode, protocols, initialization)
+    }
+
+    fn begin_transaction(&mut self) -> BackendResult<()> {
+        // Transactions need a `SystemCard`, which can hold one open
+        Err(pcsc::Error::UnsupportedFeature)
+    }
+
+    fn end_transaction(&mut self, _disposition: Disposition) -> BackendResult<()> {
+        Err(pcsc::Error::NotTransacted)
+    }
+
+    fn disconnect(self: Box<Self>, disposition: Disposition) -> BackendResult<()> {
+        Card::disconnect(*self, disposition).map_err(|(_card, e)| e)
+    }
+
+    fn canceller(&self) -> Canceller {
+        // A bare card runs its operations on the calling thread
+        Arc::new(|| {})
+    }
+}
+
+/// Reader group queries, which the `pcsc` crate does not wrap
+///
+/// The handle of a `pcsc::Context` is not accessible, so these calls use a
+/// short-lived context of their own. Note that pcsc-lite only knows the
+/// `SCard$DefaultReaders` group and ignores groups when listing readers.
+mod groups {
+    use pcsc::ffi::{self, DWORD, LONG, SCARDCONTEXT};
+    use pcsc::Scope;
+    use std::os::raw::c_char;
+    use std::ptr::{null, null_mut};
+
+    use super::BackendResult;
+
+    #[cfg_attr(target_os = "windows", link(name = "winscard"))]
+    extern "system" {
+        #[cfg_attr(target_os = "windows", link_name = "SCardListReaderGroupsA")]
+        fn SCardListReaderGroups(hContext: SCARDCONTEXT, mszGroups: *mut c_char, pcchGroups: *mut DWORD) -> LONG;
+    }
+
+    /// Raw PC/SC context, released when dropped
+    struct RawContext(SCARDCONTEXT);
+
+    impl RawContext {
+        fn establish(scope: Scope) -> BackendResult<Self> {
+            let scope = match scope {
+                Scope::User => ffi::SCARD_SCOPE_USER,
+                Scope::Terminal => ffi::SCARD_SCOPE_TERMINAL,
+                Scope::System => ffi::SCARD_SCOPE_SYSTEM,
+                Scope::Global => ffi::SCARD_SCOPE_GLOBAL,
+            };
+            let mut handle: SCARDCONTEXT = 0;
+            // SAFETY: the output pointer is valid for the duration of the call
+            check(unsafe { ffi::SCardEstablishContext(scope, null(), null(), &mut handle) })?;
+            Ok(Self(handle))
+        }
+    }
+
+    impl Drop for RawContext {
+        fn drop(&mut self) {
+            // SAFETY: the handle was returned by SCardEstablishContext
+            unsafe {
+                ffi::SCardReleaseContext(self.0);
+            }
+        }
+    }
+
+    /// List the reader groups known to the PC/SC service
+    pub fn list_reader_groups(scope: Scope) -> BackendResult<Vec<String>> {
+        let context = RawContext::establish(scope)?;
+        // SAFETY: read_multi_string passes a buffer of the given length
+        read_multi_string(|buffer, len| unsafe { SCardListReaderGroups(context.0, buffer, len) })
+    }
+
+    /// List the readers belonging to a group
+    pub fn list_readers_in_group(scope: Scope, group: &str) -> BackendResult<Vec<String>> {
+        if group.contains('\0') {
+            return Err(pcsc::Error::InvalidParameter);
+        }
+        // Groups are passed as a multi-string: each name NUL-terminated, plus a final NUL
+        let mut groups = group.as_bytes().to_vec();
+        groups.extend([0, 0]);
+
+        let context = RawContext::establish(scope)?;
+        // SAFETY: `groups` is a valid multi-string and read_multi_string
+        // passes a buffer of the given length
+        read_multi_string(|buffer, len| unsafe {
+            ffi::SCardListReaders(context.0, groups.as_ptr() as *const c_char, buffer, len)
+        })
+    }
+
+    /// Query a multi-string: first its length, then its contents
+    fn read_multi_string<F>(mut query: F) -> BackendResult<Vec<String>>
+    where
+        F: FnMut(*mut c_char, *mut DWORD) -> LONG,
+    {
+        let mut len: DWORD = 0;
+        check(query(null_mut(), &mut len))?;
+
+        let mut buffer = vec![0u8; len as usize];
+        check(query(buffer.as_mut_ptr() as *mut c_char, &mut len))?;
+        buffer.truncate(len as usize);
+
+        Ok(buffer.split(|&b| b == 0)
+            .filter(|name| !name.is_empty())
+            .map(|name| String::from_utf8_lossy(name).into_owned())
+            .collect())
+    }
+
+    /// Map a PC/SC return code to an error
+    fn check(code: LONG) -> BackendResult<()> {
+        match code {
+            ffi::SCARD_S_SUCCESS => Ok(()),
+            ffi::SCARD_E_NO_READERS_AVAILABLE => Err(pcsc::Error::NoReadersAvailable),
+            ffi::SCARD_E_NO_SERVICE => Err(pcsc::Error::NoService),
+            ffi::SCARD_E_SERVICE_STOPPED => Err(pcsc::Error::ServiceStopped),
+            ffi::SCARD_E_INVALID_HANDLE => Err(pcsc::Error::InvalidHandle),
+            ffi::SCARD_E_INVALID_PARAMETER => Err(pcsc::Error::InvalidParameter),
+            ffi::SCARD_E_INSUFFICIENT_BUFFER => Err(pcsc::Error::InsufficientBuffer),
+            ffi::SCARD_E_NO_MEMORY => Err(pcsc::Error::NoMemory),
+            _ => Err(pcsc::Error::UnknownError),
+        }
+    }
+}
@@ -0,0 +1,217 @@
+//! Cancellation of blocking operations
+//!
+//! Waiting for a card or a reader event, and exchanges with a mute card, can
+//! block for a long time. A [`CancelToken`] is shared between the thread
+//! running such an operation and the threads that may want to stop it (a
+//! Ctrl-C handler, a GUI button). While an operation blocks, it registers a
+//! [`Canceller`] that interrupts it: `SCardCancel` for status change waits,
+//! or abandoning the wait for the card's response.
+//!
+//! A cancelled token stays cancelled, failing every later operation with
+//! [`Error::Cancelled`], until it is reset.
+
+use anyhow::{Result, bail};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::core::backend::Canceller;
+use crate::core::error::Error;
+
+/// Shared flag requesting that the current and later operations stop
+#[derive(Clone, Default)]
+pub struct CancelToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    cancelled: Mutex<bool>,
+    /// Signalled on cancellation, to wake up [`CancelToken::sleep`]
+    wakeup: Condvar,
+    /// Cancellers of the operations in progress, by registration id
+    hooks: Mutex<Vec<(u64, Canceller)>>,
+    next_id: AtomicU64,
+}
+
+impl std::fmt::Debug for CancelToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CancelToken")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+impl CancelToken {
+    /// Create a token that is not cancelled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel: interrupt the operations in progress and fail later ones
+    pub fn cancel(&self) {
+        *self.inner.cancelled.lock().unwrap() = true;
+        self.inner.wakeup.notify_all();
+        let hooks: Vec<Canceller> = self.inner.hooks.lock().unwrap().iter()
+            .map(|(_, canceller)| Arc::clone(canceller))
+            .collect();
+        for canceller in hooks {
+            canceller();
+        }
+    }
+
+    /// Check whether the token was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        *self.inner.cancelled.lock().unwrap()
+    }
+
+    /// Clear the cancellation, e.g. before the next interactive command
+    pub fn reset(&self) {
+        *self.inner.cancelled.lock().unwrap() = false;
+    }
+
+    /// Fail with [`Error::Cancelled`] if the token was cancelled
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            bail!(Error::Cancelled);
+        }
+        Ok(())
+    }
+
+    /// Canceller cancelling this token
+    pub fn canceller(&self) -> Canceller {
+        let token = self.clone();
+        Arc::new(move || token.cancel())
+    }
+
+    /// Call `canceller` on cancellation for as long as the returned guard
+    /// lives; it is called right away if the token is already cancelled
+    pub fn register(&self, canceller: Canceller) -> CancelGuard {
+        let id = self.inner.next_id.fetch_add(1, Ordering::SeqCst);
+        self.inner.hooks.lock().unwrap().push((id, Arc::clone(&canceller)));
+        if self.is_cancelled() {
+            canceller();
+        }
+        CancelGuard { token: self.clone(), id }
+    }
+
+    /// Sleep for `duration`, returning early with [`Error::Cancelled`] on
+    /// cancellation
+    pub fn sleep(&self, duration: Duration) -> Result<()> {
+        let cancelled = self.inner.cancelled.lock().unwrap();
+        let (cancelled, _) = self.inner.wakeup
+            .wait_timeout_while(cancelled, duration, |cancelled| !*cancelled)
+            .unwrap();
+        if *cancelled {
+            bail!(Error::Cancelled);
+        }
+        Ok(())
+    }
+}
+
+/// Registration of a canceller, undone when dropped
+pub struct CancelGuard {
+    token: CancelToken,
+    id: u64,
+}
+
+impl Drop for CancelGuard {
+    fn drop(&mut self) {
+        self.token.inner.hooks.lock().unwrap().retain(|(id, _)| *id != self.id);
+    }
+}
+
+/// Timer calling a canceller when an operation takes too long
+pub struct Watchdog {
+    done: mpsc::Sender<()>,
+    fired: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+impl Watchdog {
+    /// Call `canceller` once `timeout` elapses, unless disarmed before
+    pub fn arm(timeout: Duration, canceller: Canceller) -> Self {
+        let (done, done_rx) = mpsc::channel();
+        let fired = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&fired);
+        let thread = std::thread::spawn(move || {
+            if let Err(RecvTimeoutError::Timeout) = done_rx.recv_timeout(timeout) {
+                flag.store(true, Ordering::SeqCst);
+                canceller();
+            }
+        });
+        Self { done, fired, thread }
+    }
+
+    /// Stop the timer and tell whether it fired
+    pub fn disarm(self) -> bool {
+        let _ = self.done.send(());
+        let _ = self.thread.join();
+        self.fired.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Instant;
+
+    fn counter() -> (Arc<AtomicUsize>, Canceller) {
+        let count = Arc::new(AtomicUsize::new(0));
+        let counted = Arc::clone(&count);
+        (count, Arc::new(move || { counted.fetch_add(1, Ordering::SeqCst); }))
+    }
+
+    #[test]
+    fn test_cancel_token_hooks() {
+        let token = CancelToken::new();
+        let (count, canceller) = counter();
+        let guard = token.register(Arc::clone(&canceller));
+        assert!(token.check().is_ok());
+
+        token.cancel();
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        let error = token.check().unwrap_err();
+        assert!(matches!(error.downcast_ref::<Error>(), Some(Error::Cancelled)));
+
+        // Registering on a cancelled token cancels right away
+        let _late = token.register(Arc::clone(&canceller));
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+
+        drop(guard);
+        token.reset();
+        assert!(!token.is_cancelled());
+        token.cancel();
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_cancel_token_sleep() {
+        let token = CancelToken::new();
+        assert!(token.sleep(Duration::from_millis(10)).is_ok());
+
+        let canceller = token.clone();
+        let start = Instant::now();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            canceller.cancel();
+        });
+        assert!(token.sleep(Duration::from_secs(10)).is_err());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_watchdog() {
+        let (count, canceller) = counter();
+        let watchdog = Watchdog::arm(Duration::from_secs(10), Arc::clone(&canceller));
+        assert!(!watchdog.disarm());
+
+        let watchdog = Watchdog::arm(Duration::from_millis(10), canceller);
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(watchdog.disarm());
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+}
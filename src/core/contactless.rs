@@ -0,0 +1,259 @@
+//! Contactless storage cards (PC/SC Part 3)
+//!
+//! Memory cards such as MIFARE Classic or Ultralight have no APDU interface
+//! of their own: the reader takes pseudo-APDUs with class `FF` and turns them
+//! into the card's native commands. Part 3 defines Get Data, Load Keys,
+//! General Authenticate, Read Binary and Update Binary; value block
+//! increment and decrement use the widespread `FF D7` command.
+//!
+//! The reader answers these commands itself, so `63 00` and `6A 81` mean the
+//! operation failed on the card and the reader or card does not support the
+//! function, rather than what an application would mean by them.
+
+use anyhow::{Result, bail};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::core::commands::TransmitResult;
+use crate::core::error::Error;
+use crate::core::utils::describe_status_word;
+
+/// Class byte of the pseudo-APDUs
+pub const CLA_PSEUDO: u8 = 0xFF;
+
+const INS_GET_DATA: u8 = 0xCA;
+const INS_LOAD_KEYS: u8 = 0x82;
+const INS_GENERAL_AUTHENTICATE: u8 = 0x86;
+const INS_READ_BINARY: u8 = 0xB0;
+const INS_UPDATE_BINARY: u8 = 0xD6;
+const INS_VALUE_BLOCK: u8 = 0xD7;
+
+/// Load Keys P1: keep the key across power cycles
+const KEY_NON_VOLATILE: u8 = 0x20;
+
+/// MIFARE key used for authentication
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyType {
+    #[default]
+    A,
+    B,
+}
+
+impl KeyType {
+    fn code(self) -> u8 {
+        match self {
+            KeyType::A => 0x60,
+            KeyType::B => 0x61,
+        }
+    }
+}
+
+impl FromStr for KeyType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "a" => Ok(KeyType::A),
+            "b" => Ok(KeyType::B),
+            _ => Err(Error::Parse(format!("Invalid key type: {} (expected a or b)", s))),
+        }
+    }
+}
+
+impl fmt::Display for KeyType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyType::A => write!(f, "A"),
+            KeyType::B => write!(f, "B"),
+        }
+    }
+}
+
+/// A storage card command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageCommand {
+    /// Get Data: the card's UID
+    GetUid,
+    /// Get Data: the historical bytes of the ATS (ISO 14443-4 type A)
+    GetAts,
+    /// Load Keys: store a key in the reader for later authentication
+    LoadKey { key_number: u8, key: Vec<u8>, non_volatile: bool },
+    /// General Authenticate: authenticate a block with a loaded key
+    Authenticate { block: u16, key_type: KeyType, key_number: u8 },
+    /// Read Binary: read `length` bytes from a block, 0 for 256
+    ReadBinary { block: u16, length: u8 },
+    /// Update Binary: write to a block
+    UpdateBinary { block: u16, data: Vec<u8> },
+    /// Add to a value block
+    Increment { block: u8, value: u32 },
+    /// Subtract from a value block
+    Decrement { block: u8, value: u32 },
+}
+
+impl StorageCommand {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StorageCommand::GetUid => "Get UID",
+            StorageCommand::GetAts => "Get ATS",
+            StorageCommand::LoadKey { .. } => "Load Keys",
+            StorageCommand::Authenticate { .. } => "General Authenticate",
+            StorageCommand::ReadBinary { .. } => "Read Binary",
+            StorageCommand::UpdateBinary { .. } => "Update Binary",
+            StorageCommand::Increment { .. } => "Increment",
+            StorageCommand::Decrement { .. } => "Decrement",
+        }
+    }
+
+    /// Build the pseudo-APDU
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let apdu = match self {
+            StorageCommand::GetUid => vec![CLA_PSEUDO, INS_GET_DATA, 0x00, 0x00, 0x00],
+            StorageCommand::GetAts => vec![CLA_PSEUDO, INS_GET_DATA, 0x01, 0x00, 0x00],
+            StorageCommand::LoadKey { key_number, key, non_volatile } => {
+                let p1 = if *non_volatile { KEY_NON_VOLATILE } else { 0x00 };
+                with_data(&[CLA_PSEUDO, INS_LOAD_KEYS, p1, *key_number], key, "Key")?
+            }
+            StorageCommand::Authenticate { block, key_type, key_number } => {
+                let [msb, lsb] = block.to_be_bytes();
+                vec![CLA_PSEUDO, INS_GENERAL_AUTHENTICATE, 0x00, 0x00, 0x05,
+                     0x01, msb, lsb, key_type.code(), *key_number]
+            }
+            StorageCommand::ReadBinary { block, length } => {
+                let [msb, lsb] = block.to_be_bytes();
+                vec![CLA_PSEUDO, INS_READ_BINARY, msb, lsb, *length]
+            }
+            StorageCommand::UpdateBinary { block, data } => {
+                let [msb, lsb] = block.to_be_bytes();
+                with_data(&[CLA_PSEUDO, INS_UPDATE_BINARY, msb, lsb], data, "Data")?
+            }
+            StorageCommand::Increment { block, value } => value_block(*block, 0x01, *value),
+            StorageCommand::Decrement { block, value } => value_block(*block, 0x02, *value),
+        };
+        Ok(apdu)
+    }
+}
+
+fn with_data(header: &[u8], data: &[u8], what: &str) -> Result<Vec<u8>> {
+    if data.is_empty() || data.len() > 255 {
+        bail!(Error::Parse(format!("{} must be 1 to 255 bytes long, got {}", what, data.len())));
+    }
+    let mut apdu = header.to_vec();
+    apdu.push(data.len() as u8);
+    apdu.extend_from_slice(data);
+    Ok(apdu)
+}
+
+fn value_block(block: u8, operation: u8, value: u32) -> Vec<u8> {
+    let mut apdu = vec![CLA_PSEUDO, INS_VALUE_BLOCK, 0x00, block, 0x05, operation];
+    apdu.extend_from_slice(&value.to_be_bytes());
+    apdu
+}
+
+/// Answer to a storage card command
+#[derive(Debug, Clone)]
+pub struct StorageResult {
+    pub command: StorageCommand,
+    /// The pseudo-APDU as sent and answered
+    pub transmit: TransmitResult,
+}
+
+impl StorageResult {
+    pub fn new(command: StorageCommand, transmit: TransmitResult) -> Self {
+        Self { command, transmit }
+    }
+
+    /// Response data, without the status word
+    pub fn data(&self) -> &[u8] {
+        let response = &self.transmit.response;
+        &response[..response.len().saturating_sub(2)]
+    }
+
+    pub fn is_success(&self) -> bool {
+        (self.transmit.sw1, self.transmit.sw2) == (0x90, 0x00)
+    }
+
+    pub fn description(&self) -> String {
+        describe_storage_status(self.transmit.sw1, self.transmit.sw2)
+    }
+
+    /// Fail with a status word error unless the command succeeded
+    pub fn check(&self) -> Result<()> {
+        if !self.is_success() {
+            bail!(Error::StatusWord {
+                expected: "9000".to_string(),
+                sw1: self.transmit.sw1,
+                sw2: self.transmit.sw2,
+                description: self.description(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Describe a status word answered to a pseudo-APDU, where the reader gives
+/// some of them a meaning of its own
+pub fn describe_storage_status(sw1: u8, sw2: u8) -> String {
+    match (sw1, sw2) {
+        (0x63, 0x00) => "Operation failed on the card: wrong key, not authenticated or card out of the field".to_string(),
+        (0x6A, 0x81) => "Function not supported by the reader or the card".to_string(),
+        (0x6A, 0x82) => "Block address out of range".to_string(),
+        (0x69, 0x82) => "Security status not satisfied: authenticate first".to_string(),
+        (0x62, 0x82) => "End of memory reached before the requested length".to_string(),
+        (0x65, 0x81) => "Memory failure".to_string(),
+        _ => describe_status_word(sw1, sw2),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::utils::parse_hex;
+
+    fn result(command: StorageCommand, response: &str) -> StorageResult {
+        let response = parse_hex(response).unwrap();
+        let [.., sw1, sw2] = response[..] else { panic!("no status word") };
+        let transmit = TransmitResult { apdu: command.encode().unwrap(), response, sw1, sw2, duration_ms: 0 };
+        StorageResult::new(command, transmit)
+    }
+
+    #[test]
+    fn test_encode_storage_commands() {
+        let encode = |command: StorageCommand| command.encode().unwrap();
+        assert_eq!(encode(StorageCommand::GetUid), parse_hex("FFCA000000").unwrap());
+        assert_eq!(encode(StorageCommand::GetAts), parse_hex("FFCA010000").unwrap());
+        assert_eq!(encode(StorageCommand::LoadKey { key_number: 1, key: vec![0xFF; 6], non_volatile: false }),
+                   parse_hex("FF820001 06 FFFFFFFFFFFF").unwrap());
+        assert_eq!(encode(StorageCommand::LoadKey { key_number: 0, key: vec![0xA0; 6], non_volatile: true }),
+                   parse_hex("FF822000 06 A0A0A0A0A0A0").unwrap());
+        assert_eq!(encode(StorageCommand::Authenticate { block: 4, key_type: KeyType::B, key_number: 1 }),
+                   parse_hex("FF860000 05 01 0004 61 01").unwrap());
+        assert_eq!(encode(StorageCommand::ReadBinary { block: 0x104, length: 16 }), parse_hex("FFB00104 10").unwrap());
+        assert_eq!(encode(StorageCommand::UpdateBinary { block: 5, data: vec![0x01, 0x02] }),
+                   parse_hex("FFD60005 02 0102").unwrap());
+        assert_eq!(encode(StorageCommand::Increment { block: 5, value: 0x100 }), parse_hex("FFD70005 05 01 00000100").unwrap());
+        assert_eq!(encode(StorageCommand::Decrement { block: 5, value: 1 }), parse_hex("FFD70005 05 02 00000001").unwrap());
+    }
+
+    #[test]
+    fn test_encode_storage_commands_invalid() {
+        let error = StorageCommand::UpdateBinary { block: 4, data: Vec::new() }.encode().unwrap_err();
+        assert_eq!(error.to_string(), "Data must be 1 to 255 bytes long, got 0");
+        assert!(StorageCommand::LoadKey { key_number: 0, key: vec![0; 256], non_volatile: false }.encode().is_err());
+        assert_eq!("b".parse::<KeyType>().unwrap(), KeyType::B);
+        assert!("c".parse::<KeyType>().is_err());
+    }
+
+    #[test]
+    fn test_storage_result() {
+        let uid = result(StorageCommand::GetUid, "04A23B1C 9000");
+        assert!(uid.is_success());
+        assert_eq!(uid.data(), &[0x04, 0xA2, 0x3B, 0x1C]);
+        assert!(uid.check().is_ok());
+
+        let failed = result(StorageCommand::Authenticate { block: 4, key_type: KeyType::A, key_number: 0 }, "6300");
+        assert!(failed.data().is_empty());
+        let error = failed.check().unwrap_err();
+        assert!(error.to_string().contains("card returned 6300 (Operation failed on the card"), "{}", error);
+        assert_eq!(describe_storage_status(0x6A, 0x81), "Function not supported by the reader or the card");
+    }
+}
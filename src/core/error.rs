@@ -0,0 +1,160 @@
+//! Error kinds and process exit codes
+//!
+//! Functions keep returning `anyhow::Result` with context messages, but the
+//! root of an error is one of the kinds below (or a bare `pcsc::Error`), so
+//! that callers can tell failures apart without parsing messages. The CLI
+//! turns them into the exit codes listed in [`ExitStatus`].
+
+use thiserror::Error;
+
+/// What went wrong
+#[derive(Debug, Error)]
+pub enum Error {
+    /// Error reported by the PC/SC service or the card
+    ///
+    /// Timeouts are `pcsc::Error::Timeout` too, whether the wait for a card
+    /// or a reader expired or a command outlived the command timeout.
+    #[error(transparent)]
+    Pcsc(#[from] pcsc::Error),
+    /// No reader is attached (or none in the selected group)
+    #[error("No PCSC readers found")]
+    NoReaders,
+    /// The requested reader does not exist or no reader matches a selector
+    #[error("{0}")]
+    ReaderNotFound(String),
+    /// A reader selector matches several readers
+    #[error("{0}")]
+    AmbiguousReader(String),
+    /// There is no card to talk to
+    #[error("{0}")]
+    NoCard(String),
+    /// Invalid user input: hex strings, control codes, patterns...
+    #[error("{0}")]
+    Parse(String),
+    /// The card answered with another status word than the expected one
+    #[error("Expected status word {expected}, card returned {sw1:02X}{sw2:02X} ({description})")]
+    StatusWord { expected: String, sw1: u8, sw2: u8, description: String },
+    /// Malformed script line
+    #[error("{0}")]
+    Script(String),
+    /// Transaction begun twice, ended without being begun, or a reset
+    /// during a transaction
+    #[error("{0}")]
+    Transaction(String),
+    /// The operation was cancelled, e.g. with Ctrl-C
+    #[error("Cancelled")]
+    Cancelled,
+}
+
+impl Error {
+    /// Exit status the CLI reports for this error
+    pub fn exit_status(&self) -> ExitStatus {
+        match self {
+            Error::Pcsc(e) => ExitStatus::from_pcsc(e),
+            Error::NoReaders | Error::ReaderNotFound(_) | Error::AmbiguousReader(_) => ExitStatus::NoReader,
+            Error::NoCard(_) => ExitStatus::NoCard,
+            Error::Parse(_) => ExitStatus::InvalidInput,
+            Error::StatusWord { .. } => ExitStatus::StatusWord,
+            Error::Script(_) | Error::Transaction(_) => ExitStatus::Script,
+            Error::Cancelled => ExitStatus::Cancelled,
+        }
+    }
+
+    /// "No card connected", the error of commands run before connecting
+    pub fn not_connected() -> Self {
+        Error::NoCard("No card connected".to_string())
+    }
+}
+
+/// Documented exit codes of the CLI
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    Success = 0,
+    /// Any error not covered below
+    Failure = 1,
+    /// Invalid command line (reported by the argument parser)
+    Usage = 2,
+    /// Invalid hex, control code, pattern or other input
+    InvalidInput = 3,
+    /// No reader, unknown reader, or no single reader matching the selector
+    NoReader = 4,
+    /// No card in the reader, or the card was removed or is unresponsive
+    NoCard = 5,
+    /// Other PC/SC or communication error
+    Pcsc = 6,
+    /// Status word assertion failed
+    StatusWord = 7,
+    /// Malformed script, or `begin`/`end` out of order
+    Script = 8,
+    /// Timed out
+    Timeout = 9,
+    /// Cancelled with Ctrl-C (the shell convention for SIGINT)
+    Cancelled = 130,
+}
+
+impl ExitStatus {
+    /// Exit status of a PC/SC error
+    pub fn from_pcsc(error: &pcsc::Error) -> Self {
+        use pcsc::Error as E;
+        match error {
+            E::NoReadersAvailable | E::UnknownReader | E::ReaderUnavailable => ExitStatus::NoReader,
+            E::NoSmartcard | E::RemovedCard | E::UnpoweredCard | E::UnresponsiveCard
+            | E::UnsupportedCard => ExitStatus::NoCard,
+            E::Timeout => ExitStatus::Timeout,
+            E::Cancelled => ExitStatus::Cancelled,
+            E::InvalidParameter | E::InvalidValue | E::InsufficientBuffer => ExitStatus::InvalidInput,
+            _ => ExitStatus::Pcsc,
+        }
+    }
+
+    /// Exit status of an error chain: the first typed error found, from the
+    /// outermost context to the root cause
+    pub fn of(error: &anyhow::Error) -> Self {
+        error.chain()
+            .find_map(|cause| {
+                cause.downcast_ref::<Error>().map(Error::exit_status)
+                    .or_else(|| cause.downcast_ref::<pcsc::Error>().map(ExitStatus::from_pcsc))
+            })
+            .unwrap_or(ExitStatus::Failure)
+    }
+
+    /// Numeric process exit code
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_exit_status_of_chain() {
+        let error = anyhow::Error::from(pcsc::Error::NoSmartcard).context("Failed to connect to reader");
+        assert_eq!(ExitStatus::of(&error), ExitStatus::NoCard);
+
+        let error = Err::<(), _>(Error::Pcsc(pcsc::Error::Timeout)).context("Transmit failed").unwrap_err();
+        assert_eq!(ExitStatus::of(&error), ExitStatus::Timeout);
+        assert_eq!(format!("{:#}", error), format!("Transmit failed: {}", pcsc::Error::Timeout));
+
+        let error = anyhow::Error::from(Error::Parse("Invalid hex string".into())).context("Failed to parse APDU");
+        assert_eq!(ExitStatus::of(&error), ExitStatus::InvalidInput);
+        assert_eq!(ExitStatus::of(&anyhow::anyhow!("Something else")), ExitStatus::Failure);
+
+        let error = anyhow::Error::from(Error::Transaction("No transaction in progress".into())).context("Line 3");
+        assert_eq!(ExitStatus::of(&error), ExitStatus::Script);
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(Error::NoReaders.exit_status().code(), 4);
+        assert_eq!(Error::not_connected().exit_status().code(), 5);
+        assert_eq!(ExitStatus::from_pcsc(&pcsc::Error::SharingViolation).code(), 6);
+        assert_eq!(ExitStatus::from_pcsc(&pcsc::Error::Cancelled).code(), 130);
+        assert_eq!(Error::Cancelled.exit_status().code(), 130);
+        let error = Error::StatusWord { expected: "9000".into(), sw1: 0x6A, sw2: 0x82, description: "File not found".into() };
+        assert_eq!(error.exit_status().code(), 7);
+        assert_eq!(error.to_string(), "Expected status word 9000, card returned 6A82 (File not found)");
+    }
+}
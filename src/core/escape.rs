@@ -0,0 +1,215 @@
+//! Vendor escape commands
+//!
+//! Readers take vendor commands (firmware version, LEDs and buzzer, RF field,
+//! polling...) through `SCardControl`, usually with the CCID escape control
+//! code. Each vendor has its own payloads; this catalog names the common ones
+//! so they do not have to be looked up in reference manuals every time.
+//!
+//! Payloads follow the vendors' reference manuals, which differ between
+//! firmware revisions: check them against the reader's manual when a command
+//! is refused. Adding a command is a matter of adding an entry to
+//! [`ESCAPE_COMMANDS`].
+
+use anyhow::{Result, bail};
+
+use crate::core::error::Error;
+use crate::core::utils::{ctl_code, parse_hex};
+
+/// `SCARD_CTL_CODE` function of the CCID escape command (`IOCTL_CCID_ESCAPE`)
+pub const ESCAPE_FUNCTION: u32 = 3500;
+
+/// A one-byte parameter of an escape command
+#[derive(Debug, Clone, Copy)]
+pub struct EscapeParam {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Value when the parameter is left out, `None` if it is required
+    pub default: Option<u8>,
+}
+
+impl EscapeParam {
+    const fn required(name: &'static str, description: &'static str) -> Self {
+        Self { name, description, default: None }
+    }
+
+    const fn optional(name: &'static str, description: &'static str, default: u8) -> Self {
+        Self { name, description, default: Some(default) }
+    }
+}
+
+/// A named vendor escape command
+#[derive(Debug, Clone, Copy)]
+pub struct EscapeDef {
+    pub name: &'static str,
+    pub vendor: &'static str,
+    /// Readers the command is documented for
+    pub readers: &'static str,
+    pub description: &'static str,
+    /// `SCARD_CTL_CODE` function the payload is sent to
+    pub function: u32,
+    /// Payload in hex, with a `{name}` placeholder for each parameter
+    pub template: &'static str,
+    pub params: &'static [EscapeParam],
+}
+
+impl EscapeDef {
+    const fn new(name: &'static str, vendor: &'static str, readers: &'static str, description: &'static str,
+                 template: &'static str, params: &'static [EscapeParam]) -> Self {
+        Self { name, vendor, readers, description, function: ESCAPE_FUNCTION, template, params }
+    }
+
+    /// Control code for this platform, `SCARD_CTL_CODE(function)`
+    pub fn control_code(&self) -> u32 {
+        ctl_code(self.function)
+    }
+
+    /// Command line syntax, e.g. `acr1252u-led <leds>`
+    pub fn usage(&self) -> String {
+        let mut usage = self.name.to_string();
+        for param in self.params {
+            match param.default {
+                Some(_) => usage.push_str(&format!(" [{}]", param.name)),
+                None => usage.push_str(&format!(" <{}>", param.name)),
+            }
+        }
+        usage
+    }
+
+    /// Build the payload from the parameter values, in order
+    ///
+    /// Values are decimal or hex with a `0x` prefix; trailing parameters with
+    /// a default can be left out.
+    pub fn encode<S: AsRef<str>>(&self, args: &[S]) -> Result<Vec<u8>> {
+        if args.len() > self.params.len() {
+            bail!(Error::Parse(format!("Too many arguments for {}: expected {}", self.name, self.usage())));
+        }
+        let mut payload = self.template.to_string();
+        for (i, param) in self.params.iter().enumerate() {
+            let value = match (args.get(i), param.default) {
+                (Some(arg), _) => parse_param(param, arg.as_ref())?,
+                (None, Some(default)) => default,
+                (None, None) => bail!(Error::Parse(format!(
+                    "Missing {} for {}: expected {}", param.name, self.name, self.usage()))),
+            };
+            payload = payload.replace(&format!("{{{}}}", param.name), &format!("{:02X}", value));
+        }
+        parse_hex(&payload)
+    }
+}
+
+fn parse_param(param: &EscapeParam, value: &str) -> Result<u8> {
+    let value = value.trim();
+    let parsed = match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => value.parse::<u8>(),
+    };
+    parsed.map_err(|_| Error::Parse(format!(
+        "Invalid {}: '{}' (expected a byte, decimal or 0x-prefixed hex)", param.name, value)).into())
+}
+
+const ACR122U_LED_BUZZER: &[EscapeParam] = &[
+    EscapeParam::required("leds", "LED state control: bits 0-1 final red/green, 2-3 masks, 4-5 initial blink states, 6-7 blink masks"),
+    EscapeParam::optional("on_time", "Blink on time, in units of 100 ms", 0x01),
+    EscapeParam::optional("off_time", "Blink off time, in units of 100 ms", 0x01),
+    EscapeParam::optional("repeat", "Number of blinks", 0x01),
+    EscapeParam::optional("buzzer", "Buzzer: 0 off, 1 on T1, 2 on T2, 3 on both", 0x01),
+];
+
+/// Known escape commands, grouped by vendor
+pub const ESCAPE_COMMANDS: &[EscapeDef] = &[
+    // ACS, PN53x-based readers; the commands are pseudo-APDUs
+    EscapeDef::new("acr122u-firmware", "ACS", "ACR122U", "Firmware version, as text",
+                   "FF 00 48 00 00", &[]),
+    EscapeDef::new("acr122u-led-buzzer", "ACS", "ACR122U", "Blink the LEDs and sound the buzzer",
+                   "FF 00 40 {leds} 04 {on_time} {off_time} {repeat} {buzzer}", ACR122U_LED_BUZZER),
+    EscapeDef::new("acr122u-rf-on", "ACS", "ACR122U", "Switch the RF field on",
+                   "FF 00 00 00 04 D4 32 01 01", &[]),
+    EscapeDef::new("acr122u-rf-off", "ACS", "ACR122U", "Switch the RF field off",
+                   "FF 00 00 00 04 D4 32 01 00", &[]),
+    EscapeDef::new("acr122u-polling", "ACS", "ACR122U", "Set the PICC operating parameter: auto polling and card types",
+                   "FF 00 51 {parameter} 00",
+                   &[EscapeParam::required("parameter", "Bit 7 auto polling, 6 auto ATS, 5 polling interval, 0-4 card types")]),
+    // ACS, readers with the E0 escape command set
+    EscapeDef::new("acr1252u-firmware", "ACS", "ACR1252U, ACR1281U, ACR1581U", "Firmware version, as text",
+                   "E0 00 00 18 00", &[]),
+    EscapeDef::new("acr1252u-serial", "ACS", "ACR1252U, ACR1581U", "Reader serial number, as text",
+                   "E0 00 00 33 00", &[]),
+    EscapeDef::new("acr1252u-led", "ACS", "ACR1252U, ACR1281U, ACR1581U", "Set the LEDs",
+                   "E0 00 00 29 01 {leds}",
+                   &[EscapeParam::required("leds", "Bit 0 red, bit 1 green")]),
+    EscapeDef::new("acr1252u-buzzer", "ACS", "ACR1252U, ACR1281U, ACR1581U", "Sound the buzzer",
+                   "E0 00 00 28 01 {duration}",
+                   &[EscapeParam::optional("duration", "Duration in units of 10 ms, 0 to turn it off", 0x0A)]),
+    EscapeDef::new("acr1252u-polling", "ACS", "ACR1252U, ACR1281U", "Set automatic PICC polling",
+                   "E0 00 00 23 01 {setting}",
+                   &[EscapeParam::required("setting", "Bit 0 auto polling, 1 turn off the antenna when no card, 4-5 polling interval")]),
+    // HID Omnikey, readers with the generic FF 70 07 6B command set
+    EscapeDef::new("omnikey-firmware", "HID Omnikey", "5022, 5122, 5422, 5x27 CK", "Firmware version",
+                   "FF 70 07 6B 08 A2 06 A0 04 A0 02 82 00 00", &[]),
+    EscapeDef::new("omnikey-serial", "HID Omnikey", "5022, 5122, 5422, 5x27 CK", "Reader serial number",
+                   "FF 70 07 6B 08 A2 06 A0 04 A0 02 92 00 00", &[]),
+    EscapeDef::new("omnikey-rf", "HID Omnikey", "5022, 5122, 5422, 5x27 CK", "Switch the RF field on or off",
+                   "FF 70 07 6B 09 A2 07 A1 05 A1 03 80 01 {state} 00",
+                   &[EscapeParam::required("state", "1 on, 0 off")]),
+    // Identiv, readers answering the Part 3 reader information pseudo-APDU
+    EscapeDef::new("identiv-firmware", "Identiv", "uTrust 3700 F, 3720 F, 4701 F", "Firmware version, as text",
+                   "FF 9A 01 06 00", &[]),
+    EscapeDef::new("identiv-reader-info", "Identiv", "uTrust 3700 F, 3720 F, 4701 F", "Reader information item",
+                   "FF 9A 01 {item} 00",
+                   &[EscapeParam::required("item", "1 vendor name, 2 vendor ID, 3 product ID, 6 firmware version")]),
+    // Feitian contactless readers, which take the ACR122U-style pseudo-APDUs
+    EscapeDef::new("feitian-firmware", "Feitian", "R502 CL, R701", "Firmware version, as text",
+                   "FF 00 48 00 00", &[]),
+    EscapeDef::new("feitian-led-buzzer", "Feitian", "R502 CL, R701", "Blink the LEDs and sound the buzzer",
+                   "FF 00 40 {leds} 04 {on_time} {off_time} {repeat} {buzzer}", ACR122U_LED_BUZZER),
+];
+
+/// Look up an escape command by name (`acr122u-firmware`, case and `_`
+/// versus `-` do not matter)
+pub fn escape_def(name: &str) -> Result<&'static EscapeDef> {
+    let name = name.trim().to_lowercase().replace('_', "-");
+    match ESCAPE_COMMANDS.iter().find(|def| def.name == name) {
+        Some(def) => Ok(def),
+        None => bail!(Error::Parse(format!(
+            "Unknown escape command: {} (see `pcsc-tester escape --list`)", name))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalog_is_consistent() {
+        for (i, def) in ESCAPE_COMMANDS.iter().enumerate() {
+            assert!(ESCAPE_COMMANDS[..i].iter().all(|other| other.name != def.name), "duplicate {}", def.name);
+            for param in def.params {
+                assert!(def.template.contains(&format!("{{{}}}", param.name)), "{} lacks {{{}}}", def.name, param.name);
+            }
+            // Every placeholder has a parameter, so the payload is valid hex
+            let args: Vec<&str> = def.params.iter().map(|_| "0").collect();
+            assert!(def.encode(&args).is_ok(), "{}", def.name);
+        }
+    }
+
+    #[test]
+    fn test_encode_escape() {
+        let def = escape_def("ACR122U_LED_BUZZER").unwrap();
+        assert_eq!(def.usage(), "acr122u-led-buzzer <leds> [on_time] [off_time] [repeat] [buzzer]");
+        assert_eq!(def.encode(&["0x0F"]).unwrap(), parse_hex("FF 00 40 0F 04 01 01 01 01").unwrap());
+        assert_eq!(def.encode(&["0x0F", "5", "0x05", "3"]).unwrap(), parse_hex("FF 00 40 0F 04 05 05 03 01").unwrap());
+        assert_eq!(escape_def("acr1252u-firmware").unwrap().encode::<&str>(&[]).unwrap(), vec![0xE0, 0x00, 0x00, 0x18, 0x00]);
+        assert_eq!(def.control_code(), pcsc::ctl_code(3500) as u32);
+    }
+
+    #[test]
+    fn test_encode_escape_errors() {
+        let def = escape_def("acr1252u-led").unwrap();
+        let error = def.encode::<&str>(&[]).unwrap_err();
+        assert_eq!(error.to_string(), "Missing leds for acr1252u-led: expected acr1252u-led <leds>");
+        let error = def.encode(&["256"]).unwrap_err();
+        assert!(error.to_string().contains("Invalid leds: '256'"), "{}", error);
+        assert!(def.encode(&["1", "2"]).unwrap_err().to_string().contains("Too many arguments"));
+        assert!(escape_def("beep").is_err());
+    }
+}
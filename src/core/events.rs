@@ -0,0 +1,170 @@
+//! Reader and card events derived from status changes
+
+use chrono::{DateTime, Utc};
+use pcsc::State;
+use serde::{Deserialize, Serialize};
+
+use crate::core::backend::ReaderState;
+use crate::core::utils::format_hex_spaced;
+
+/// Something that happened to a reader or its card
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ReaderEventKind {
+    ReaderAttached,
+    ReaderDetached,
+    CardInserted { atr: Vec<u8> },
+    CardRemoved,
+    ExclusiveChanged { exclusive: bool },
+    InUseChanged { in_use: bool },
+    MuteChanged { mute: bool },
+}
+
+/// Event reported by `PcscReader::watch`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReaderEvent {
+    pub timestamp: DateTime<Utc>,
+    pub reader: String,
+    #[serde(flatten)]
+    pub kind: ReaderEventKind,
+}
+
+impl ReaderEvent {
+    pub fn new(reader: &str, kind: ReaderEventKind) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            reader: reader.to_string(),
+            kind,
+        }
+    }
+
+    /// Serialize the event as a single JSON line
+    pub fn to_json_line(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+impl std::fmt::Display for ReaderEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}: ", self.timestamp.format("%H:%M:%S%.3f"), self.reader)?;
+        match &self.kind {
+            ReaderEventKind::ReaderAttached => write!(f, "reader attached"),
+            ReaderEventKind::ReaderDetached => write!(f, "reader detached"),
+            ReaderEventKind::CardInserted { atr } => write!(f, "card inserted (ATR: {})", format_hex_spaced(atr)),
+            ReaderEventKind::CardRemoved => write!(f, "card removed"),
+            ReaderEventKind::ExclusiveChanged { exclusive } => {
+                write!(f, "{}", if *exclusive { "card in exclusive use" } else { "card no longer in exclusive use" })
+            }
+            ReaderEventKind::InUseChanged { in_use } => {
+                write!(f, "{}", if *in_use { "card in use" } else { "card no longer in use" })
+            }
+            ReaderEventKind::MuteChanged { mute } => {
+                write!(f, "{}", if *mute { "card is mute" } else { "card no longer mute" })
+            }
+        }
+    }
+}
+
+/// Compute the events between the previously known state of a reader and
+/// the state reported by the last status change
+pub fn diff_reader_state(state: &ReaderState) -> Vec<ReaderEvent> {
+    let old = state.current_state;
+    let new = state.event_state;
+    let mut events = Vec::new();
+
+    if new.intersects(State::UNKNOWN | State::IGNORE) {
+        events.push(ReaderEvent::new(&state.name, ReaderEventKind::ReaderDetached));
+        return events;
+    }
+
+    let was_present = old.contains(State::PRESENT);
+    let is_present = new.contains(State::PRESENT);
+    // A quick removal and re-insertion only shows up in the event counter
+    let reinserted = was_present && is_present && state.event_count != state.current_count;
+
+    if (was_present && !is_present) || reinserted {
+        events.push(ReaderEvent::new(&state.name, ReaderEventKind::CardRemoved));
+    }
+    if (!was_present && is_present) || reinserted {
+        events.push(ReaderEvent::new(&state.name, ReaderEventKind::CardInserted { atr: state.atr.clone() }));
+    }
+
+    let flags = [
+        (State::EXCLUSIVE, ReaderEventKind::ExclusiveChanged { exclusive: new.contains(State::EXCLUSIVE) }),
+        (State::INUSE, ReaderEventKind::InUseChanged { in_use: new.contains(State::INUSE) }),
+        (State::MUTE, ReaderEventKind::MuteChanged { mute: new.contains(State::MUTE) }),
+    ];
+    for (flag, kind) in flags {
+        if old.contains(flag) != new.contains(flag) {
+            events.push(ReaderEvent::new(&state.name, kind));
+        }
+    }
+
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(old: State, new: State) -> ReaderState {
+        let mut state = ReaderState::new("Reader");
+        state.current_state = old;
+        state.event_state = new | State::CHANGED;
+        state
+    }
+
+    fn kinds(events: Vec<ReaderEvent>) -> Vec<ReaderEventKind> {
+        events.into_iter().map(|e| e.kind).collect()
+    }
+
+    #[test]
+    fn test_diff_card_insertion_and_removal() {
+        let mut inserted = state(State::EMPTY, State::PRESENT);
+        inserted.atr = vec![0x3B, 0x00];
+        assert_eq!(kinds(diff_reader_state(&inserted)),
+                   vec![ReaderEventKind::CardInserted { atr: vec![0x3B, 0x00] }]);
+
+        let removed = state(State::PRESENT, State::EMPTY);
+        assert_eq!(kinds(diff_reader_state(&removed)), vec![ReaderEventKind::CardRemoved]);
+    }
+
+    #[test]
+    fn test_diff_quick_reinsertion() {
+        let mut reinserted = state(State::PRESENT, State::PRESENT);
+        reinserted.current_count = 3;
+        reinserted.event_count = 5;
+        assert_eq!(kinds(diff_reader_state(&reinserted)),
+                   vec![ReaderEventKind::CardRemoved, ReaderEventKind::CardInserted { atr: vec![] }]);
+    }
+
+    #[test]
+    fn test_diff_state_flags() {
+        let changed = state(State::PRESENT | State::INUSE, State::PRESENT | State::EXCLUSIVE | State::MUTE);
+        assert_eq!(kinds(diff_reader_state(&changed)), vec![
+            ReaderEventKind::ExclusiveChanged { exclusive: true },
+            ReaderEventKind::InUseChanged { in_use: false },
+            ReaderEventKind::MuteChanged { mute: true },
+        ]);
+    }
+
+    #[test]
+    fn test_diff_reader_detached() {
+        let detached = state(State::PRESENT, State::UNKNOWN | State::UNAVAILABLE);
+        assert_eq!(kinds(diff_reader_state(&detached)), vec![ReaderEventKind::ReaderDetached]);
+    }
+
+    #[test]
+    fn test_event_formatting() {
+        let event = ReaderEvent::new("Reader", ReaderEventKind::CardInserted { atr: vec![0x3B, 0x02] });
+        assert!(event.to_string().ends_with("Reader: card inserted (ATR: 3B 02)"));
+
+        let json = event.to_json_line();
+        assert!(json.contains("\"event\":\"card_inserted\""));
+        assert!(json.contains("\"reader\":\"Reader\""));
+        assert!(!json.contains('\n'));
+
+        let parsed: ReaderEvent = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, event);
+    }
+}
@@ -0,0 +1,462 @@
+//! Reader features (PC/SC Part 10)
+//!
+//! Readers with a PIN pad, a display or vendor commands advertise them in
+//! answer to the `CM_IOCTL_GET_FEATURE_REQUEST` control code: a list of TLVs
+//! giving, for each feature, its tag and the control code to use for it.
+//! Those control codes are assigned by the driver and differ between readers
+//! and platforms, so they are looked up rather than hard-coded.
+//!
+//! Two of those features describe the reader itself: `GET_TLV_PROPERTIES`
+//! answers with property TLVs, and the older `IFD_PIN_PROPERTIES` with a
+//! fixed structure covering the PIN pad only. Both decode to
+//! [`ReaderProperties`].
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::core::error::Error;
+use crate::core::pinpad::{VALIDATE_KEY, VALIDATE_MAX_LENGTH, VALIDATE_TIMEOUT};
+use crate::core::utils::{format_hex, parse_control_code};
+
+/// Control code asking the reader for its features, `SCARD_CTL_CODE(3400)`
+pub const GET_FEATURE_REQUEST: u32 = pcsc::ctl_code(3400) as u32;
+
+pub const FEATURE_VERIFY_PIN_START: u8 = 0x01;
+pub const FEATURE_VERIFY_PIN_FINISH: u8 = 0x02;
+pub const FEATURE_MODIFY_PIN_START: u8 = 0x03;
+pub const FEATURE_MODIFY_PIN_FINISH: u8 = 0x04;
+pub const FEATURE_GET_KEY_PRESSED: u8 = 0x05;
+pub const FEATURE_VERIFY_PIN_DIRECT: u8 = 0x06;
+pub const FEATURE_MODIFY_PIN_DIRECT: u8 = 0x07;
+pub const FEATURE_MCT_READER_DIRECT: u8 = 0x08;
+pub const FEATURE_MCT_UNIVERSAL: u8 = 0x09;
+pub const FEATURE_IFD_PIN_PROPERTIES: u8 = 0x0A;
+pub const FEATURE_ABORT: u8 = 0x0B;
+pub const FEATURE_SET_SPE_MESSAGE: u8 = 0x0C;
+pub const FEATURE_VERIFY_PIN_DIRECT_APP_ID: u8 = 0x0D;
+pub const FEATURE_MODIFY_PIN_DIRECT_APP_ID: u8 = 0x0E;
+pub const FEATURE_WRITE_DISPLAY: u8 = 0x0F;
+pub const FEATURE_GET_KEY: u8 = 0x10;
+pub const FEATURE_IFD_DISPLAY_PROPERTIES: u8 = 0x11;
+pub const FEATURE_GET_TLV_PROPERTIES: u8 = 0x12;
+pub const FEATURE_CCID_ESC_COMMAND: u8 = 0x13;
+pub const FEATURE_EXECUTE_PACE: u8 = 0x20;
+
+/// A known Part 10 feature
+#[derive(Debug, Clone, Copy)]
+pub struct FeatureDef {
+    pub tag: u8,
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+impl FeatureDef {
+    const fn new(tag: u8, name: &'static str, description: &'static str) -> Self {
+        Self { tag, name, description }
+    }
+}
+
+/// Known features, by tag
+pub const KNOWN_FEATURES: &[FeatureDef] = &[
+    FeatureDef::new(FEATURE_VERIFY_PIN_START, "VERIFY_PIN_START", "Start a PIN verification"),
+    FeatureDef::new(FEATURE_VERIFY_PIN_FINISH, "VERIFY_PIN_FINISH", "Finish a PIN verification"),
+    FeatureDef::new(FEATURE_MODIFY_PIN_START, "MODIFY_PIN_START", "Start a PIN modification"),
+    FeatureDef::new(FEATURE_MODIFY_PIN_FINISH, "MODIFY_PIN_FINISH", "Finish a PIN modification"),
+    FeatureDef::new(FEATURE_GET_KEY_PRESSED, "GET_KEY_PRESSED", "Key pressed during PIN entry"),
+    FeatureDef::new(FEATURE_VERIFY_PIN_DIRECT, "VERIFY_PIN_DIRECT", "PIN verification on the PIN pad"),
+    FeatureDef::new(FEATURE_MODIFY_PIN_DIRECT, "MODIFY_PIN_DIRECT", "PIN modification on the PIN pad"),
+    FeatureDef::new(FEATURE_MCT_READER_DIRECT, "MCT_READER_DIRECT", "MKT reader commands"),
+    FeatureDef::new(FEATURE_MCT_UNIVERSAL, "MCT_UNIVERSAL", "MKT universal commands"),
+    FeatureDef::new(FEATURE_IFD_PIN_PROPERTIES, "IFD_PIN_PROPERTIES", "PIN pad properties"),
+    FeatureDef::new(FEATURE_ABORT, "ABORT", "Abort PIN entry"),
+    FeatureDef::new(FEATURE_SET_SPE_MESSAGE, "SET_SPE_MESSAGE", "Set the PIN entry messages"),
+    FeatureDef::new(FEATURE_VERIFY_PIN_DIRECT_APP_ID, "VERIFY_PIN_DIRECT_APP_ID", "PIN verification for an application"),
+    FeatureDef::new(FEATURE_MODIFY_PIN_DIRECT_APP_ID, "MODIFY_PIN_DIRECT_APP_ID", "PIN modification for an application"),
+    FeatureDef::new(FEATURE_WRITE_DISPLAY, "WRITE_DISPLAY", "Write to the display"),
+    FeatureDef::new(FEATURE_GET_KEY, "GET_KEY", "Read a key from the keypad"),
+    FeatureDef::new(FEATURE_IFD_DISPLAY_PROPERTIES, "IFD_DISPLAY_PROPERTIES", "Display properties"),
+    FeatureDef::new(FEATURE_GET_TLV_PROPERTIES, "GET_TLV_PROPERTIES", "Reader properties"),
+    FeatureDef::new(FEATURE_CCID_ESC_COMMAND, "CCID_ESC_COMMAND", "Vendor escape commands"),
+    FeatureDef::new(FEATURE_EXECUTE_PACE, "EXECUTE_PACE", "PACE authentication"),
+];
+
+/// Look up the definition of a feature tag
+pub fn feature_def(tag: u8) -> Option<&'static FeatureDef> {
+    KNOWN_FEATURES.iter().find(|def| def.tag == tag)
+}
+
+/// Parse a feature given by name (e.g. `verify_pin_direct` or
+/// `FEATURE_VERIFY_PIN_DIRECT`) or tag (e.g. `0x06`)
+pub fn parse_feature(s: &str) -> Result<u8> {
+    let name = s.trim().to_uppercase().replace('-', "_");
+    let name = name.strip_prefix("FEATURE_").unwrap_or(&name);
+    if let Some(def) = KNOWN_FEATURES.iter().find(|def| def.name == name) {
+        return Ok(def.tag);
+    }
+    match parse_control_code(s) {
+        Ok(tag) if tag <= 0xFF => Ok(tag as u8),
+        _ => bail!(Error::Parse(format!("Unknown feature: {}", s))),
+    }
+}
+
+/// A feature reported by a reader
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Feature {
+    pub tag: u8,
+    pub name: String,
+    /// Control code to send the feature's requests to
+    pub control_code: u32,
+}
+
+impl Feature {
+    pub fn new(tag: u8, control_code: u32) -> Self {
+        let name = feature_def(tag).map_or("unknown", |def| def.name);
+        Self { tag, name: name.to_string(), control_code }
+    }
+}
+
+/// Features reported by a reader
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ReaderFeatures {
+    features: Vec<Feature>,
+}
+
+impl ReaderFeatures {
+    /// Decode the answer to [`GET_FEATURE_REQUEST`]: TLVs made of a tag, a
+    /// length of 4 and a big-endian control code
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut features = Vec::new();
+        let mut rest = data;
+        while !rest.is_empty() {
+            let (tag, length, value) = match rest {
+                [tag, length, value @ ..] if value.len() >= *length as usize => {
+                    (*tag, *length as usize, &value[..*length as usize])
+                }
+                _ => bail!(Error::Parse(format!("Truncated feature TLV: {}", format_hex(rest)))),
+            };
+            let code: [u8; 4] = value.try_into().map_err(|_| Error::Parse(format!(
+                "Feature 0x{:02X} has a {}-byte control code, expected 4", tag, length)))?;
+            features.push(Feature::new(tag, u32::from_be_bytes(code)));
+            rest = &rest[2 + length..];
+        }
+        Ok(Self { features })
+    }
+
+    /// Control code of a feature, if the reader has it
+    pub fn control_code(&self, tag: u8) -> Option<u32> {
+        self.features.iter()
+            .find(|feature| feature.tag == tag)
+            .map(|feature| feature.control_code)
+    }
+
+    /// Control code of a feature the operation cannot do without
+    pub fn require(&self, tag: u8) -> Result<u32> {
+        match self.control_code(tag) {
+            Some(code) => Ok(code),
+            None => {
+                let name = feature_def(tag).map_or_else(|| format!("0x{:02X}", tag), |def| def.name.to_string());
+                Err(anyhow::Error::new(pcsc::Error::UnsupportedFeature)
+                    .context(format!("Reader does not support {}", name)))
+            }
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Feature> {
+        self.features.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.features.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.features.len()
+    }
+}
+
+pub const PROPERTY_LCD_LAYOUT: u8 = 0x01;
+pub const PROPERTY_ENTRY_VALIDATION_CONDITION: u8 = 0x02;
+pub const PROPERTY_TIMEOUT2: u8 = 0x03;
+pub const PROPERTY_LCD_MAX_CHARACTERS: u8 = 0x04;
+pub const PROPERTY_LCD_MAX_LINES: u8 = 0x05;
+pub const PROPERTY_MIN_PIN_SIZE: u8 = 0x06;
+pub const PROPERTY_MAX_PIN_SIZE: u8 = 0x07;
+pub const PROPERTY_FIRMWARE_ID: u8 = 0x08;
+pub const PROPERTY_PPDU_SUPPORT: u8 = 0x09;
+pub const PROPERTY_MAX_APDU_DATA_SIZE: u8 = 0x0A;
+pub const PROPERTY_VENDOR_ID: u8 = 0x0B;
+pub const PROPERTY_PRODUCT_ID: u8 = 0x0C;
+
+/// `bPPDUSupport`: pseudo-APDUs go through `CCID_ESC_COMMAND`
+pub const PPDU_OVER_CONTROL: u8 = 0x01;
+/// `bPPDUSupport`: pseudo-APDUs go through `SCardTransmit`
+pub const PPDU_OVER_TRANSMIT: u8 = 0x02;
+
+/// Reader properties, from `GET_TLV_PROPERTIES` or `IFD_PIN_PROPERTIES`
+///
+/// Readers only report some of them; the others are `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReaderProperties {
+    /// `wLcdLayout`: lines in the high byte, characters per line in the low
+    /// byte, 0 without a display
+    pub lcd_layout: Option<u16>,
+    /// `bEntryValidationCondition`: `VALIDATE_*` flags the PIN pad supports
+    pub entry_validation_condition: Option<u8>,
+    /// `bTimeOut2`: PIN entry timeout in seconds, 0 for the reader default
+    pub timeout2: Option<u8>,
+    pub lcd_max_characters: Option<u16>,
+    pub lcd_max_lines: Option<u16>,
+    pub min_pin_size: Option<u8>,
+    pub max_pin_size: Option<u8>,
+    pub firmware_id: Option<String>,
+    /// `bPPDUSupport`: `PPDU_*` flags
+    pub ppdu_support: Option<u8>,
+    /// `dwMaxAPDUDataSize`: largest command data the reader passes on, above
+    /// 255 when it supports extended-length APDUs
+    pub max_apdu_data_size: Option<u32>,
+    pub vendor_id: Option<u16>,
+    pub product_id: Option<u16>,
+}
+
+impl ReaderProperties {
+    /// Decode the answer to `GET_TLV_PROPERTIES`: TLVs made of a tag, a
+    /// length and a little-endian value
+    ///
+    /// Unknown tags are skipped.
+    pub fn parse_tlv(data: &[u8]) -> Result<Self> {
+        let mut properties = Self::default();
+        let mut rest = data;
+        while !rest.is_empty() {
+            let (tag, value) = match rest {
+                [tag, length, value @ ..] if value.len() >= *length as usize => (*tag, &value[..*length as usize]),
+                _ => bail!(Error::Parse(format!("Truncated property TLV: {}", format_hex(rest)))),
+            };
+            rest = &rest[2 + value.len()..];
+            match tag {
+                PROPERTY_LCD_LAYOUT => properties.lcd_layout = Some(property_u16(tag, value)?),
+                PROPERTY_ENTRY_VALIDATION_CONDITION => properties.entry_validation_condition = Some(property_u8(tag, value)?),
+                PROPERTY_TIMEOUT2 => properties.timeout2 = Some(property_u8(tag, value)?),
+                PROPERTY_LCD_MAX_CHARACTERS => properties.lcd_max_characters = Some(property_u16(tag, value)?),
+                PROPERTY_LCD_MAX_LINES => properties.lcd_max_lines = Some(property_u16(tag, value)?),
+                PROPERTY_MIN_PIN_SIZE => properties.min_pin_size = Some(property_u8(tag, value)?),
+                PROPERTY_MAX_PIN_SIZE => properties.max_pin_size = Some(property_u8(tag, value)?),
+                PROPERTY_FIRMWARE_ID => {
+                    let firmware = String::from_utf8_lossy(value);
+                    properties.firmware_id = Some(firmware.trim_end_matches('\0').trim().to_string());
+                }
+                PROPERTY_PPDU_SUPPORT => properties.ppdu_support = Some(property_u8(tag, value)?),
+                PROPERTY_MAX_APDU_DATA_SIZE => properties.max_apdu_data_size = Some(property_u32(tag, value)?),
+                PROPERTY_VENDOR_ID => properties.vendor_id = Some(property_u16(tag, value)?),
+                PROPERTY_PRODUCT_ID => properties.product_id = Some(property_u16(tag, value)?),
+                _ => log::debug!("Skipping unknown reader property 0x{:02X}: {}", tag, format_hex(value)),
+            }
+        }
+        Ok(properties)
+    }
+
+    /// Decode the answer to `IFD_PIN_PROPERTIES`: `wLcdLayout`,
+    /// `bEntryValidationCondition` and `bTimeOut2`
+    pub fn parse_pin_properties(data: &[u8]) -> Result<Self> {
+        match data {
+            [layout_lo, layout_hi, validation, timeout2, ..] => Ok(Self {
+                lcd_layout: Some(u16::from_le_bytes([*layout_lo, *layout_hi])),
+                entry_validation_condition: Some(*validation),
+                timeout2: Some(*timeout2),
+                ..Self::default()
+            }),
+            _ => bail!(Error::Parse(format!(
+                "PIN properties are {} byte(s) long, expected at least 4: {}", data.len(), format_hex(data)))),
+        }
+    }
+
+    /// Display size as (lines, characters per line), `None` without a display
+    pub fn lcd_size(&self) -> Option<(u8, u8)> {
+        let [columns, lines] = self.lcd_layout?.to_le_bytes();
+        (self.lcd_layout != Some(0)).then_some((lines, columns))
+    }
+
+    /// Whether the reader takes extended-length APDUs, if it says
+    pub fn supports_extended_apdus(&self) -> Option<bool> {
+        self.max_apdu_data_size.map(|size| size > 255)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The properties the reader reported, as label and value for display
+    pub fn describe(&self) -> Vec<(&'static str, String)> {
+        let mut lines = Vec::new();
+        if let Some(firmware) = &self.firmware_id {
+            lines.push(("Firmware", firmware.clone()));
+        }
+        if let Some(vendor) = self.vendor_id {
+            lines.push(("Vendor ID", format!("0x{:04X}", vendor)));
+        }
+        if let Some(product) = self.product_id {
+            lines.push(("Product ID", format!("0x{:04X}", product)));
+        }
+        if let Some(size) = self.max_apdu_data_size {
+            let kind = if size > 255 { "extended" } else { "short" };
+            lines.push(("Max APDU data", format!("{} bytes ({} APDUs)", size, kind)));
+        }
+        if let Some(support) = self.ppdu_support {
+            let mut ways = Vec::new();
+            if support & PPDU_OVER_CONTROL != 0 {
+                ways.push("control");
+            }
+            if support & PPDU_OVER_TRANSMIT != 0 {
+                ways.push("transmit");
+            }
+            lines.push(("Pseudo-APDUs", if ways.is_empty() { "none".to_string() } else { ways.join(", ") }));
+        }
+        if self.lcd_layout.is_some() {
+            lines.push(("Display", match self.lcd_size() {
+                Some((lines, columns)) => format!("{} line(s) of {} characters", lines, columns),
+                None => "none".to_string(),
+            }));
+        }
+        if let (Some(characters), Some(max_lines)) = (self.lcd_max_characters, self.lcd_max_lines) {
+            lines.push(("Display maximum", format!("{} line(s) of {} characters", max_lines, characters)));
+        }
+        match (self.min_pin_size, self.max_pin_size) {
+            (Some(min), Some(max)) => lines.push(("PIN length", format!("{} to {} digits", min, max))),
+            (Some(min), None) => lines.push(("PIN length", format!("at least {} digits", min))),
+            (None, Some(max)) => lines.push(("PIN length", format!("at most {} digits", max))),
+            (None, None) => {}
+        }
+        if let Some(validation) = self.entry_validation_condition {
+            let mut conditions = Vec::new();
+            if validation & VALIDATE_MAX_LENGTH != 0 {
+                conditions.push("maximum length");
+            }
+            if validation & VALIDATE_KEY != 0 {
+                conditions.push("validation key");
+            }
+            if validation & VALIDATE_TIMEOUT != 0 {
+                conditions.push("timeout");
+            }
+            let conditions = if conditions.is_empty() { "none".to_string() } else { conditions.join(", ") };
+            lines.push(("PIN entry ends on", conditions));
+        }
+        if let Some(timeout) = self.timeout2 {
+            lines.push(("PIN entry timeout", if timeout == 0 { "reader default".to_string() } else { format!("{} s", timeout) }));
+        }
+        lines
+    }
+}
+
+fn property_u8(tag: u8, value: &[u8]) -> Result<u8> {
+    Ok(property_bytes::<1>(tag, value)?[0])
+}
+
+fn property_u16(tag: u8, value: &[u8]) -> Result<u16> {
+    property_bytes(tag, value).map(u16::from_le_bytes)
+}
+
+fn property_u32(tag: u8, value: &[u8]) -> Result<u32> {
+    property_bytes(tag, value).map(u32::from_le_bytes)
+}
+
+fn property_bytes<const N: usize>(tag: u8, value: &[u8]) -> Result<[u8; N]> {
+    value.try_into().map_err(|_| anyhow::Error::new(Error::Parse(format!(
+        "Property 0x{:02X} has a {}-byte value, expected {}", tag, value.len(), N))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_features() {
+        let features = ReaderFeatures::parse(&[
+            0x06, 0x04, 0x42, 0x33, 0x00, 0x06,
+            0x0A, 0x04, 0x42, 0x33, 0x00, 0x0A,
+            0x42, 0x04, 0x00, 0x00, 0x00, 0x01,
+        ]).unwrap();
+        assert_eq!(features.len(), 3);
+        assert_eq!(features.control_code(FEATURE_VERIFY_PIN_DIRECT), Some(0x42330006));
+        assert_eq!(features.control_code(FEATURE_MODIFY_PIN_DIRECT), None);
+        let names: Vec<&str> = features.iter().map(|feature| feature.name.as_str()).collect();
+        assert_eq!(names, vec!["VERIFY_PIN_DIRECT", "IFD_PIN_PROPERTIES", "unknown"]);
+        assert!(ReaderFeatures::parse(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_features_malformed() {
+        let error = ReaderFeatures::parse(&[0x06, 0x04, 0x42, 0x33]).unwrap_err();
+        assert!(error.to_string().contains("Truncated feature TLV: 06044233"), "{}", error);
+        let error = ReaderFeatures::parse(&[0x06, 0x02, 0x42, 0x33]).unwrap_err();
+        assert!(error.to_string().contains("2-byte control code"), "{}", error);
+    }
+
+    #[test]
+    fn test_require_feature() {
+        let features = ReaderFeatures::parse(&[0x13, 0x04, 0x42, 0x00, 0x0D, 0xAC]).unwrap();
+        assert_eq!(features.require(FEATURE_CCID_ESC_COMMAND).unwrap(), 0x42000DAC);
+        let error = features.require(FEATURE_MODIFY_PIN_DIRECT).unwrap_err();
+        assert_eq!(error.to_string(), "Reader does not support MODIFY_PIN_DIRECT");
+    }
+
+    #[test]
+    fn test_parse_feature() {
+        assert_eq!(parse_feature("verify_pin_direct").unwrap(), FEATURE_VERIFY_PIN_DIRECT);
+        assert_eq!(parse_feature("FEATURE_CCID_ESC_COMMAND").unwrap(), FEATURE_CCID_ESC_COMMAND);
+        assert_eq!(parse_feature("get-tlv-properties").unwrap(), FEATURE_GET_TLV_PROPERTIES);
+        assert_eq!(parse_feature("0x0A").unwrap(), FEATURE_IFD_PIN_PROPERTIES);
+        assert!(parse_feature("pinpad").is_err());
+        assert!(parse_feature("0x100").is_err());
+    }
+
+    #[test]
+    fn test_parse_tlv_properties() {
+        let properties = ReaderProperties::parse_tlv(&[
+            0x01, 0x02, 0x10, 0x02,
+            0x02, 0x01, 0x02,
+            0x03, 0x01, 0x00,
+            0x06, 0x01, 0x04,
+            0x07, 0x01, 0x08,
+            0x08, 0x06, b'1', b'.', b'2', b'3', 0x00, 0x00,
+            0x0A, 0x04, 0x00, 0x01, 0x00, 0x00,
+            0x0B, 0x02, 0x2F, 0x07,
+            0x0C, 0x02, 0xCC, 0x90,
+            0x7F, 0x01, 0xAA,
+        ]).unwrap();
+        assert_eq!(properties.lcd_size(), Some((2, 16)));
+        assert_eq!(properties.entry_validation_condition, Some(VALIDATE_KEY));
+        assert_eq!(properties.min_pin_size, Some(4));
+        assert_eq!(properties.max_pin_size, Some(8));
+        assert_eq!(properties.firmware_id.as_deref(), Some("1.23"));
+        assert_eq!(properties.max_apdu_data_size, Some(256));
+        assert_eq!(properties.supports_extended_apdus(), Some(true));
+        assert_eq!(properties.vendor_id, Some(0x072F));
+        assert_eq!(properties.product_id, Some(0x90CC));
+        assert_eq!(properties.ppdu_support, None);
+
+        let described = properties.describe();
+        assert!(described.contains(&("Max APDU data", "256 bytes (extended APDUs)".to_string())), "{:?}", described);
+        assert!(described.contains(&("Display", "2 line(s) of 16 characters".to_string())), "{:?}", described);
+        assert!(described.contains(&("PIN entry timeout", "reader default".to_string())), "{:?}", described);
+        assert!(ReaderProperties::parse_tlv(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_tlv_properties_malformed() {
+        let error = ReaderProperties::parse_tlv(&[0x0A, 0x04, 0x00, 0x01]).unwrap_err();
+        assert!(error.to_string().contains("Truncated property TLV: 0A040001"), "{}", error);
+        let error = ReaderProperties::parse_tlv(&[0x0A, 0x02, 0x00, 0x01]).unwrap_err();
+        assert!(error.to_string().contains("Property 0x0A has a 2-byte value, expected 4"), "{}", error);
+    }
+
+    #[test]
+    fn test_parse_pin_properties() {
+        let properties = ReaderProperties::parse_pin_properties(&[0x00, 0x00, 0x07, 0x1E]).unwrap();
+        assert_eq!(properties.lcd_layout, Some(0));
+        assert_eq!(properties.lcd_size(), None);
+        assert_eq!(properties.entry_validation_condition, Some(VALIDATE_MAX_LENGTH | VALIDATE_KEY | VALIDATE_TIMEOUT));
+        assert_eq!(properties.timeout2, Some(30));
+        assert_eq!(properties.max_apdu_data_size, None);
+        assert!(ReaderProperties::parse_pin_properties(&[0x00, 0x00]).is_err());
+    }
+}
@@ -0,0 +1,284 @@
+//! Connections to several readers at once
+//!
+//! A [`PcscReader`] holds a single card connection. [`SessionManager`] keeps
+//! any number of them side by side, each under a user-chosen alias such as
+//! `host` or `sam`, so that flows needing the card and a SAM at the same
+//! time can address both by name. All sessions share one backend.
+
+use anyhow::{Result, Context as AnyhowContext, bail};
+use pcsc::{Disposition, Scope, ShareMode};
+use std::sync::Arc;
+
+use crate::core::backend::{BackendSpec, DynBackend, PcscBackend, ProtocolSelection, SystemBackend};
+use crate::core::cancel::CancelToken;
+use crate::core::commands::{CommandExecutor, ControlResult, TransmitResult};
+use crate::core::error::Error;
+use crate::core::reader::PcscReader;
+
+/// A connection to one reader, known by its alias
+pub struct ReaderSession<B: PcscBackend> {
+    alias: String,
+    reader_name: String,
+    share_mode: ShareMode,
+    protocol: ProtocolSelection,
+    reader: PcscReader<Arc<B>>,
+}
+
+impl<B: PcscBackend> ReaderSession<B> {
+    /// Alias the session was opened with
+    pub fn alias(&self) -> &str {
+        &self.alias
+    }
+
+    /// Name of the reader the session is bound to
+    pub fn reader_name(&self) -> &str {
+        &self.reader_name
+    }
+
+    /// Share mode the session connects with
+    pub fn share_mode(&self) -> ShareMode {
+        self.share_mode
+    }
+
+    /// Protocols the session accepts
+    pub fn protocol_selection(&self) -> ProtocolSelection {
+        self.protocol
+    }
+
+    /// Reader manager holding the session's connection
+    pub fn reader(&self) -> &PcscReader<Arc<B>> {
+        &self.reader
+    }
+
+    /// Mutable reader manager holding the session's connection
+    pub fn reader_mut(&mut self) -> &mut PcscReader<Arc<B>> {
+        &mut self.reader
+    }
+
+    /// Connect again to the session's reader with its original settings,
+    /// e.g. after the card was swapped
+    pub fn reopen(&mut self) -> Result<()> {
+        self.reader.disconnect(Disposition::LeaveCard)?;
+        self.reader.connect_with_protocol(&self.reader_name, self.share_mode, self.protocol)
+            .with_context(|| format!("Failed to reconnect session {}", self.alias))
+    }
+}
+
+/// Manager for connections to several readers, addressed by alias
+pub struct SessionManager<B: PcscBackend = SystemBackend> {
+    backend: Arc<B>,
+    /// Connection-less reader manager used for listing and waiting
+    readers: PcscReader<Arc<B>>,
+    sessions: Vec<ReaderSession<B>>,
+}
+
+impl SessionManager {
+    /// Create a session manager on the platform PC/SC service
+    pub fn new() -> Result<Self> {
+        Self::with_scope(Scope::User)
+    }
+
+    /// Create a session manager with a PC/SC context of the given scope
+    pub fn with_scope(scope: Scope) -> Result<Self> {
+        let backend = SystemBackend::establish(scope)
+            .context("Failed to establish PCSC context")?;
+
+        Ok(Self::with_backend(backend))
+    }
+}
+
+impl SessionManager<DynBackend> {
+    /// Create a session manager on the backend selected at runtime
+    pub fn open(spec: &BackendSpec) -> Result<Self> {
+        Ok(Self::with_backend(spec.open()?))
+    }
+
+    /// Create a session manager on the backend selected at runtime, with a
+    /// context of the given scope
+    pub fn open_with_scope(spec: &BackendSpec, scope: Scope) -> Result<Self> {
+        Ok(Self::with_backend(spec.open_with_scope(scope)?))
+    }
+}
+
+impl<B: PcscBackend> SessionManager<B> {
+    /// Create a session manager on top of an arbitrary backend
+    pub fn with_backend(backend: B) -> Self {
+        let backend = Arc::new(backend);
+        Self {
+            readers: PcscReader::with_backend(Arc::clone(&backend)),
+            backend,
+            sessions: Vec::new(),
+        }
+    }
+
+    /// Reader manager for listing readers and waiting for cards
+    pub fn readers(&self) -> &PcscReader<Arc<B>> {
+        &self.readers
+    }
+
+    /// Mutable reader manager, e.g. to restrict listing to a reader group
+    pub fn readers_mut(&mut self) -> &mut PcscReader<Arc<B>> {
+        &mut self.readers
+    }
+
+    /// Share a cancellation token with the reader manager and every
+    /// session, including the ones opened later
+    pub fn set_cancel_token(&mut self, token: CancelToken) {
+        for session in &mut self.sessions {
+            session.reader.set_cancel_token(token.clone());
+        }
+        self.readers.set_cancel_token(token);
+    }
+
+    /// Get the token interrupting the blocking operations of all sessions
+    pub fn cancel_token(&self) -> &CancelToken {
+        self.readers.cancel_token()
+    }
+
+    /// Connect to a reader and keep the connection under `alias`
+    pub fn connect(&mut self, alias: &str, reader_name: &str, share_mode: ShareMode, protocol: ProtocolSelection) -> Result<&mut ReaderSession<B>> {
+        validate_alias(alias)?;
+        if self.contains(alias) {
+            bail!(Error::Parse(format!("Alias already in use: {}", alias)));
+        }
+
+        let mut reader = PcscReader::with_backend(Arc::clone(&self.backend));
+        reader.set_cancel_token(self.cancel_token().clone());
+        reader.connect_with_protocol(reader_name, share_mode, protocol)?;
+        log::info!("Session {} opened on {}", alias, reader_name);
+
+        self.sessions.push(ReaderSession {
+            alias: alias.to_string(),
+            reader_name: reader_name.to_string(),
+            share_mode,
+            protocol,
+            reader,
+        });
+        Ok(self.sessions.last_mut().expect("session was just added"))
+    }
+
+    /// Close the session `alias`, leaving, resetting, unpowering or
+    /// ejecting its card according to `disposition`
+    pub fn disconnect(&mut self, alias: &str, disposition: Disposition) -> Result<()> {
+        let index = self.position(alias)?;
+        let mut session = self.sessions.remove(index);
+        session.reader.disconnect(disposition)?;
+        log::info!("Session {} closed", alias);
+        Ok(())
+    }
+
+    /// Close every session
+    pub fn disconnect_all(&mut self, disposition: Disposition) -> Result<()> {
+        for mut session in self.sessions.drain(..) {
+            session.reader.disconnect(disposition)?;
+        }
+        Ok(())
+    }
+
+    /// Check if a session exists under `alias`
+    pub fn contains(&self, alias: &str) -> bool {
+        self.sessions.iter().any(|s| s.alias == alias)
+    }
+
+    /// Get the session opened under `alias`
+    pub fn session(&self, alias: &str) -> Result<&ReaderSession<B>> {
+        let index = self.position(alias)?;
+        Ok(&self.sessions[index])
+    }
+
+    /// Get the session opened under `alias`, mutably
+    pub fn session_mut(&mut self, alias: &str) -> Result<&mut ReaderSession<B>> {
+        let index = self.position(alias)?;
+        Ok(&mut self.sessions[index])
+    }
+
+    /// Open sessions, in the order they were opened
+    pub fn sessions(&self) -> &[ReaderSession<B>] {
+        &self.sessions
+    }
+
+    /// Open sessions, mutably
+    pub fn sessions_mut(&mut self) -> &mut [ReaderSession<B>] {
+        &mut self.sessions
+    }
+
+    /// Aliases of the open sessions, in the order they were opened
+    pub fn aliases(&self) -> impl Iterator<Item = &str> {
+        self.sessions.iter().map(|s| s.alias.as_str())
+    }
+
+    /// Send an APDU to the card of session `alias`
+    pub fn transmit(&mut self, executor: &mut CommandExecutor, alias: &str, apdu_hex: &str) -> Result<TransmitResult> {
+        let session = self.session_mut(alias)?;
+        executor.transmit(&mut session.reader, apdu_hex)
+    }
+
+    /// Send a control command to the reader of session `alias`
+    pub fn control(&mut self, executor: &mut CommandExecutor, alias: &str, code: u32, data_hex: &str) -> Result<ControlResult> {
+        let session = self.session_mut(alias)?;
+        executor.control(&mut session.reader, code, data_hex)
+    }
+
+    fn position(&self, alias: &str) -> Result<usize> {
+        self.sessions.iter()
+            .position(|s| s.alias == alias)
+            .ok_or_else(|| Error::Script(format!("Unknown session: {}", alias)).into())
+    }
+}
+
+/// Check that an alias is usable: a letter followed by letters, digits,
+/// `-` or `_`
+pub fn validate_alias(alias: &str) -> Result<()> {
+    let mut chars = alias.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        bail!(Error::Parse(format!("Invalid session alias: {:?} (expected a letter followed by letters, digits, '-' or '_')", alias)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::backend::{SimBackend, sim::SimConfig};
+    use crate::core::error::ExitStatus;
+
+    fn manager() -> SessionManager<SimBackend> {
+        SessionManager::with_backend(SimBackend::new(&SimConfig::default()).unwrap())
+    }
+
+    #[test]
+    fn test_validate_alias() {
+        assert!(validate_alias("sam").is_ok());
+        assert!(validate_alias("host-1").is_ok());
+        assert!(validate_alias("card_b").is_ok());
+        assert!(validate_alias("").is_err());
+        assert!(validate_alias("0").is_err());
+        assert!(validate_alias("@sam").is_err());
+        assert!(validate_alias("my sam").is_err());
+        assert_eq!(ExitStatus::of(&validate_alias("0").unwrap_err()), ExitStatus::InvalidInput);
+    }
+
+    #[test]
+    fn test_sessions_by_alias() {
+        let mut manager = manager();
+        let reader_name = manager.readers().list_readers().unwrap()[0].name.clone();
+
+        manager.connect("host", &reader_name, ShareMode::Shared, ProtocolSelection::T0T1).unwrap();
+        manager.connect("sam", &reader_name, ShareMode::Shared, ProtocolSelection::T0T1).unwrap();
+        assert_eq!(manager.aliases().collect::<Vec<_>>(), vec!["host", "sam"]);
+        let error = manager.connect("sam", &reader_name, ShareMode::Shared, ProtocolSelection::T0T1).err().unwrap();
+        assert_eq!(error.to_string(), "Alias already in use: sam");
+        assert_eq!(ExitStatus::of(&error), ExitStatus::InvalidInput);
+        assert!(manager.session("sam").unwrap().reader().is_connected());
+
+        manager.disconnect("host", Disposition::LeaveCard).unwrap();
+        assert!(!manager.contains("host"));
+        assert!(manager.session("host").is_err());
+        assert!(manager.disconnect("host", Disposition::LeaveCard).is_err());
+
+        manager.disconnect_all(Disposition::LeaveCard).unwrap();
+        assert!(manager.sessions().is_empty());
+    }
+}
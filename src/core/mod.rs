@@ -1,5 +1,20 @@
-/// Core PCSC functionality
+//! Core PCSC functionality
 
+pub mod apdu;
+pub mod attributes;
+pub mod backend;
+pub mod cancel;
 pub mod reader;
+pub mod manager;
+pub mod pinpad;
+pub mod selector;
 pub mod commands;
-pub mod utils;
\ No newline at end of file
+pub mod contactless;
+pub mod error;
+pub mod escape;
+pub mod events;
+pub mod features;
+pub mod recovery;
+pub mod session;
+pub mod utils;
+pub mod worker;
@@ -0,0 +1,410 @@
+//! Secure PIN entry on PIN pad readers (PC/SC Part 10)
+//!
+//! With the `VERIFY_PIN_DIRECT` and `MODIFY_PIN_DIRECT` features, the reader
+//! prompts for the PIN on its own keypad and inserts it into a VERIFY or
+//! CHANGE REFERENCE DATA APDU, so that the PIN never reaches the host. The
+//! request sent to the feature's control code is a PIN_VERIFY_STRUCTURE or
+//! PIN_MODIFY_STRUCTURE: how long to wait for the user, how to encode the
+//! PIN and where to insert it in the APDU. [`PinVerify`] and [`PinModify`]
+//! build them.
+//!
+//! The reader answers with the card's response, or with a status word of its
+//! own when PIN entry itself failed (timeout, Cancel key...).
+
+use anyhow::{Result, bail};
+
+use crate::core::commands::ControlResult;
+use crate::core::error::Error;
+use crate::core::utils::describe_status_word;
+
+/// PIN entry ends when the maximum PIN length is reached
+pub const VALIDATE_MAX_LENGTH: u8 = 0x01;
+/// PIN entry ends when the validation (OK) key is pressed
+pub const VALIDATE_KEY: u8 = 0x02;
+/// PIN entry ends when the timeout elapses
+pub const VALIDATE_TIMEOUT: u8 = 0x04;
+
+/// Default language of the reader's prompts, English (US)
+pub const DEFAULT_LANGUAGE: u16 = 0x0409;
+
+/// How each PIN digit is encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PinEncoding {
+    /// Each digit as a byte value, e.g. `1234` as `01 02 03 04`
+    Binary,
+    /// Two digits per byte, e.g. `1234` as `12 34`
+    Bcd,
+    /// ASCII digits, e.g. `1234` as `31 32 33 34`
+    #[default]
+    Ascii,
+}
+
+impl std::str::FromStr for PinEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "binary" => Ok(PinEncoding::Binary),
+            "bcd" => Ok(PinEncoding::Bcd),
+            "ascii" => Ok(PinEncoding::Ascii),
+            _ => Err(format!("Invalid PIN encoding: {} (expected binary, bcd or ascii)", s)),
+        }
+    }
+}
+
+/// Where and how the PIN is written into the APDU data
+///
+/// Positions are counted from the start of the APDU data field, in bytes or
+/// in bits depending on `byte_units`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PinFormat {
+    pub encoding: PinEncoding,
+    /// Pad the PIN on the left instead of the right
+    pub right_justify: bool,
+    /// Positions are in bytes rather than bits
+    pub byte_units: bool,
+    /// Position of the PIN, 0 to 15
+    pub pin_position: u8,
+    /// Size in bytes of the PIN block the PIN is written to, 0 to 15
+    pub block_size: u8,
+    /// Size in bits of the PIN length field, 0 for none
+    pub length_size: u8,
+    /// Position of the PIN length field, 0 to 15
+    pub length_position: u8,
+}
+
+impl Default for PinFormat {
+    fn default() -> Self {
+        Self::ascii(8)
+    }
+}
+
+impl PinFormat {
+    /// ASCII PIN at the start of a `block_size`-byte block, e.g. padded with
+    /// FF by the APDU template
+    pub fn ascii(block_size: u8) -> Self {
+        Self {
+            encoding: PinEncoding::Ascii,
+            right_justify: false,
+            byte_units: true,
+            pin_position: 0,
+            block_size,
+            length_size: 0,
+            length_position: 0,
+        }
+    }
+
+    /// BCD PIN at the start of a `block_size`-byte block
+    pub fn bcd(block_size: u8) -> Self {
+        Self { encoding: PinEncoding::Bcd, ..Self::ascii(block_size) }
+    }
+
+    /// ISO 9564 format 2 PIN block: `2L` followed by the BCD PIN padded with
+    /// F, in 8 bytes; the APDU template starts the block with `2F FF...`
+    pub fn iso9564_format2() -> Self {
+        Self {
+            encoding: PinEncoding::Bcd,
+            right_justify: false,
+            byte_units: false,
+            pin_position: 8,
+            block_size: 8,
+            length_size: 4,
+            length_position: 4,
+        }
+    }
+
+    /// Encode as the bmFormatString, bmPINBlockString and bmPINLengthFormat
+    /// fields
+    fn encode(&self) -> Result<[u8; 3]> {
+        for (name, value) in [
+            ("PIN position", self.pin_position),
+            ("PIN block size", self.block_size),
+            ("PIN length size", self.length_size),
+            ("PIN length position", self.length_position),
+        ] {
+            if value > 15 {
+                bail!(Error::Parse(format!("{} out of range: {}, at most 15", name, value)));
+            }
+        }
+        let encoding = match self.encoding {
+            PinEncoding::Binary => 0x00,
+            PinEncoding::Bcd => 0x01,
+            PinEncoding::Ascii => 0x02,
+        };
+        // The unit of the length position only matters with a length field
+        let length_units = (self.byte_units && self.length_size > 0) as u8;
+        Ok([
+            (self.byte_units as u8) << 7 | self.pin_position << 3 | (self.right_justify as u8) << 2 | encoding,
+            self.length_size << 4 | self.block_size,
+            length_units << 4 | self.length_position,
+        ])
+    }
+
+    /// Check that the PIN block fits in the APDU data when it starts at
+    /// `offset` bytes
+    fn check_fits(&self, apdu: &[u8], offset: u8) -> Result<()> {
+        if apdu.len() < 4 {
+            bail!(Error::Parse(format!("APDU too short: {} bytes, the header takes 4", apdu.len())));
+        }
+        let data_length = apdu.len().saturating_sub(5);
+        let end = offset as usize + self.block_size as usize;
+        if end > data_length {
+            bail!(Error::Parse(format!(
+                "The PIN block ends at byte {} but the APDU carries {} data bytes", end, data_length)));
+        }
+        Ok(())
+    }
+}
+
+/// PIN entry settings shared by verification and modification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinEntry {
+    /// Seconds to wait for PIN entry, 0 for the reader's default
+    pub timeout: u8,
+    /// Seconds to wait after the first key stroke, 0 for the reader's default
+    pub first_key_timeout: u8,
+    pub format: PinFormat,
+    /// Minimum PIN length in digits
+    pub min_length: u8,
+    /// Maximum PIN length in digits
+    pub max_length: u8,
+    /// What ends PIN entry: `VALIDATE_*` flags
+    pub validation: u8,
+    /// Language of the reader's prompts
+    pub language: u16,
+}
+
+impl Default for PinEntry {
+    fn default() -> Self {
+        Self {
+            timeout: 30,
+            first_key_timeout: 30,
+            format: PinFormat::default(),
+            min_length: 4,
+            max_length: 8,
+            validation: VALIDATE_KEY,
+            language: DEFAULT_LANGUAGE,
+        }
+    }
+}
+
+impl PinEntry {
+    /// wPINMaxExtraDigit: minimum length in the high byte, maximum in the low
+    /// one
+    fn length_limits(&self) -> Result<[u8; 2]> {
+        if self.min_length > self.max_length {
+            bail!(Error::Parse(format!("Minimum PIN length {} exceeds the maximum {}",
+                                       self.min_length, self.max_length)));
+        }
+        Ok(u16::from_be_bytes([self.min_length, self.max_length]).to_le_bytes())
+    }
+}
+
+/// PIN verification on the PIN pad (PIN_VERIFY_STRUCTURE)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinVerify {
+    pub entry: PinEntry,
+    /// VERIFY APDU the PIN is inserted into, e.g.
+    /// `00 20 00 81 08 FF FF FF FF FF FF FF FF`
+    pub apdu: Vec<u8>,
+}
+
+impl PinVerify {
+    pub fn new(apdu: Vec<u8>) -> Self {
+        Self { entry: PinEntry::default(), apdu }
+    }
+
+    /// Encode the structure sent to the `VERIFY_PIN_DIRECT` control code
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let entry = &self.entry;
+        let format = entry.format.encode()?;
+        entry.format.check_fits(&self.apdu, 0)?;
+        let mut data = vec![entry.timeout, entry.first_key_timeout];
+        data.extend(format);
+        data.extend(entry.length_limits()?);
+        data.push(entry.validation);
+        // One prompt, the reader's first message
+        data.push(0x01);
+        data.extend(entry.language.to_le_bytes());
+        data.push(0x00);
+        // T=1 block prologue, filled in by the reader
+        data.extend([0x00; 3]);
+        data.extend((self.apdu.len() as u32).to_le_bytes());
+        data.extend(&self.apdu);
+        Ok(data)
+    }
+}
+
+/// PIN modification on the PIN pad (PIN_MODIFY_STRUCTURE)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinModify {
+    pub entry: PinEntry,
+    /// Offset in bytes of the current PIN block in the APDU data
+    pub old_pin_offset: u8,
+    /// Offset in bytes of the new PIN block in the APDU data
+    pub new_pin_offset: u8,
+    /// Ask for the new PIN twice
+    pub confirm: bool,
+    /// Ask for the current PIN; off to set a PIN without knowing it, e.g.
+    /// after RESET RETRY COUNTER
+    pub enter_old: bool,
+    /// CHANGE REFERENCE DATA APDU both PINs are inserted into, e.g.
+    /// `00 24 00 81 10` followed by two blocks of `FF`
+    pub apdu: Vec<u8>,
+}
+
+impl PinModify {
+    /// Modification with both PINs in blocks of the default format, the
+    /// current one first
+    pub fn new(apdu: Vec<u8>) -> Self {
+        let entry = PinEntry::default();
+        let new_pin_offset = entry.format.block_size;
+        Self { entry, old_pin_offset: 0, new_pin_offset, confirm: true, enter_old: true, apdu }
+    }
+
+    /// Encode the structure sent to the `MODIFY_PIN_DIRECT` control code
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let entry = &self.entry;
+        let format = entry.format.encode()?;
+        if self.enter_old {
+            entry.format.check_fits(&self.apdu, self.old_pin_offset)?;
+        }
+        entry.format.check_fits(&self.apdu, self.new_pin_offset)?;
+        let mut data = vec![entry.timeout, entry.first_key_timeout];
+        data.extend(format);
+        data.extend([self.old_pin_offset, self.new_pin_offset]);
+        data.extend(entry.length_limits()?);
+        data.push((self.enter_old as u8) << 1 | self.confirm as u8);
+        data.push(entry.validation);
+        // One prompt per PIN entered, the reader's messages 0 to 2
+        data.push(1 + self.enter_old as u8 + self.confirm as u8);
+        data.extend(entry.language.to_le_bytes());
+        data.extend([0x00, 0x01, 0x02]);
+        // T=1 block prologue, filled in by the reader
+        data.extend([0x00; 3]);
+        data.extend((self.apdu.len() as u32).to_le_bytes());
+        data.extend(&self.apdu);
+        Ok(data)
+    }
+}
+
+/// Answer to a PIN verification or modification
+#[derive(Debug, Clone)]
+pub struct PinResult {
+    /// The control command as sent and answered
+    pub control: ControlResult,
+    pub sw1: u8,
+    pub sw2: u8,
+}
+
+impl PinResult {
+    /// Split the status word off the reader's answer
+    pub fn new(control: ControlResult) -> Result<Self> {
+        let [.., sw1, sw2] = control.output[..] else {
+            bail!(Error::Parse(format!("PIN pad answered {} bytes, expected a status word", control.output.len())));
+        };
+        Ok(Self { control, sw1, sw2 })
+    }
+
+    /// Whether the card accepted the PIN
+    pub fn is_success(&self) -> bool {
+        (self.sw1, self.sw2) == (0x90, 0x00)
+    }
+
+    /// Describe the outcome of PIN entry
+    pub fn description(&self) -> String {
+        describe_pin_status(self.sw1, self.sw2)
+    }
+
+    /// Fail with a status word error unless the card accepted the PIN
+    pub fn check(&self) -> Result<()> {
+        if !self.is_success() {
+            bail!(Error::StatusWord {
+                expected: "9000".to_string(),
+                sw1: self.sw1,
+                sw2: self.sw2,
+                description: self.description(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Describe a status word answered to PIN entry, including those the reader
+/// returns when PIN entry itself failed
+pub fn describe_pin_status(sw1: u8, sw2: u8) -> String {
+    match (sw1, sw2) {
+        (0x64, 0x00) => "PIN entry timed out".to_string(),
+        (0x64, 0x01) => "PIN entry cancelled on the reader".to_string(),
+        (0x64, 0x02) => "New PIN entries do not match".to_string(),
+        (0x64, 0x03) => "PIN too short or too long".to_string(),
+        (0x6B, 0x80) => "Reader rejected the PIN entry parameters".to_string(),
+        (0x63, n) if n & 0xF0 == 0xC0 => format!("Wrong PIN, {} tries left", n & 0x0F),
+        (0x69, 0x83) => "PIN blocked".to_string(),
+        _ => describe_status_word(sw1, sw2),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::utils::parse_hex;
+
+    #[test]
+    fn test_encode_verify() {
+        let verify = PinVerify::new(parse_hex("00 20 00 81 08 FFFFFFFFFFFFFFFF").unwrap());
+        assert_eq!(verify.encode().unwrap(), parse_hex(
+            "1E 1E 82 08 00 0804 02 01 0904 00 000000 0D000000 00200081 08 FFFFFFFFFFFFFFFF").unwrap());
+
+        let mut verify = PinVerify::new(parse_hex("00 20 00 80 08 2FFFFFFFFFFFFFFF").unwrap());
+        verify.entry.format = PinFormat::iso9564_format2();
+        verify.entry.validation = VALIDATE_KEY | VALIDATE_MAX_LENGTH;
+        verify.entry.max_length = 12;
+        assert_eq!(&verify.encode().unwrap()[..8], &[0x1E, 0x1E, 0x41, 0x48, 0x04, 0x0C, 0x04, 0x03]);
+    }
+
+    #[test]
+    fn test_encode_modify() {
+        let modify = PinModify::new(parse_hex("00 24 00 81 10 FFFFFFFFFFFFFFFF FFFFFFFFFFFFFFFF").unwrap());
+        assert_eq!(modify.encode().unwrap(), parse_hex(
+            "1E 1E 82 08 00 00 08 0804 03 02 03 0904 000102 000000 15000000 \
+             00240081 10 FFFFFFFFFFFFFFFF FFFFFFFFFFFFFFFF").unwrap());
+
+        // Setting a PIN without entering the current one
+        let mut modify = PinModify::new(parse_hex("00 24 01 81 08 FFFFFFFFFFFFFFFF").unwrap());
+        modify.enter_old = false;
+        modify.new_pin_offset = 0;
+        let data = modify.encode().unwrap();
+        assert_eq!((data[9], data[11]), (0x01, 0x02));
+    }
+
+    #[test]
+    fn test_encode_errors() {
+        // The PIN block does not fit in the APDU data
+        let error = PinVerify::new(parse_hex("00 20 00 81 04 FFFFFFFF").unwrap()).encode().unwrap_err();
+        assert!(error.to_string().contains("PIN block ends at byte 8 but the APDU carries 4"), "{}", error);
+        let modify = PinModify::new(parse_hex("00 24 00 81 08 FFFFFFFFFFFFFFFF").unwrap());
+        assert!(modify.encode().is_err());
+
+        let mut verify = PinVerify::new(parse_hex("00 20 00 81 08 FFFFFFFFFFFFFFFF").unwrap());
+        verify.entry.min_length = 9;
+        assert!(verify.encode().unwrap_err().to_string().contains("Minimum PIN length 9 exceeds"));
+        verify.entry.min_length = 4;
+        verify.entry.format.block_size = 16;
+        assert!(verify.encode().unwrap_err().to_string().contains("PIN block size out of range"));
+    }
+
+    #[test]
+    fn test_pin_result() {
+        let control = |output: &[u8]| ControlResult { code: 0x42330006, input: Vec::new(), output: output.to_vec(), duration_ms: 0 };
+        assert!(PinResult::new(control(&[0x90, 0x00])).unwrap().check().is_ok());
+
+        let result = PinResult::new(control(&[0x63, 0xC2])).unwrap();
+        assert_eq!(result.description(), "Wrong PIN, 2 tries left");
+        let error = result.check().unwrap_err();
+        assert!(error.to_string().contains("card returned 63C2 (Wrong PIN, 2 tries left)"), "{}", error);
+
+        assert_eq!(describe_pin_status(0x64, 0x01), "PIN entry cancelled on the reader");
+        assert!(PinResult::new(control(&[0x90])).is_err());
+    }
+}
@@ -1,103 +1,613 @@
-use pcsc::{Context, Scope, ShareMode, Protocols, Card};
-use anyhow::{Result, Context as AnyhowContext};
+use pcsc::{Attribute, Scope, ShareMode, Disposition};
+use anyhow::{Result, Context as AnyhowContext, bail};
 use serde::{Deserialize, Serialize};
-use std::ffi::CString;
+use std::time::{Duration, Instant};
+
+use crate::core::backend::{PcscBackend, CardBackend, CardProtocol, SystemBackend, DynBackend, BackendSpec, ProtocolSelection, ReaderState, PNP_NOTIFICATION};
+use crate::core::attributes::{AttributeValue, KNOWN_ATTRIBUTES};
+use crate::core::cancel::CancelToken;
+use crate::core::error::Error;
+use crate::core::events::{diff_reader_state, ReaderEvent, ReaderEventKind};
+use crate::core::features::{ReaderFeatures, ReaderProperties, FEATURE_GET_TLV_PROPERTIES, FEATURE_IFD_PIN_PROPERTIES, GET_FEATURE_REQUEST};
+
+/// Longest single status change call made by `watch` and the wait functions
+const STATUS_POLL_INTERVAL: Duration = Duration::from_millis(500);
 
 /// Information about a PCSC reader
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ReaderInfo {
     pub name: String,
+    /// Whether a card is present in the reader
     pub is_connected: bool,
     pub atr: Option<Vec<u8>>,
+    #[serde(default)]
+    pub status: ReaderStatus,
+}
+
+/// Reader state as reported by a status query
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReaderStatus {
+    pub present: bool,
+    pub empty: bool,
+    pub exclusive: bool,
+    pub in_use: bool,
+    pub mute: bool,
+    pub unpowered: bool,
+    pub unavailable: bool,
+    /// Number of card insertions/removals seen by the reader
+    pub event_count: u32,
+}
+
+impl ReaderStatus {
+    /// Labels of the state flags that are set, e.g. `["present", "in use"]`
+    pub fn flags(&self) -> Vec<&'static str> {
+        [
+            (self.present, "present"),
+            (self.empty, "empty"),
+            (self.exclusive, "exclusive"),
+            (self.in_use, "in use"),
+            (self.mute, "mute"),
+            (self.unpowered, "unpowered"),
+            (self.unavailable, "unavailable"),
+        ]
+        .into_iter()
+        .filter_map(|(set, label)| set.then_some(label))
+        .collect()
+    }
+}
+
+impl From<&ReaderState> for ReaderStatus {
+    fn from(state: &ReaderState) -> Self {
+        let flags = state.event_state;
+        Self {
+            present: flags.contains(pcsc::State::PRESENT),
+            empty: flags.contains(pcsc::State::EMPTY),
+            exclusive: flags.contains(pcsc::State::EXCLUSIVE),
+            in_use: flags.contains(pcsc::State::INUSE),
+            mute: flags.contains(pcsc::State::MUTE),
+            unpowered: flags.contains(pcsc::State::UNPOWERED),
+            unavailable: flags.contains(pcsc::State::UNAVAILABLE),
+            event_count: state.event_count,
+        }
+    }
+}
+
+impl From<&ReaderState> for ReaderInfo {
+    fn from(state: &ReaderState) -> Self {
+        let status = ReaderStatus::from(state);
+        Self {
+            name: state.name.clone(),
+            is_connected: status.present,
+            atr: (status.present && !state.atr.is_empty()).then(|| state.atr.clone()),
+            status,
+        }
+    }
 }
 
 /// PCSC reader manager
-pub struct PcscReader {
-    context: Context,
+///
+/// Generic over the [`PcscBackend`] used to reach readers and cards; the
+/// default is the platform PC/SC service.
+pub struct PcscReader<B: PcscBackend = SystemBackend> {
+    backend: B,
     current_reader: Option<String>,
-    current_card: Option<Card>,
+    current_card: Option<B::Card>,
+    current_protocol: Option<CardProtocol>,
+    /// Share mode and protocols the current connection was made with
+    current_mode: Option<(ShareMode, ProtocolSelection)>,
+    in_transaction: bool,
+    /// Reader group that listing is restricted to
+    group: Option<String>,
+    /// Interrupts waits for cards and reader events
+    cancel: CancelToken,
 }
 
 impl PcscReader {
     /// Create a new PCSC reader manager
     pub fn new() -> Result<Self> {
-        let context = Context::establish(Scope::User)
+        Self::with_scope(Scope::User)
+    }
+    
+    /// Create a new PCSC reader manager with a context of the given scope
+    pub fn with_scope(scope: Scope) -> Result<Self> {
+        let backend = SystemBackend::establish(scope)
             .context("Failed to establish PCSC context")?;
         
-        Ok(Self {
-            context,
+        Ok(Self::with_backend(backend))
+    }
+}
+
+impl PcscReader<DynBackend> {
+    /// Create a reader manager on the backend selected at runtime
+    pub fn open(spec: &BackendSpec) -> Result<Self> {
+        Ok(Self::with_backend(spec.open()?))
+    }
+    
+    /// Create a reader manager on the backend selected at runtime, with a
+    /// context of the given scope
+    pub fn open_with_scope(spec: &BackendSpec, scope: Scope) -> Result<Self> {
+        Ok(Self::with_backend(spec.open_with_scope(scope)?))
+    }
+    
+    /// Watch readers on a background thread, e.g. to follow hot-plug while
+    /// the calling thread is busy
+    ///
+    /// The thread opens a context of its own on the backend and calls
+    /// `on_event` for every event until it returns `false` or the backend
+    /// fails.
+    pub fn spawn_watch<F>(spec: &BackendSpec, scope: Scope, group: Option<String>, on_event: F) -> std::thread::JoinHandle<Result<()>>
+    where
+        F: FnMut(&ReaderEvent) -> bool + Send + 'static,
+    {
+        let spec = spec.clone();
+        std::thread::spawn(move || {
+            let mut reader = Self::open_with_scope(&spec, scope)?;
+            reader.set_group(group);
+            reader.watch(None, on_event)
+        })
+    }
+}
+
+impl<B: PcscBackend> PcscReader<B> {
+    /// Create a reader manager on top of an arbitrary backend
+    pub fn with_backend(backend: B) -> Self {
+        Self {
+            backend,
             current_reader: None,
             current_card: None,
-        })
+            current_protocol: None,
+            current_mode: None,
+            in_transaction: false,
+            group: None,
+            cancel: CancelToken::new(),
+        }
+    }
+
+    /// Get the backend used by this reader manager
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Restrict reader listing (and therefore index resolution and
+    /// watching) to a reader group, or lift the restriction with `None`
+    pub fn set_group(&mut self, group: Option<String>) {
+        self.group = group;
+    }
+
+    /// Get the reader group listing is restricted to
+    pub fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
+    /// Share a cancellation token with other readers or a Ctrl-C handler;
+    /// cancelling it interrupts `watch` and the waits for cards
+    pub fn set_cancel_token(&mut self, token: CancelToken) {
+        self.cancel = token;
     }
 
-    /// List all available readers
+    /// Get the token interrupting this reader's blocking operations
+    pub fn cancel_token(&self) -> &CancelToken {
+        &self.cancel
+    }
+
+    /// List the reader groups known to the backend
+    pub fn list_reader_groups(&self) -> Result<Vec<String>> {
+        self.backend.list_reader_groups()
+            .context("Failed to list reader groups")
+    }
+
+    /// List all available readers, or those of the selected group
+    ///
+    /// Card presence, ATR and reader state come from a status query, so
+    /// listing neither connects to nor powers up the cards.
     pub fn list_readers(&self) -> Result<Vec<ReaderInfo>> {
-        let mut readers_buf = vec![0; 2048];
-        let readers = self.context.list_readers(&mut readers_buf)
-            .context("Failed to list readers")?;
+        let readers = match &self.group {
+            Some(group) => self.readers_in_group(group)?,
+            None => self.backend.list_readers()
+                .context("Failed to list readers")?,
+        };
 
-        let mut reader_infos = Vec::new();
-        
-        for reader_name in readers {
-            let reader_name_str = reader_name.to_string_lossy().to_string();
-            
-            // Try to get reader status to check if card is present
-            let (is_connected, atr) = self.get_reader_status(&reader_name_str)
-                .unwrap_or((false, None));
-            
-            reader_infos.push(ReaderInfo {
-                name: reader_name_str,
-                is_connected,
-                atr,
-            });
+        let mut states: Vec<ReaderState> = readers.iter()
+            .map(|name| ReaderState::new(name))
+            .collect();
+        if let Err(e) = self.query_states(&mut states) {
+            log::warn!("Failed to get reader status: {:#}", e);
+            return Ok(readers.into_iter()
+                .map(|name| ReaderInfo { name, ..Default::default() })
+                .collect());
         }
 
-        Ok(reader_infos)
+        Ok(states.iter().map(ReaderInfo::from).collect())
     }
 
-    /// Get the status of a specific reader
-    fn get_reader_status(&self, reader_name: &str) -> Result<(bool, Option<Vec<u8>>)> {
-        // Convert to CString for PCSC API
-        let reader_cstr = CString::new(reader_name).context("Invalid reader name")?;
-        
-        // Try to connect to get status
-        match self.context.connect(&reader_cstr, ShareMode::Shared, Protocols::ANY) {
-            Ok(card) => {
-                // Get ATR if available
-                match card.status2_owned() {
-                    Ok(status) => Ok((true, Some(status.atr().to_vec()))),
-                    Err(_) => Ok((true, None)),
+    /// Block on reader status changes and report them as events
+    ///
+    /// `on_event` is called for every event; returning `false` stops the
+    /// watch. Without a timeout the watch runs until stopped or until the
+    /// backend fails. Readers attached or detached are found by re-listing
+    /// the readers, when the plug-and-play pseudo-reader reports a change or,
+    /// if the backend has no such notification, between status change calls.
+    pub fn watch<F>(&self, timeout: Option<Duration>, mut on_event: F) -> Result<()>
+    where
+        F: FnMut(&ReaderEvent) -> bool,
+    {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        let _guard = self.cancel.register(self.backend.canceller());
+        let mut states: Vec<ReaderState> = self.watched_readers()?
+            .iter()
+            .map(|name| ReaderState::new(name))
+            .collect();
+
+        // Take the initial state as baseline so that only later changes are reported
+        if !states.is_empty() {
+            match self.backend.get_status_change(Some(Duration::ZERO), &mut states) {
+                Ok(()) | Err(pcsc::Error::Timeout) => {}
+                Err(e) => return Err(e).context("Failed to get reader status"),
+            }
+            for state in states.iter_mut() {
+                state.sync_current_state();
+            }
+        }
+        let hotplug = self.hotplug_state();
+        let has_hotplug = hotplug.is_some();
+        states.extend(hotplug);
+
+        loop {
+            self.cancel.check()?;
+            let wait = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        return Ok(());
+                    }
+                    remaining.min(STATUS_POLL_INTERVAL)
+                }
+                None => STATUS_POLL_INTERVAL,
+            };
+
+            let mut events = Vec::new();
+            let mut plugged = !has_hotplug;
+            if states.is_empty() {
+                self.cancel.sleep(wait)?;
+            } else {
+                match self.backend.get_status_change(Some(wait), &mut states) {
+                    Ok(()) => {
+                        for state in states.iter_mut().filter(|s| s.changed()) {
+                            if state.name == PNP_NOTIFICATION {
+                                plugged = true;
+                            } else {
+                                events.extend(diff_reader_state(state));
+                            }
+                            state.sync_current_state();
+                        }
+                    }
+                    Err(pcsc::Error::Timeout) => {}
+                    Err(e) => {
+                        self.cancel.check()?;
+                        return Err(e).context("Failed to wait for reader status change");
+                    }
+                }
+            }
+
+            // Readers reported as gone have already produced their event
+            states.retain(|s| !s.current_state.intersects(pcsc::State::UNKNOWN | pcsc::State::IGNORE));
+
+            if plugged {
+                let names = self.watched_readers()?;
+                states.retain(|state| {
+                    let present = state.name == PNP_NOTIFICATION || names.contains(&state.name);
+                    if !present {
+                        events.push(ReaderEvent::new(&state.name, ReaderEventKind::ReaderDetached));
+                    }
+                    present
+                });
+                for name in names {
+                    if !states.iter().any(|s| s.name == name) {
+                        events.push(ReaderEvent::new(&name, ReaderEventKind::ReaderAttached));
+                        // Unaware state: a card already in the reader shows up as inserted
+                        states.push(ReaderState::new(&name));
+                    }
+                }
+            }
+
+            for event in &events {
+                if !on_event(event) {
+                    return Ok(());
                 }
             }
-            Err(pcsc::Error::NoSmartcard) => Ok((false, None)),
-            Err(_) => Ok((false, None)),
         }
     }
 
-    /// Connect to a specific reader
+    /// Check whether the backend notifies reader attach/detach through the
+    /// plug-and-play pseudo-reader
+    ///
+    /// Without it, hot-plug is still detected but by listing the readers
+    /// periodically.
+    pub fn supports_hotplug(&self) -> bool {
+        self.hotplug_state().is_some()
+    }
+
+    /// Current state of the plug-and-play pseudo-reader, if the backend
+    /// supports it
+    fn hotplug_state(&self) -> Option<ReaderState> {
+        let mut states = [ReaderState::new(PNP_NOTIFICATION)];
+        match self.backend.get_status_change(Some(Duration::ZERO), &mut states) {
+            Ok(()) | Err(pcsc::Error::Timeout) => {}
+            Err(e) => {
+                log::debug!("No plug-and-play notification: {}", e);
+                return None;
+            }
+        }
+        let [mut state] = states;
+        if state.event_state.intersects(pcsc::State::UNKNOWN | pcsc::State::IGNORE) {
+            return None;
+        }
+        state.sync_current_state();
+        Some(state)
+    }
+
+    /// Wait until a card is present in the reader and return its ATR
+    ///
+    /// Returns immediately if a card is already present. Without a timeout
+    /// this waits indefinitely.
+    pub fn wait_for_card(&self, reader_name: &str, timeout: Option<Duration>) -> Result<Vec<u8>> {
+        log::info!("Waiting for card in reader: {}", reader_name);
+        let state = self.wait_for_state(reader_name, timeout, |state| state.contains(pcsc::State::PRESENT))
+            .map_err(|e| timeout_context(e, || format!("Timed out waiting for card in reader: {}", reader_name)))?;
+        Ok(state.atr)
+    }
+
+    /// Wait until the reader no longer holds a card
+    ///
+    /// Returns immediately if the reader is empty. Without a timeout this
+    /// waits indefinitely.
+    pub fn wait_for_removal(&self, reader_name: &str, timeout: Option<Duration>) -> Result<()> {
+        log::info!("Waiting for card removal from reader: {}", reader_name);
+        self.wait_for_state(reader_name, timeout, |state| !state.contains(pcsc::State::PRESENT))
+            .map_err(|e| timeout_context(e, || format!("Timed out waiting for card removal from reader: {}", reader_name)))?;
+        Ok(())
+    }
+
+    /// Block on status changes of one reader until `done` accepts its state
+    fn wait_for_state<F>(&self, reader_name: &str, timeout: Option<Duration>, done: F) -> Result<ReaderState>
+    where
+        F: Fn(pcsc::State) -> bool,
+    {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        let mut states = [ReaderState::new(reader_name)];
+        let mut wait = Duration::ZERO;
+        let _guard = self.cancel.register(self.backend.canceller());
+
+        loop {
+            self.cancel.check()?;
+            match self.backend.get_status_change(Some(wait), &mut states) {
+                Ok(()) | Err(pcsc::Error::Timeout) => {}
+                Err(e) => {
+                    self.cancel.check()?;
+                    return Err(e).context("Failed to get reader status");
+                }
+            }
+            let state = &mut states[0];
+            if state.event_state.intersects(pcsc::State::UNKNOWN | pcsc::State::IGNORE) {
+                bail!(Error::ReaderNotFound(format!("Reader not found: {}", reader_name)));
+            }
+            if done(state.event_state) {
+                return Ok(state.clone());
+            }
+            state.sync_current_state();
+
+            wait = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        return Err(pcsc::Error::Timeout.into());
+                    }
+                    remaining.min(STATUS_POLL_INTERVAL)
+                }
+                None => STATUS_POLL_INTERVAL,
+            };
+        }
+    }
+
+    /// List reader names for `watch`, treating "no readers" as an empty list
+    fn watched_readers(&self) -> Result<Vec<String>> {
+        if let Some(group) = &self.group {
+            return self.readers_in_group(group);
+        }
+        match self.backend.list_readers() {
+            Ok(names) => Ok(names),
+            Err(pcsc::Error::NoReadersAvailable) => Ok(Vec::new()),
+            Err(e) => Err(e).context("Failed to list readers"),
+        }
+    }
+
+    /// List the readers of a group; an empty group is not an error
+    fn readers_in_group(&self, group: &str) -> Result<Vec<String>> {
+        match self.backend.list_readers_in_group(group) {
+            Ok(names) => Ok(names),
+            Err(pcsc::Error::NoReadersAvailable) => Ok(Vec::new()),
+            Err(e) => Err(e).with_context(|| format!("Failed to list readers in group: {}", group)),
+        }
+    }
+
+    /// Fill in the current state of readers without waiting for changes
+    fn query_states(&self, states: &mut [ReaderState]) -> Result<()> {
+        if states.is_empty() {
+            return Ok(());
+        }
+        match self.backend.get_status_change(Some(Duration::ZERO), states) {
+            Ok(()) | Err(pcsc::Error::Timeout) => Ok(()),
+            Err(e) => Err(e).context("Failed to get reader status"),
+        }
+    }
+
+    /// Connect to a specific reader, accepting T=0 or T=1
     pub fn connect(&mut self, reader_name: &str, share_mode: ShareMode) -> Result<()> {
-        log::info!("Connecting to reader: {}", reader_name);
+        self.connect_with_protocol(reader_name, share_mode, ProtocolSelection::default())
+    }
+
+    /// Connect to a specific reader with the given acceptable protocols
+    pub fn connect_with_protocol(&mut self, reader_name: &str, share_mode: ShareMode, protocol: ProtocolSelection) -> Result<()> {
+        log::info!("Connecting to reader: {} (protocol {})", reader_name, protocol);
         
-        let reader_cstr = CString::new(reader_name).context("Invalid reader name")?;
-        let card = self.context.connect(&reader_cstr, share_mode, Protocols::ANY)
+        let card = self.backend.connect(reader_name, share_mode, protocol.protocols())
             .with_context(|| format!("Failed to connect to reader: {}", reader_name))?;
         
+        let negotiated = negotiated_protocol(&card);
+        
         self.current_reader = Some(reader_name.to_string());
         self.current_card = Some(card);
+        self.current_protocol = negotiated;
+        self.current_mode = Some((share_mode, protocol));
+        
+        match negotiated {
+            Some(p) => log::info!("Successfully connected to reader: {} using {}", reader_name, p),
+            None => log::info!("Successfully connected to reader: {}", reader_name),
+        }
+        Ok(())
+    }
+
+    /// Re-establish the current connection and return the card's ATR
+    ///
+    /// `initialization` selects what happens to the card: `LeaveCard` keeps
+    /// it as it is, `ResetCard` performs a warm reset and `UnpowerCard` a
+    /// cold reset. The protocol is negotiated again.
+    pub fn reconnect(&mut self, share_mode: ShareMode, protocol: ProtocolSelection, initialization: Disposition) -> Result<Vec<u8>> {
+        let reader_name = self.current_reader.clone()
+            .ok_or_else(Error::not_connected)?;
+        if self.in_transaction {
+            bail!(Error::Transaction("Cannot reconnect during a transaction".to_string()));
+        }
+        let card = self.current_card.as_mut()
+            .ok_or_else(Error::not_connected)?;
+        log::info!("Reconnecting to reader: {} ({:?})", reader_name, initialization);
+        
+        card.reconnect(share_mode, protocol.protocols(), initialization)
+            .with_context(|| format!("Failed to reconnect to reader: {}", reader_name))?;
+        let atr = card.status()
+            .map(|status| status.atr)
+            .unwrap_or_default();
+        
+        self.current_protocol = negotiated_protocol(card);
+        self.current_mode = Some((share_mode, protocol));
+        Ok(atr)
+    }
+    
+    /// Reset the card, keeping the current share mode and protocols, and
+    /// return its new ATR
+    ///
+    /// A warm reset is performed unless `cold` is set, in which case the
+    /// card is powered down and up again.
+    pub fn reset(&mut self, cold: bool) -> Result<Vec<u8>> {
+        let (share_mode, protocol) = self.current_mode
+            .ok_or_else(Error::not_connected)?;
+        let initialization = if cold { Disposition::UnpowerCard } else { Disposition::ResetCard };
+        self.reconnect(share_mode, protocol, initialization)
+    }
+
+    /// Re-establish the current connection after the card was reset or
+    /// removed, or the reader was unavailable, and return the card's ATR
+    ///
+    /// The card is reconnected in place when possible and connected afresh
+    /// otherwise. A transaction in progress does not survive recovery. On
+    /// failure the previous (stale) connection is kept so that recovery can
+    /// be attempted again.
+    pub fn recover(&mut self) -> Result<Vec<u8>> {
+        let (share_mode, protocol) = self.current_mode
+            .ok_or_else(Error::not_connected)?;
+        let reader_name = self.current_reader.clone()
+            .ok_or_else(Error::not_connected)?;
+        let card = self.current_card.as_mut()
+            .ok_or_else(Error::not_connected)?;
+        log::info!("Recovering connection to reader: {}", reader_name);
+
+        if self.in_transaction {
+            log::warn!("Transaction lost while recovering the connection to {}", reader_name);
+            let _ = card.end_transaction(Disposition::LeaveCard);
+            self.in_transaction = false;
+        }
+
+        if let Err(e) = card.reconnect(share_mode, protocol.protocols(), Disposition::LeaveCard) {
+            log::info!("Reconnect failed ({}), connecting again", e);
+            let card = self.backend.connect(&reader_name, share_mode, protocol.protocols())
+                .with_context(|| format!("Failed to connect to reader: {}", reader_name))?;
+            if let Some(stale) = self.current_card.replace(card) {
+                let _ = Box::new(stale).disconnect(Disposition::LeaveCard);
+            }
+        }
+
+        let card = self.current_card.as_ref()
+            .ok_or_else(Error::not_connected)?;
+        let atr = card.status()
+            .map(|status| status.atr)
+            .unwrap_or_default();
+        self.current_protocol = negotiated_protocol(card);
+        Ok(atr)
+    }
+
+    /// Begin a transaction on the current connection
+    ///
+    /// Until the transaction ends, no other application can use the card,
+    /// even on a shared connection. [`PcscReader::transaction`] returns a
+    /// guard that ends the transaction when dropped.
+    pub fn begin_transaction(&mut self) -> Result<()> {
+        if self.in_transaction {
+            bail!(Error::Transaction("Transaction already in progress".to_string()));
+        }
+        let card = self.current_card.as_mut()
+            .ok_or_else(Error::not_connected)?;
+        card.begin_transaction()
+            .context("Failed to begin transaction")?;
+        
+        self.in_transaction = true;
+        log::info!("Transaction started");
+        Ok(())
+    }
+    
+    /// End the current transaction, leaving, resetting, unpowering or
+    /// ejecting the card according to `disposition`
+    pub fn end_transaction(&mut self, disposition: Disposition) -> Result<()> {
+        if !self.in_transaction {
+            bail!(Error::Transaction("No transaction in progress".to_string()));
+        }
+        let card = self.current_card.as_mut()
+            .ok_or_else(Error::not_connected)?;
         
-        log::info!("Successfully connected to reader: {}", reader_name);
+        // Even if ending fails, the backend no longer holds the transaction
+        self.in_transaction = false;
+        card.end_transaction(disposition)
+            .context("Failed to end transaction")?;
+        log::info!("Transaction ended ({:?})", disposition);
         Ok(())
     }
+    
+    /// Begin a transaction that lasts as long as the returned guard
+    ///
+    /// Commands are run through the guard, which dereferences to the reader.
+    /// Dropping the guard ends the transaction leaving the card as is; use
+    /// [`ReaderTransaction::end`] to choose another disposition.
+    pub fn transaction(&mut self) -> Result<ReaderTransaction<'_, B>> {
+        self.begin_transaction()?;
+        Ok(ReaderTransaction { reader: self })
+    }
+    
+    /// Check if a transaction is in progress
+    pub fn in_transaction(&self) -> bool {
+        self.in_transaction
+    }
 
-    /// Disconnect from current reader
-    pub fn disconnect(&mut self) -> Result<()> {
+    /// Disconnect from current reader, leaving, resetting, unpowering or
+    /// ejecting the card according to `disposition`
+    pub fn disconnect(&mut self, disposition: Disposition) -> Result<()> {
         if let Some(card) = self.current_card.take() {
-            let _ = card.disconnect(pcsc::Disposition::LeaveCard)
+            let _ = Box::new(card).disconnect(disposition)
                 .map_err(|_e| log::warn!("Failed to disconnect cleanly from card"));
         }
         
         self.current_reader = None;
+        self.current_protocol = None;
+        self.current_mode = None;
+        self.in_transaction = false;
         log::info!("Disconnected from reader");
         Ok(())
     }
@@ -107,39 +617,153 @@ impl PcscReader {
         self.current_reader.as_deref()
     }
 
+    /// Get the protocol negotiated on the current connection
+    pub fn protocol(&self) -> Option<CardProtocol> {
+        self.current_protocol
+    }
+
     /// Check if connected to a reader
     pub fn is_connected(&self) -> bool {
         self.current_card.is_some()
     }
 
     /// Get the current card handle (for commands)
-    pub fn card(&self) -> Option<&Card> {
+    pub fn card(&self) -> Option<&B::Card> {
         self.current_card.as_ref()
     }
 
     /// Get mutable card handle (for commands)
-    pub fn card_mut(&mut self) -> Option<&mut Card> {
+    pub fn card_mut(&mut self) -> Option<&mut B::Card> {
         self.current_card.as_mut()
     }
 
+    /// Read a reader attribute through the current connection
+    pub fn get_attribute(&self, attribute: Attribute) -> Result<Vec<u8>> {
+        let card = self.card()
+            .ok_or_else(Error::not_connected)?;
+        card.get_attribute(attribute)
+            .with_context(|| format!("Failed to get attribute {:?}", attribute))
+    }
+
+    /// Write a reader attribute through the current connection
+    pub fn set_attribute(&self, attribute: Attribute, data: &[u8]) -> Result<()> {
+        let card = self.card()
+            .ok_or_else(Error::not_connected)?;
+        card.set_attribute(attribute, data)
+            .with_context(|| format!("Failed to set attribute {:?}", attribute))
+    }
+
+    /// Read every known attribute the reader supports
+    pub fn read_attributes(&self) -> Result<Vec<AttributeValue>> {
+        let card = self.card()
+            .ok_or_else(Error::not_connected)?;
+        Ok(KNOWN_ATTRIBUTES.iter()
+            .filter_map(|def| card.get_attribute(def.attribute).ok()
+                .map(|raw| AttributeValue::new(def.attribute, raw)))
+            .collect())
+    }
+
+    /// Ask the reader for its PC/SC Part 10 features and their control codes
+    ///
+    /// Readers without Part 10 support report no features.
+    pub fn features(&self) -> Result<ReaderFeatures> {
+        let card = self.card()
+            .ok_or_else(Error::not_connected)?;
+        match card.control(GET_FEATURE_REQUEST, &[]) {
+            Ok(data) => ReaderFeatures::parse(&data),
+            Err(pcsc::Error::UnsupportedFeature) => Ok(ReaderFeatures::default()),
+            Err(e) => Err(e).context("Failed to get reader features"),
+        }
+    }
+    
+    /// Ask the reader for its properties
+    ///
+    /// `GET_TLV_PROPERTIES` is preferred; readers that only have
+    /// `IFD_PIN_PROPERTIES` report the PIN pad properties, and readers with
+    /// neither report none.
+    pub fn properties(&self) -> Result<ReaderProperties> {
+        let features = self.features()?;
+        let card = self.card()
+            .ok_or_else(Error::not_connected)?;
+        if let Some(code) = features.control_code(FEATURE_GET_TLV_PROPERTIES) {
+            let data = card.control(code, &[])
+                .context("Failed to get reader properties")?;
+            ReaderProperties::parse_tlv(&data)
+        } else if let Some(code) = features.control_code(FEATURE_IFD_PIN_PROPERTIES) {
+            let data = card.control(code, &[])
+                .context("Failed to get PIN pad properties")?;
+            ReaderProperties::parse_pin_properties(&data)
+        } else {
+            Ok(ReaderProperties::default())
+        }
+    }
+
     /// Get reader information for the currently connected reader
     pub fn current_reader_info(&self) -> Result<Option<ReaderInfo>> {
         if let Some(reader_name) = &self.current_reader {
-            let (is_connected, atr) = self.get_reader_status(reader_name)?;
-            Ok(Some(ReaderInfo {
-                name: reader_name.clone(),
-                is_connected,
-                atr,
-            }))
+            let mut states = [ReaderState::new(reader_name)];
+            self.query_states(&mut states)?;
+            Ok(Some(ReaderInfo::from(&states[0])))
         } else {
             Ok(None)
         }
     }
 }
 
-impl Drop for PcscReader {
+impl<B: PcscBackend> Drop for PcscReader<B> {
     fn drop(&mut self) {
-        let _ = self.disconnect();
+        let _ = self.disconnect(Disposition::LeaveCard);
+    }
+}
+
+/// Transaction guard returned by [`PcscReader::transaction`]
+pub struct ReaderTransaction<'a, B: PcscBackend> {
+    reader: &'a mut PcscReader<B>,
+}
+
+impl<B: PcscBackend> ReaderTransaction<'_, B> {
+    /// End the transaction with the given disposition
+    pub fn end(self, disposition: Disposition) -> Result<()> {
+        self.reader.end_transaction(disposition)
+    }
+}
+
+impl<B: PcscBackend> std::ops::Deref for ReaderTransaction<'_, B> {
+    type Target = PcscReader<B>;
+
+    fn deref(&self) -> &PcscReader<B> {
+        self.reader
+    }
+}
+
+impl<B: PcscBackend> std::ops::DerefMut for ReaderTransaction<'_, B> {
+    fn deref_mut(&mut self) -> &mut PcscReader<B> {
+        self.reader
+    }
+}
+
+impl<B: PcscBackend> Drop for ReaderTransaction<'_, B> {
+    fn drop(&mut self) {
+        if self.reader.in_transaction() {
+            let _ = self.reader.end_transaction(Disposition::LeaveCard)
+                .map_err(|e| log::warn!("Failed to end transaction: {:#}", e));
+        }
+    }
+}
+
+/// Protocol in use on a connection; none is negotiated on direct connections
+fn negotiated_protocol<C: CardBackend>(card: &C) -> Option<CardProtocol> {
+    card.status().ok()
+        .and_then(|status| status.protocol)
+        .map(CardProtocol::from)
+}
+
+/// Describe a timeout of a wait; other failures, such as a cancellation,
+/// keep their own message
+fn timeout_context(error: anyhow::Error, message: impl FnOnce() -> String) -> anyhow::Error {
+    match error.downcast_ref::<pcsc::Error>() {
+        Some(pcsc::Error::Timeout) => error.context(message()),
+        _ => error,
     }
 }
 
@@ -153,6 +777,7 @@ mod tests {
             name: "Test Reader".to_string(),
             is_connected: true,
             atr: Some(vec![0x3B, 0x75, 0x13, 0x00]),
+            ..Default::default()
         };
 
         assert_eq!(reader_info.name, "Test Reader");
@@ -166,6 +791,7 @@ mod tests {
             name: "Test Reader".to_string(),
             is_connected: false,
             atr: None,
+            ..Default::default()
         };
 
         // Test JSON serialization
@@ -187,20 +813,15 @@ mod tests {
             name: "Smart Card Reader".to_string(), 
             is_connected: true,
             atr: Some(atr.clone()),
+            ..Default::default()
         };
 
         assert!(reader_info.is_connected);
         assert_eq!(reader_info.atr.unwrap(), atr);
     }
 
-    // Note: Testing PcscReader methods that interact with actual PCSC hardware
-    // would require either:
-    // 1. Real hardware (not practical for unit tests)
-    // 2. Mocking the PCSC library (would require significant refactoring)
-    // 3. A test-only PCSC implementation
-    //
-    // For now, we test the data structures and would rely on integration tests
-    // for actual PCSC functionality.
+    // Note: PcscReader methods are exercised against a mock backend in
+    // tests/mock_pcsc_tests.rs; here we only test the data structures.
 
     #[test] 
     fn test_reader_info_clone() {
@@ -208,6 +829,7 @@ mod tests {
             name: "Original Reader".to_string(),
             is_connected: true,
             atr: Some(vec![0x3B, 0x00]),
+            ..Default::default()
         };
 
         let cloned = original.clone();
@@ -222,6 +844,7 @@ mod tests {
             name: "Debug Reader".to_string(),
             is_connected: false,
             atr: None,
+            ..Default::default()
         };
 
         let debug_str = format!("{:?}", reader_info);
@@ -237,6 +860,7 @@ mod tests {
             name: "".to_string(),
             is_connected: false,
             atr: None,
+            ..Default::default()
         };
 
         assert_eq!(reader_info.name, "");
@@ -252,6 +876,7 @@ mod tests {
             name: "Long ATR Reader".to_string(),
             is_connected: true,
             atr: Some(long_atr.clone()),
+            ..Default::default()
         };
 
         assert_eq!(reader_info.atr.unwrap().len(), 33);
@@ -263,9 +888,41 @@ mod tests {
             name: "Empty ATR Reader".to_string(),
             is_connected: true,
             atr: Some(vec![]),
+            ..Default::default()
         };
 
         assert!(reader_info.is_connected);
         assert_eq!(reader_info.atr.unwrap().len(), 0);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_reader_info_from_state() {
+        let mut state = ReaderState::new("State Reader");
+        state.event_state = pcsc::State::CHANGED | pcsc::State::PRESENT | pcsc::State::INUSE | pcsc::State::EXCLUSIVE;
+        state.event_count = 4;
+        state.atr = vec![0x3B, 0x00];
+
+        let reader_info = ReaderInfo::from(&state);
+        assert!(reader_info.is_connected);
+        assert_eq!(reader_info.atr, Some(vec![0x3B, 0x00]));
+        assert!(reader_info.status.present && reader_info.status.in_use && reader_info.status.exclusive);
+        assert!(!reader_info.status.mute && !reader_info.status.empty);
+        assert_eq!(reader_info.status.event_count, 4);
+        assert_eq!(reader_info.status.flags(), vec!["present", "exclusive", "in use"]);
+
+        state.event_state = pcsc::State::EMPTY | pcsc::State::UNAVAILABLE;
+        state.atr.clear();
+        let reader_info = ReaderInfo::from(&state);
+        assert!(!reader_info.is_connected);
+        assert!(reader_info.atr.is_none());
+        assert_eq!(reader_info.status.flags(), vec!["empty", "unavailable"]);
+    }
+
+    #[test]
+    fn test_reader_info_legacy_json() {
+        // Reader info serialized before the status was added
+        let json = r#"{"name":"Old Reader","is_connected":true,"atr":[59,0]}"#;
+        let reader_info: ReaderInfo = serde_json::from_str(json).unwrap();
+        assert_eq!(reader_info.status, ReaderStatus::default());
+    }
+}
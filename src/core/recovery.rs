@@ -0,0 +1,92 @@
+//! Recovery from transient card and reader errors
+//!
+//! A card reset by another application, a card briefly losing contact or a
+//! USB reader dropping off the bus make commands fail with errors that a new
+//! connection usually fixes. With a [`RecoveryPolicy`], `CommandExecutor`
+//! reconnects, re-sends a preamble (e.g. the SELECT that put the card in the
+//! expected state) and retries the command, waiting longer after each
+//! failed attempt.
+
+use std::time::Duration;
+
+/// How `CommandExecutor` recovers from transient errors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecoveryPolicy {
+    /// Number of retries after the first failure; 0 disables recovery
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for each following one
+    pub backoff: Duration,
+    /// Upper bound of the delay between retries
+    pub max_backoff: Duration,
+    /// APDUs sent after reconnecting and before retrying the command
+    pub preamble: Vec<Vec<u8>>,
+}
+
+impl Default for RecoveryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 0,
+            backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+            preamble: Vec::new(),
+        }
+    }
+}
+
+impl RecoveryPolicy {
+    /// Policy retrying up to `max_retries` times with the default backoff
+    pub fn with_retries(max_retries: u32) -> Self {
+        Self { max_retries, ..Default::default() }
+    }
+
+    /// Check whether recovery is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.max_retries > 0
+    }
+
+    /// Delay before retry number `attempt` (starting at 1)
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1u32.checked_shl(attempt.saturating_sub(1)).unwrap_or(u32::MAX);
+        self.backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+
+    /// Check whether an error is worth reconnecting and retrying for
+    pub fn is_recoverable(error: &pcsc::Error) -> bool {
+        matches!(error,
+            pcsc::Error::ResetCard
+            | pcsc::Error::RemovedCard
+            | pcsc::Error::UnpoweredCard
+            | pcsc::Error::ReaderUnavailable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recovery_backoff() {
+        let policy = RecoveryPolicy {
+            max_retries: 5,
+            backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(500),
+            preamble: Vec::new(),
+        };
+        assert_eq!(policy.delay(1), Duration::from_millis(100));
+        assert_eq!(policy.delay(2), Duration::from_millis(200));
+        assert_eq!(policy.delay(3), Duration::from_millis(400));
+        assert_eq!(policy.delay(4), Duration::from_millis(500));
+        assert_eq!(policy.delay(40), Duration::from_millis(500));
+        assert!(policy.is_enabled());
+        assert!(!RecoveryPolicy::default().is_enabled());
+    }
+
+    #[test]
+    fn test_recoverable_errors() {
+        assert!(RecoveryPolicy::is_recoverable(&pcsc::Error::ResetCard));
+        assert!(RecoveryPolicy::is_recoverable(&pcsc::Error::RemovedCard));
+        assert!(RecoveryPolicy::is_recoverable(&pcsc::Error::ReaderUnavailable));
+        assert!(!RecoveryPolicy::is_recoverable(&pcsc::Error::InvalidValue));
+        assert!(!RecoveryPolicy::is_recoverable(&pcsc::Error::SharingViolation));
+    }
+}
@@ -0,0 +1,265 @@
+//! Reader selection by index, name pattern or card ATR
+//!
+//! Everywhere a reader is given on the command line it can be one of:
+//!
+//! - `2`: index in the reader list
+//! - `name~Omnikey`: name contains the text (case-insensitive)
+//! - `name:*CL*`: name matches a glob pattern (`*` and `?`)
+//! - `re:^HID.*\d$`: name matches a regular expression
+//! - `atr:3B8F80..`: the card's ATR matches a pattern, optionally with a
+//!   mask as in `atr:3B8F8001/FFFFFF0F`
+//! - `first-with-card`: the first reader holding a card
+//! - anything else: the exact reader name
+//!
+//! ATR patterns use the APDU pattern syntax of the simulator (`?` or `X` for
+//! any nibble, a trailing `*` for any remaining bytes); a trailing `..` is
+//! accepted as well.
+
+use anyhow::{Result, Context, bail};
+use regex::Regex;
+
+use crate::core::backend::sim::ApduPattern;
+use crate::core::error::Error;
+use crate::core::reader::ReaderInfo;
+use crate::core::utils::parse_hex;
+
+/// Selector picking one reader out of the reader list
+#[derive(Debug, Clone)]
+pub enum ReaderSelector {
+    /// Position in the reader list
+    Index(usize),
+    /// Exact reader name
+    Name(String),
+    /// Case-insensitive substring of the name
+    Contains(String),
+    /// Glob pattern over the name
+    Glob(String),
+    /// Regular expression over the name
+    Regex(Regex),
+    /// Pattern over the ATR of the card in the reader, and the pattern as
+    /// given
+    Atr { pattern: ApduPattern, text: String },
+    /// First reader with a card
+    FirstWithCard,
+}
+
+impl std::str::FromStr for ReaderSelector {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Ok(index) = s.parse::<usize>() {
+            return Ok(ReaderSelector::Index(index));
+        }
+        if s.eq_ignore_ascii_case("first-with-card") {
+            return Ok(ReaderSelector::FirstWithCard);
+        }
+        if let Some(text) = s.strip_prefix("name~") {
+            return Ok(ReaderSelector::Contains(text.to_lowercase()));
+        }
+        if let Some(pattern) = s.strip_prefix("name:") {
+            return Ok(ReaderSelector::Glob(pattern.to_string()));
+        }
+        if let Some(pattern) = s.strip_prefix("re:") {
+            let regex = Regex::new(pattern)
+                .map_err(|e| Error::Parse(format!("Invalid reader regex: {}: {}", pattern, e)))?;
+            return Ok(ReaderSelector::Regex(regex));
+        }
+        if let Some(pattern) = s.strip_prefix("atr:") {
+            return parse_atr_pattern(pattern)
+                .with_context(|| format!("Invalid ATR selector: {}", s))
+                .map(|parsed| ReaderSelector::Atr { pattern: parsed, text: pattern.to_string() });
+        }
+        Ok(ReaderSelector::Name(s.to_string()))
+    }
+}
+
+impl std::fmt::Display for ReaderSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReaderSelector::Index(index) => write!(f, "{}", index),
+            ReaderSelector::Name(name) => write!(f, "{}", name),
+            ReaderSelector::Contains(text) => write!(f, "name~{}", text),
+            ReaderSelector::Glob(pattern) => write!(f, "name:{}", pattern),
+            ReaderSelector::Regex(regex) => write!(f, "re:{}", regex),
+            ReaderSelector::Atr { text, .. } => write!(f, "atr:{}", text),
+            ReaderSelector::FirstWithCard => write!(f, "first-with-card"),
+        }
+    }
+}
+
+impl ReaderSelector {
+    /// Check whether a reader matches the selector; indexes match nothing
+    pub fn matches(&self, reader: &ReaderInfo) -> bool {
+        match self {
+            ReaderSelector::Index(_) => false,
+            ReaderSelector::Name(name) => reader.name == *name,
+            ReaderSelector::Contains(text) => reader.name.to_lowercase().contains(text.as_str()),
+            ReaderSelector::Glob(pattern) => glob_match(pattern, &reader.name),
+            ReaderSelector::Regex(regex) => regex.is_match(&reader.name),
+            ReaderSelector::Atr { pattern, .. } => reader.atr.as_deref()
+                .is_some_and(|atr| reader.is_connected && pattern.matches(atr)),
+            ReaderSelector::FirstWithCard => reader.is_connected,
+        }
+    }
+
+    /// Check whether resolving the selector needs the reader list
+    ///
+    /// Exact names are used as given, so that readers outside the listed
+    /// ones (e.g. outside the selected group) can still be reached.
+    pub fn needs_reader_list(&self) -> bool {
+        !matches!(self, ReaderSelector::Name(_))
+    }
+
+    /// Pick the reader this selector designates and return its name
+    ///
+    /// Patterns must match exactly one reader; `first-with-card` takes the
+    /// first match.
+    pub fn resolve(&self, readers: &[ReaderInfo]) -> Result<String> {
+        match self {
+            ReaderSelector::Name(name) => return Ok(name.clone()),
+            ReaderSelector::Index(index) => {
+                if readers.is_empty() {
+                    bail!(Error::NoReaders);
+                }
+                return readers.get(*index)
+                    .map(|reader| reader.name.clone())
+                    .ok_or_else(|| Error::ReaderNotFound(format!("Reader index {} out of range (0-{})", index, readers.len() - 1)).into());
+            }
+            _ => {}
+        }
+
+        let matching: Vec<&ReaderInfo> = readers.iter()
+            .filter(|reader| self.matches(reader))
+            .collect();
+        match (self, matching.as_slice()) {
+            (ReaderSelector::FirstWithCard, []) => bail!(Error::NoCard("No reader has a card".to_string())),
+            (ReaderSelector::FirstWithCard, [first, ..]) => Ok(first.name.clone()),
+            (_, []) => bail!(Error::ReaderNotFound(format!("No reader matches {}", self))),
+            (_, [reader]) => Ok(reader.name.clone()),
+            (_, several) => bail!(Error::AmbiguousReader(format!("{} matches {} readers: {}", self, several.len(),
+                                  several.iter().map(|r| r.name.as_str()).collect::<Vec<_>>().join(", ")))),
+        }
+    }
+}
+
+/// Parse an ATR pattern with an optional `/mask`
+fn parse_atr_pattern(s: &str) -> Result<ApduPattern> {
+    let (pattern, mask) = match s.split_once('/') {
+        Some((pattern, mask)) => (pattern, Some(parse_hex(mask).context("Invalid ATR mask")?)),
+        None => (s, None),
+    };
+    let pattern = match pattern.trim_end().strip_suffix("..") {
+        Some(prefix) => format!("{}*", prefix),
+        None => pattern.to_string(),
+    };
+    let pattern = ApduPattern::parse(&pattern)?;
+    Ok(match mask {
+        Some(mask) => pattern.with_mask(&mask),
+        None => pattern,
+    })
+}
+
+/// Match text against a glob pattern where `*` is any run of characters
+/// and `?` any single character
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and the text position it was tried at
+    let mut backtrack = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, start)) => {
+                    p = star + 1;
+                    t = start + 1;
+                    backtrack = Some((star, start + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::error::ExitStatus;
+
+    fn readers() -> Vec<ReaderInfo> {
+        vec![
+            ReaderInfo {
+                name: "HID Global OMNIKEY 5422 Smartcard Reader 00 00".to_string(),
+                is_connected: true,
+                atr: Some(vec![0x3B, 0x8F, 0x80, 0x01, 0x80, 0x4F]),
+                ..Default::default()
+            },
+            ReaderInfo {
+                name: "HID Global OMNIKEY 5422CL Smartcard Reader 01 00".to_string(),
+                is_connected: false,
+                atr: None,
+                ..Default::default()
+            },
+            ReaderInfo {
+                name: "Identiv uTrust 4701 F Dual Interface Reader(2)".to_string(),
+                is_connected: true,
+                atr: Some(vec![0x3B, 0x02, 0x14, 0x50]),
+                ..Default::default()
+            },
+        ]
+    }
+
+    fn resolve(selector: &str) -> Result<String> {
+        selector.parse::<ReaderSelector>()?.resolve(&readers())
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*CL*", "OMNIKEY 5422CL Reader"));
+        assert!(glob_match("HID*00 00", "HID Global Reader 00 00"));
+        assert!(glob_match("Reader(?)", "Reader(2)"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("*CL", "OMNIKEY 5422CL Reader"));
+        assert!(!glob_match("Reader", "Reader 2"));
+    }
+
+    #[test]
+    fn test_selector_by_name() {
+        assert_eq!(resolve("1").unwrap(), "HID Global OMNIKEY 5422CL Smartcard Reader 01 00");
+        assert!(resolve("3").unwrap_err().to_string().contains("out of range (0-2)"));
+        assert_eq!(resolve("name~utrust").unwrap(), "Identiv uTrust 4701 F Dual Interface Reader(2)");
+        assert_eq!(resolve("name:*CL*").unwrap(), "HID Global OMNIKEY 5422CL Smartcard Reader 01 00");
+        assert_eq!(resolve(r"re:Reader 00 \d+$").unwrap(), "HID Global OMNIKEY 5422 Smartcard Reader 00 00");
+        assert_eq!(resolve("Some Reader").unwrap(), "Some Reader");
+
+        let error = resolve("name~omnikey").unwrap_err();
+        assert!(error.to_string().contains("matches 2 readers"), "{}", error);
+        assert_eq!(ExitStatus::of(&error), ExitStatus::NoReader);
+        assert!(resolve("name:*Contactless*").unwrap_err().to_string().contains("No reader matches"));
+        assert!(resolve("re:(").is_err());
+    }
+
+    #[test]
+    fn test_selector_by_card() {
+        assert_eq!(resolve("atr:3B8F80..").unwrap(), "HID Global OMNIKEY 5422 Smartcard Reader 00 00");
+        assert_eq!(resolve("atr:3B 02 14 50").unwrap(), "Identiv uTrust 4701 F Dual Interface Reader(2)");
+        assert_eq!(resolve("atr:3B001450/FF00FFFF").unwrap(), "Identiv uTrust 4701 F Dual Interface Reader(2)");
+        assert!(resolve("atr:3B*").unwrap_err().to_string().contains("matches 2 readers"));
+        assert!(resolve("atr:3BZZ").is_err());
+
+        assert_eq!(resolve("first-with-card").unwrap(), "HID Global OMNIKEY 5422 Smartcard Reader 00 00");
+        let empty = ReaderSelector::FirstWithCard.resolve(&readers()[1..2]);
+        assert_eq!(empty.unwrap_err().to_string(), "No reader has a card");
+        assert!(ReaderSelector::Index(0).resolve(&[]).is_err());
+    }
+}
@@ -0,0 +1,113 @@
+//! Recorded card sessions
+//!
+//! A session captures the exchanges made through `CommandExecutor` together
+//! with the reader and card they were made with, so that they can later be
+//! replayed without the card (see `backend::replay`).
+
+use anyhow::{Result, Context};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::core::backend::CardProtocol;
+use crate::core::commands::CommandResult;
+
+/// A recorded session with one reader
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub recorded_at: DateTime<Utc>,
+    pub reader_name: String,
+    pub atr: Vec<u8>,
+    pub protocol: Option<CardProtocol>,
+    pub exchanges: Vec<CommandResult>,
+}
+
+impl Session {
+    /// Create an empty session
+    pub fn new() -> Self {
+        Self {
+            recorded_at: Utc::now(),
+            reader_name: String::new(),
+            atr: Vec::new(),
+            protocol: None,
+            exchanges: Vec::new(),
+        }
+    }
+
+    /// Whether the reader/card information has been captured yet
+    pub fn has_reader(&self) -> bool {
+        !self.reader_name.is_empty()
+    }
+
+    /// Serialize the session to JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self)
+            .context("Failed to serialize session")
+    }
+
+    /// Deserialize a session from JSON
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json)
+            .context("Failed to deserialize session")
+    }
+
+    /// Save the session to a file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_json()?)
+            .with_context(|| format!("Failed to write session file: {}", path.display()))
+    }
+
+    /// Load a session from a file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read session file: {}", path.display()))?;
+        Self::from_json(&json)
+            .with_context(|| format!("Invalid session file: {}", path.display()))
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::commands::CommandType;
+
+    #[test]
+    fn test_session_roundtrip() {
+        let mut session = Session::new();
+        session.reader_name = "Test Reader".to_string();
+        session.atr = vec![0x3B, 0x02, 0x14, 0x50];
+        session.protocol = Some(CardProtocol::T1);
+        session.exchanges.push(CommandResult {
+            timestamp: Utc::now(),
+            command_type: CommandType::Transmit,
+            input: vec![0x00, 0xA4, 0x04, 0x00],
+            output: vec![0x90, 0x00],
+            success: true,
+            error: None,
+            duration_ms: 12,
+            protocol: None,
+        });
+
+        let json = session.to_json().unwrap();
+        let loaded = Session::from_json(&json).unwrap();
+        assert_eq!(loaded.reader_name, "Test Reader");
+        assert_eq!(loaded.atr, session.atr);
+        assert_eq!(loaded.protocol, Some(CardProtocol::T1));
+        assert_eq!(loaded.exchanges.len(), 1);
+        assert_eq!(loaded.exchanges[0].output, vec![0x90, 0x00]);
+    }
+
+    #[test]
+    fn test_session_invalid_json() {
+        assert!(Session::from_json("[]").is_err());
+        assert!(Session::load("nonexistent-session.json").is_err());
+    }
+}
@@ -1,4 +1,6 @@
-use anyhow::{Result, Context, bail};
+use anyhow::{Result, bail};
+
+use crate::core::error::Error;
 
 /// Parse a hex string into bytes
 /// Supports various formats:
@@ -13,12 +15,12 @@ pub fn parse_hex(hex_str: &str) -> Result<Vec<u8>> {
         return Ok(Vec::new());
     }
     
-    if cleaned.len() % 2 != 0 {
-        bail!("Hex string must have even number of characters: '{}'", hex_str);
+    if !cleaned.len().is_multiple_of(2) {
+        bail!(Error::Parse(format!("Hex string must have even number of characters: '{}'", hex_str)));
     }
     
     hex::decode(&cleaned)
-        .with_context(|| format!("Invalid hex string: '{}'", hex_str))
+        .map_err(|e| Error::Parse(format!("Invalid hex string: '{}': {}", hex_str, e)).into())
 }
 
 /// Clean a hex string by removing common separators and prefixes
@@ -120,20 +122,101 @@ pub fn format_hex_dump(bytes: &[u8]) -> String {
     result.trim_end().to_string()
 }
 
+/// `SCARD_CTL_CODE` functions known by name, for `ctl:NAME` control codes
+pub const CONTROL_FUNCTIONS: &[(&str, u32)] = &[
+    // pcsc-lite's IOCTL_SMARTCARD_VENDOR_IFD_EXCHANGE
+    ("VENDOR_IFD_EXCHANGE", 1),
+    ("GET_FEATURE_REQUEST", 3400),
+    // IOCTL_CCID_ESCAPE
+    ("ESCAPE", 3500),
+];
+
+/// Largest `SCARD_CTL_CODE` function: Windows has 12 bits for it
+const MAX_CONTROL_FUNCTION: u32 = 0xFFF;
+
+/// pcsc-lite's `SCARD_CTL_CODE(function)` is this plus the function
+const PCSCLITE_CTL_BASE: u32 = 0x4200_0000;
+
+/// Windows' `CTL_CODE(FILE_DEVICE_SMARTCARD, function, METHOD_BUFFERED,
+/// FILE_ANY_ACCESS)` is this plus the function shifted left by 2
+const WINDOWS_CTL_BASE: u32 = 0x0031_0000;
+
+/// `SCARD_CTL_CODE(function)` for this platform
+pub fn ctl_code(function: u32) -> u32 {
+    pcsc::ctl_code(function as _) as u32
+}
+
+/// The `SCARD_CTL_CODE` function a control code was built from, in either
+/// the pcsc-lite or the Windows encoding
+pub fn control_function(code: u32) -> Option<u32> {
+    if let Some(function) = code.checked_sub(PCSCLITE_CTL_BASE).filter(|f| *f <= MAX_CONTROL_FUNCTION) {
+        return Some(function);
+    }
+    let function = (code.wrapping_sub(WINDOWS_CTL_BASE)) >> 2;
+    (function <= MAX_CONTROL_FUNCTION && WINDOWS_CTL_BASE + (function << 2) == code).then_some(function)
+}
+
+/// Symbolic form of a control code, e.g. `ctl:ESCAPE` or `ctl:3072`
+pub fn symbolic_control_code(code: u32) -> Option<String> {
+    let function = control_function(code)?;
+    Some(match CONTROL_FUNCTIONS.iter().find(|(_, f)| *f == function) {
+        Some((name, _)) => format!("ctl:{}", name),
+        None => format!("ctl:{}", function),
+    })
+}
+
+/// A control code in hex, with its symbolic form when it has one, e.g.
+/// `0x42000DAC (ctl:ESCAPE)`
+pub fn format_control_code(code: u32) -> String {
+    match symbolic_control_code(code) {
+        Some(symbolic) => format!("0x{:X} ({})", code, symbolic),
+        None => format!("0x{:X}", code),
+    }
+}
+
 /// Parse a control code from various formats
+///
+/// Besides raw numbers, `ctl:3500` and `ctl:ESCAPE` give
+/// `SCARD_CTL_CODE(3500)` as encoded on this platform, so that scripts work
+/// on both pcsc-lite and Windows.
 pub fn parse_control_code(code_str: &str) -> Result<u32> {
     let cleaned = code_str.trim();
     
+    if cleaned.get(..4).is_some_and(|prefix| prefix.eq_ignore_ascii_case("ctl:")) {
+        return parse_control_function(&cleaned[4..]).map(ctl_code);
+    }
+    
     if cleaned.starts_with("0x") || cleaned.starts_with("0X") {
         u32::from_str_radix(&cleaned[2..], 16)
-            .with_context(|| format!("Invalid hex control code: '{}'", code_str))
+            .map_err(|e| Error::Parse(format!("Invalid hex control code: '{}': {}", code_str, e)).into())
     } else if cleaned.chars().all(|c| c.is_ascii_hexdigit()) && cleaned.len() > 3 {
         // Assume hex if it looks like hex
         u32::from_str_radix(cleaned, 16)
-            .with_context(|| format!("Invalid hex control code: '{}'", code_str))
+            .map_err(|e| Error::Parse(format!("Invalid hex control code: '{}': {}", code_str, e)).into())
     } else {
         cleaned.parse::<u32>()
-            .with_context(|| format!("Invalid decimal control code: '{}'", code_str))
+            .map_err(|e| Error::Parse(format!("Invalid decimal control code: '{}': {}", code_str, e)).into())
+    }
+}
+
+fn parse_control_function(function: &str) -> Result<u32> {
+    let name = function.trim().to_uppercase().replace('-', "_");
+    if let Some((_, function)) = CONTROL_FUNCTIONS.iter().find(|(known, _)| *known == name) {
+        return Ok(*function);
+    }
+    let parsed = match name.strip_prefix("0X") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => name.parse::<u32>(),
+    };
+    match parsed {
+        Ok(function) if function <= MAX_CONTROL_FUNCTION => Ok(function),
+        Ok(_) => bail!(Error::Parse(format!(
+            "Control function out of range: 'ctl:{}' (at most {})", function, MAX_CONTROL_FUNCTION))),
+        Err(_) => {
+            let names: Vec<&str> = CONTROL_FUNCTIONS.iter().map(|(name, _)| *name).collect();
+            bail!(Error::Parse(format!(
+                "Invalid control function: 'ctl:{}' (expected a number or one of {})", function, names.join(", "))))
+        }
     }
 }
 
@@ -141,13 +224,13 @@ pub fn parse_control_code(code_str: &str) -> Result<u32> {
 pub fn validate_hex_string(hex_str: &str) -> Result<()> {
     let cleaned = clean_hex_string(hex_str);
     
-    if cleaned.len() % 2 != 0 {
-        bail!("Hex string must have even number of characters");
+    if !cleaned.len().is_multiple_of(2) {
+        bail!(Error::Parse("Hex string must have even number of characters".to_string()));
     }
     
     for c in cleaned.chars() {
         if !c.is_ascii_hexdigit() {
-            bail!("Invalid hex character: '{}'", c);
+            bail!(Error::Parse(format!("Invalid hex character: '{}'", c)));
         }
     }
     
@@ -157,7 +240,7 @@ pub fn validate_hex_string(hex_str: &str) -> Result<()> {
 /// Check if a string looks like a hex string
 pub fn is_hex_like(s: &str) -> bool {
     let cleaned = clean_hex_string(s);
-    !cleaned.is_empty() && cleaned.chars().all(|c| c.is_ascii_hexdigit()) && cleaned.len() % 2 == 0
+    !cleaned.is_empty() && cleaned.chars().all(|c| c.is_ascii_hexdigit()) && cleaned.len().is_multiple_of(2)
 }
 
 /// Get a human-readable description of SW1/SW2 status words
@@ -300,6 +383,37 @@ mod tests {
         assert!(parse_control_code("0xZZZZ").is_err());
     }
 
+    #[test]
+    fn test_parse_symbolic_control_code() {
+        assert_eq!(parse_control_code("ctl:3500").unwrap(), pcsc::ctl_code(3500) as u32);
+        assert_eq!(parse_control_code("ctl:ESCAPE").unwrap(), pcsc::ctl_code(3500) as u32);
+        assert_eq!(parse_control_code("CTL:get-feature-request").unwrap(), pcsc::ctl_code(3400) as u32);
+        assert_eq!(parse_control_code("Ctl:ESCAPE").unwrap(), pcsc::ctl_code(3500) as u32);
+        assert_eq!(parse_control_code(" cTL:escape").unwrap(), pcsc::ctl_code(3500) as u32);
+        assert_eq!(parse_control_code("ctl:0xC00").unwrap(), pcsc::ctl_code(3072) as u32);
+        assert!(parse_control_code("ctl:4096").unwrap_err().to_string().contains("out of range"));
+        assert!(parse_control_code("ctl:BEEP").unwrap_err().to_string().contains("one of VENDOR_IFD_EXCHANGE"));
+    }
+
+    #[test]
+    fn test_control_function() {
+        // pcsc-lite and Windows encodings both decode, whatever the platform
+        assert_eq!(control_function(0x42000DAC), Some(3500));
+        assert_eq!(control_function(0x003136B0), Some(3500));
+        assert_eq!(control_function(0x00313520), Some(3400));
+        assert_eq!(control_function(0x42000C00), Some(3072));
+        assert_eq!(control_function(ctl_code(1)), Some(1));
+        // Feature codes assigned by the driver are not SCARD_CTL_CODEs
+        assert_eq!(control_function(0x42330006), None);
+        assert_eq!(control_function(0x00313521), None);
+        assert_eq!(control_function(0x1234), None);
+
+        assert_eq!(format_control_code(0x42000DAC), "0x42000DAC (ctl:ESCAPE)");
+        assert_eq!(format_control_code(0x003136B0), "0x3136B0 (ctl:ESCAPE)");
+        assert_eq!(format_control_code(0x42000C00), "0x42000C00 (ctl:3072)");
+        assert_eq!(format_control_code(0x42330006), "0x42330006");
+    }
+
     #[test]
     fn test_validate_hex_string() {
         assert!(validate_hex_string("0102030A").is_ok());
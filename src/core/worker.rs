@@ -0,0 +1,345 @@
+//! Reader worker: card operations on a thread of their own
+//!
+//! [`PcscReader`] and [`SessionManager`] are used from one thread at a time,
+//! and their calls block for as long as the card takes to answer. A
+//! [`ReaderWorker`] moves one of them, together with a [`CommandExecutor`],
+//! onto a dedicated thread. The handle is `Send + Sync` and cheap to clone:
+//! any thread can send it requests, which run one at a time in the order
+//! received, and collect the results through the returned [`Pending`].
+//!
+//! ```no_run
+//! # use pcsc_tester::core::{backend::BackendSpec, commands::CommandExecutor, reader::PcscReader, worker::ReaderWorker};
+//! # fn main() -> anyhow::Result<()> {
+//! let worker = ReaderWorker::spawn(PcscReader::open(&BackendSpec::System)?, CommandExecutor::new());
+//! worker.connect("Reader 0", pcsc::ShareMode::Shared, Default::default()).wait()?;
+//! let pending = worker.transmit("00A4040000");
+//! // ... do something else, then collect the response
+//! let response = pending.wait()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use anyhow::{Result, anyhow};
+use pcsc::{Disposition, ShareMode};
+use std::sync::mpsc::{self, RecvTimeoutError, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::core::backend::{PcscBackend, ProtocolSelection};
+use crate::core::cancel::CancelToken;
+use crate::core::commands::{CommandExecutor, CommandResult, ControlResult, TransmitResult};
+use crate::core::manager::SessionManager;
+use crate::core::reader::{PcscReader, ReaderInfo};
+
+/// State a [`ReaderWorker`] can own: a reader manager or a session manager
+pub trait WorkerState: Send + 'static {
+    /// Token interrupting the state's blocking operations
+    fn cancel_token(&self) -> &CancelToken;
+}
+
+impl<B> WorkerState for PcscReader<B>
+where
+    B: PcscBackend + Send + 'static,
+    B::Card: Send,
+{
+    fn cancel_token(&self) -> &CancelToken {
+        PcscReader::cancel_token(self)
+    }
+}
+
+impl<B> WorkerState for SessionManager<B>
+where
+    B: PcscBackend + Send + Sync + 'static,
+    B::Card: Send,
+{
+    fn cancel_token(&self) -> &CancelToken {
+        SessionManager::cancel_token(self)
+    }
+}
+
+/// Request run by the worker thread
+type Job<T> = Box<dyn FnOnce(&mut T, &mut CommandExecutor) + Send>;
+
+/// Request waiting for the worker thread
+struct Queued<T> {
+    /// Number of cancellations made before the request was sent
+    generation: u64,
+    job: Job<T>,
+}
+
+/// Cancellation shared by the handles and the worker thread
+struct Cancel {
+    token: CancelToken,
+    /// Number of [`ReaderWorker::cancel`] calls so far
+    generation: Mutex<u64>,
+}
+
+/// Handle to a worker thread owning a reader or session manager and a
+/// command executor
+///
+/// The thread stops once every handle is dropped and the requests already
+/// sent have run; dropping the state then releases the connections.
+pub struct ReaderWorker<T> {
+    jobs: mpsc::Sender<Queued<T>>,
+    cancel: Arc<Cancel>,
+}
+
+impl<T> Clone for ReaderWorker<T> {
+    fn clone(&self) -> Self {
+        Self { jobs: self.jobs.clone(), cancel: Arc::clone(&self.cancel) }
+    }
+}
+
+impl<T: WorkerState> ReaderWorker<T> {
+    /// Move `state` and `executor` to a new worker thread
+    pub fn spawn(state: T, executor: CommandExecutor) -> Self {
+        let cancel = Arc::new(Cancel { token: state.cancel_token().clone(), generation: Mutex::new(0) });
+        let (jobs, job_rx) = mpsc::channel();
+        let shared = Arc::clone(&cancel);
+        std::thread::spawn(move || serve(state, executor, job_rx, &shared));
+        Self { jobs, cancel }
+    }
+
+    /// Run `f` on the worker thread, after the requests sent before
+    pub fn run<R, F>(&self, f: F) -> Pending<R>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut T, &mut CommandExecutor) -> Result<R> + Send + 'static,
+    {
+        let (reply_tx, reply_rx) = mpsc::channel();
+        let job: Job<T> = Box::new(move |state, executor| {
+            let _ = reply_tx.send(f(state, executor));
+        });
+        let generation = *self.cancel.generation.lock().unwrap();
+        // A stopped worker drops the reply channel, which `Pending` reports
+        let _ = self.jobs.send(Queued { generation, job });
+        Pending { result: reply_rx }
+    }
+
+    /// Cancel the request in progress and those waiting behind it
+    ///
+    /// Requests sent after this call run normally.
+    pub fn cancel(&self) {
+        let mut generation = self.cancel.generation.lock().unwrap();
+        *generation += 1;
+        self.cancel.token.cancel();
+    }
+
+    /// Get a copy of the command history
+    pub fn history(&self) -> Pending<Vec<CommandResult>> {
+        self.run(|_, executor| Ok(executor.history().to_vec()))
+    }
+}
+
+impl<B> ReaderWorker<PcscReader<B>>
+where
+    PcscReader<B>: WorkerState,
+    B: PcscBackend,
+{
+    /// List the available readers
+    pub fn list_readers(&self) -> Pending<Vec<ReaderInfo>> {
+        self.run(|reader, _| reader.list_readers())
+    }
+
+    /// Connect to a reader
+    pub fn connect(&self, reader_name: &str, share_mode: ShareMode, protocol: ProtocolSelection) -> Pending<()> {
+        let reader_name = reader_name.to_string();
+        self.run(move |reader, _| reader.connect_with_protocol(&reader_name, share_mode, protocol))
+    }
+
+    /// Disconnect from the card
+    pub fn disconnect(&self, disposition: Disposition) -> Pending<()> {
+        self.run(move |reader, _| reader.disconnect(disposition))
+    }
+
+    /// Wait until a card is present in the reader and return its ATR
+    pub fn wait_for_card(&self, reader_name: &str, timeout: Option<Duration>) -> Pending<Vec<u8>> {
+        let reader_name = reader_name.to_string();
+        self.run(move |reader, _| reader.wait_for_card(&reader_name, timeout))
+    }
+
+    /// Send an APDU to the card
+    pub fn transmit(&self, apdu_hex: &str) -> Pending<TransmitResult> {
+        let apdu_hex = apdu_hex.to_string();
+        self.run(move |reader, executor| executor.transmit(reader, &apdu_hex))
+    }
+
+    /// Send a control command to the reader
+    pub fn control(&self, code: u32, data_hex: &str) -> Pending<ControlResult> {
+        let data_hex = data_hex.to_string();
+        self.run(move |reader, executor| executor.control(reader, code, &data_hex))
+    }
+
+    /// Warm or cold reset the card and return its new ATR
+    pub fn reset(&self, cold: bool) -> Pending<Vec<u8>> {
+        self.run(move |reader, _| reader.reset(cold))
+    }
+}
+
+impl<B> ReaderWorker<SessionManager<B>>
+where
+    SessionManager<B>: WorkerState,
+    B: PcscBackend,
+{
+    /// List the available readers
+    pub fn list_readers(&self) -> Pending<Vec<ReaderInfo>> {
+        self.run(|sessions, _| sessions.readers().list_readers())
+    }
+
+    /// Connect to a reader and keep the connection under `alias`
+    pub fn connect(&self, alias: &str, reader_name: &str, share_mode: ShareMode, protocol: ProtocolSelection) -> Pending<()> {
+        let (alias, reader_name) = (alias.to_string(), reader_name.to_string());
+        self.run(move |sessions, _| sessions.connect(&alias, &reader_name, share_mode, protocol).map(|_| ()))
+    }
+
+    /// Close the session `alias`
+    pub fn disconnect(&self, alias: &str, disposition: Disposition) -> Pending<()> {
+        let alias = alias.to_string();
+        self.run(move |sessions, _| sessions.disconnect(&alias, disposition))
+    }
+
+    /// Send an APDU to the card of session `alias`
+    pub fn transmit(&self, alias: &str, apdu_hex: &str) -> Pending<TransmitResult> {
+        let (alias, apdu_hex) = (alias.to_string(), apdu_hex.to_string());
+        self.run(move |sessions, executor| sessions.transmit(executor, &alias, &apdu_hex))
+    }
+
+    /// Send a control command to the reader of session `alias`
+    pub fn control(&self, alias: &str, code: u32, data_hex: &str) -> Pending<ControlResult> {
+        let (alias, data_hex) = (alias.to_string(), data_hex.to_string());
+        self.run(move |sessions, executor| sessions.control(executor, &alias, code, &data_hex))
+    }
+
+    /// Warm or cold reset the card of session `alias` and return its new ATR
+    pub fn reset(&self, alias: &str, cold: bool) -> Pending<Vec<u8>> {
+        let alias = alias.to_string();
+        self.run(move |sessions, _| sessions.session_mut(&alias)?.reader_mut().reset(cold))
+    }
+}
+
+/// Body of the worker thread
+fn serve<T: WorkerState>(mut state: T, mut executor: CommandExecutor, jobs: mpsc::Receiver<Queued<T>>, cancel: &Cancel) {
+    for queued in jobs {
+        {
+            // Requests sent before the last cancellation run cancelled, and
+            // fail at their first blocking operation; later ones start afresh
+            let generation = cancel.generation.lock().unwrap();
+            if queued.generation == *generation {
+                cancel.token.reset();
+            } else {
+                cancel.token.cancel();
+            }
+        }
+        (queued.job)(&mut state, &mut executor);
+    }
+}
+
+/// Result of a request, available once the worker has run it
+#[must_use = "the request runs anyway, but its result is lost"]
+pub struct Pending<R> {
+    result: mpsc::Receiver<Result<R>>,
+}
+
+impl<R> Pending<R> {
+    /// Block until the result is available
+    pub fn wait(self) -> Result<R> {
+        self.result.recv().unwrap_or_else(|_| Err(worker_stopped()))
+    }
+
+    /// Wait at most `timeout` for the result; `None` if it is not available
+    /// yet
+    pub fn wait_timeout(&self, timeout: Duration) -> Option<Result<R>> {
+        match self.result.recv_timeout(timeout) {
+            Ok(result) => Some(result),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => Some(Err(worker_stopped())),
+        }
+    }
+
+    /// Take the result if it is available, without blocking, e.g. from a
+    /// GUI frame
+    pub fn try_take(&self) -> Option<Result<R>> {
+        match self.result.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(worker_stopped())),
+        }
+    }
+}
+
+fn worker_stopped() -> anyhow::Error {
+    anyhow!("Reader worker stopped")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::backend::{DynBackend, SimBackend, sim::{SimConfig, SimRule}};
+    use crate::core::error::ExitStatus;
+    use std::time::Instant;
+
+    fn worker(config: &SimConfig) -> ReaderWorker<PcscReader<SimBackend>> {
+        let reader = PcscReader::with_backend(SimBackend::new(config).unwrap());
+        let worker = ReaderWorker::spawn(reader, CommandExecutor::new());
+        let name = worker.list_readers().wait().unwrap()[0].name.clone();
+        worker.connect(&name, ShareMode::Shared, ProtocolSelection::T0T1).wait().unwrap();
+        worker
+    }
+
+    #[test]
+    fn test_worker_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        fn assert_send<T: Send>() {}
+        assert_send_sync::<ReaderWorker<PcscReader<DynBackend>>>();
+        assert_send_sync::<ReaderWorker<SessionManager<DynBackend>>>();
+        assert_send::<Pending<TransmitResult>>();
+    }
+
+    #[test]
+    fn test_worker_from_several_threads() {
+        let worker = worker(&SimConfig::default());
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let worker = worker.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..5 {
+                        worker.transmit("00A4040000").wait().unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(worker.history().wait().unwrap().len(), 20);
+    }
+
+    #[test]
+    fn test_worker_cancel() {
+        let mut config = SimConfig::default();
+        config.rules.push(SimRule {
+            apdu: "0088 *".to_string(),
+            mask: None,
+            response: String::new(),
+            sw: "9000".to_string(),
+            delay_ms: 20_000,
+        });
+        let worker = worker(&config);
+
+        let start = Instant::now();
+        let slow = worker.transmit("0088000008");
+        let queued = worker.transmit("00A4040000");
+        assert!(slow.try_take().is_none());
+        std::thread::sleep(Duration::from_millis(50));
+        worker.cancel();
+
+        for pending in [slow, queued] {
+            let error = pending.wait().unwrap_err();
+            assert_eq!(ExitStatus::of(&error), ExitStatus::Cancelled, "{:#}", error);
+        }
+        assert!(start.elapsed() < Duration::from_secs(10));
+
+        // Requests sent afterwards run normally
+        assert!(worker.transmit("00A4040000").wait().is_ok());
+    }
+}
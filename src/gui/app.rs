@@ -1,24 +1,49 @@
 use eframe::egui;
-use pcsc::ShareMode;
-use anyhow::Result;
-use std::sync::Arc;
+use pcsc::{Disposition, Scope, ShareMode};
+use anyhow::{Result, Context};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
 
 use crate::core::{
+    backend::{BackendSpec, CardProtocol, DynBackend, ProtocolSelection},
     reader::{PcscReader, ReaderInfo},
-    commands::{CommandExecutor, CommandType},
-    utils::{format_hex_spaced, format_hex_dump, format_ascii, parse_control_code, validate_hex_string},
+    events::{ReaderEvent, ReaderEventKind},
+    manager::SessionManager,
+    commands::{CommandExecutor, CommandResult, CommandStatistics, CommandType, ControlResult, TransmitResult},
+    recovery::RecoveryPolicy,
+    escape::ESCAPE_COMMANDS,
+    features::{ReaderFeatures, ReaderProperties},
+    pinpad::{PinEncoding, PinEntry, PinModify, PinResult, PinVerify, VALIDATE_MAX_LENGTH},
+    worker::{Pending, ReaderWorker},
+    utils::{format_hex_spaced, format_hex_dump, format_ascii, format_control_code, parse_control_code, parse_hex, validate_hex_string},
 };
 
 #[derive(Default)]
 pub struct PcscTesterApp {
     // PCSC components
-    pcsc_reader: Option<PcscReader>,
-    command_executor: CommandExecutor,
+    backend: BackendSpec,
+    /// Thread running the reader operations, so that slow cards do not
+    /// freeze the window
+    worker: Option<ReaderWorker<SessionManager<DynBackend>>>,
+    /// Request in progress on the worker
+    pending: Option<Pending<Reply>>,
+    /// Open sessions and history as of the last reply from the worker
+    session_list: Vec<SessionInfo>,
+    history: Vec<CommandResult>,
+    statistics: Option<CommandStatistics>,
+    recovery: RecoveryPolicy,
+    /// Command timeout, 0 for none
+    command_timeout_ms: u64,
     
     // Reader state
+    reader_groups: Vec<String>,
+    group_filter: Option<String>,
     available_readers: Vec<ReaderInfo>,
     selected_reader_idx: Option<usize>,
-    connected_reader: Option<String>,
+    /// Events from the background reader watcher
+    reader_events: Option<mpsc::Receiver<ReaderEvent>>,
+    session_alias: String,
+    active_session: Option<String>,
     connection_status: ConnectionStatus,
     
     // Command input
@@ -26,6 +51,21 @@ pub struct PcscTesterApp {
     control_code_input: String,
     control_data_input: String,
     share_mode: ShareModeGui,
+    protocol: ProtocolSelection,
+    
+    // PIN pad
+    pin_change: bool,
+    pin_verify_apdu: String,
+    pin_modify_apdu: String,
+    pin_entry: PinEntry,
+    
+    // Escape commands
+    /// Selected entry of `ESCAPE_COMMANDS`
+    escape_index: usize,
+    escape_args: String,
+    
+    // Reader info
+    reader_details: Option<ReaderDetails>,
     
     // Response display
     last_response: Vec<u8>,
@@ -34,6 +74,9 @@ pub struct PcscTesterApp {
     // UI state
     show_history: bool,
     show_settings: bool,
+    show_pin_pad: bool,
+    show_reader_info: bool,
+    show_escape: bool,
     auto_scroll_history: bool,
     max_history_items: usize,
     
@@ -42,6 +85,42 @@ pub struct PcscTesterApp {
     error_message: String,
 }
 
+/// Open session, as last reported by the worker
+struct SessionInfo {
+    alias: String,
+    reader_name: String,
+    protocol: Option<CardProtocol>,
+}
+
+/// Features and properties of a connected reader
+struct ReaderDetails {
+    reader_name: String,
+    features: ReaderFeatures,
+    properties: ReaderProperties,
+}
+
+/// What a request to the worker produced
+enum Outcome {
+    Readers { groups: Vec<String>, readers: Vec<ReaderInfo> },
+    Connected { alias: String, reader_name: String, protocol: Option<CardProtocol> },
+    Disconnected { alias: String },
+    Reset { cold: bool, atr: Vec<u8> },
+    Transmit(TransmitResult),
+    Control(ControlResult),
+    Pin(PinResult),
+    Details(ReaderDetails),
+    Exported(String),
+}
+
+/// Reply to a request: its outcome and the state it left the sessions and
+/// history in
+struct Reply {
+    outcome: Result<Outcome>,
+    sessions: Vec<SessionInfo>,
+    history: Vec<CommandResult>,
+    statistics: CommandStatistics,
+}
+
 #[derive(Default, Clone, Copy, PartialEq)]
 enum ConnectionStatus {
     #[default]
@@ -79,28 +158,41 @@ enum ResponseFormatGui {
 }
 
 impl PcscTesterApp {
-    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
+    pub fn new(cc: &eframe::CreationContext<'_>, backend: BackendSpec) -> Self {
         // Use default fonts (egui has good monospace support built-in)
-        let _ctx = &cc.egui_ctx;
+        let ctx = cc.egui_ctx.clone();
         
         let mut app = Self {
+            backend,
             max_history_items: 1000,
             auto_scroll_history: true,
+            session_alias: "default".to_string(),
+            pin_verify_apdu: "00 20 00 81 08 FF FF FF FF FF FF FF FF".to_string(),
+            pin_modify_apdu: "00 24 00 81 10 FF FF FF FF FF FF FF FF FF FF FF FF FF FF FF FF".to_string(),
             ..Default::default()
         };
         
         // Initialize PCSC
         app.initialize_pcsc();
         
+        // Follow reader hot-plug and card insertion in the background
+        let (sender, receiver) = mpsc::channel();
+        PcscReader::spawn_watch(&app.backend, Scope::User, None, move |event| {
+            let sent = sender.send(event.clone()).is_ok();
+            ctx.request_repaint();
+            sent
+        });
+        app.reader_events = Some(receiver);
+        
         app
     }
     
     fn initialize_pcsc(&mut self) {
-        match PcscReader::new() {
-            Ok(reader) => {
-                self.pcsc_reader = Some(reader);
+        match SessionManager::open(&self.backend) {
+            Ok(sessions) => {
+                self.worker = Some(ReaderWorker::spawn(sessions, CommandExecutor::new()));
                 self.refresh_readers();
-                self.status_message = "PCSC initialized".to_string();
+                self.status_message = format!("PCSC initialized ({} backend)", self.backend);
             }
             Err(e) => {
                 self.error_message = format!("Failed to initialize PCSC: {}", e);
@@ -109,122 +201,335 @@ impl PcscTesterApp {
         }
     }
     
+    /// Send a request to the worker; its reply is picked up by
+    /// `poll_pending`
+    fn request<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut SessionManager<DynBackend>, &mut CommandExecutor) -> Result<Outcome> + Send + 'static,
+    {
+        let Some(worker) = &self.worker else { return };
+        self.pending = Some(worker.run(move |sessions, executor| {
+            let outcome = f(sessions, executor);
+            Ok(Reply {
+                outcome,
+                sessions: sessions.sessions().iter()
+                    .map(|session| SessionInfo {
+                        alias: session.alias().to_string(),
+                        reader_name: session.reader_name().to_string(),
+                        protocol: session.reader().protocol(),
+                    })
+                    .collect(),
+                history: executor.history().to_vec(),
+                statistics: executor.get_statistics(),
+            })
+        }));
+    }
+    
+    /// Send a request whose reply does not matter, such as a settings change
+    fn configure<F>(&self, f: F)
+    where
+        F: FnOnce(&mut CommandExecutor) + Send + 'static,
+    {
+        if let Some(worker) = &self.worker {
+            let _ = worker.run(move |_, executor| {
+                f(executor);
+                Ok(())
+            });
+        }
+    }
+    
     fn refresh_readers(&mut self) {
-        if let Some(ref reader) = self.pcsc_reader {
-            match reader.list_readers() {
-                Ok(readers) => {
-                    self.available_readers = readers;
-                    if self.available_readers.is_empty() {
-                        self.status_message = "No readers found".to_string();
-                    } else {
-                        self.status_message = format!("Found {} reader(s)", self.available_readers.len());
-                    }
-                }
-                Err(e) => {
-                    self.error_message = format!("Failed to list readers: {}", e);
-                }
+        let group = self.group_filter.clone();
+        self.request(move |sessions, _| {
+            let reader = sessions.readers_mut();
+            // Groups are optional; a backend without them just shows no filter
+            let groups = reader.list_reader_groups()
+                .unwrap_or_else(|e| {
+                    log::debug!("Failed to list reader groups: {:#}", e);
+                    Vec::new()
+                });
+            reader.set_group(group);
+            let readers = reader.list_readers().context("Failed to list readers")?;
+            Ok(Outcome::Readers { groups, readers })
+        });
+    }
+    
+    /// Refresh the reader list when the watcher reports readers or cards
+    /// coming and going
+    fn process_reader_events(&mut self) {
+        let Some(receiver) = &self.reader_events else { return };
+        let events: Vec<ReaderEvent> = receiver.try_iter()
+            .filter(|event| matches!(event.kind,
+                ReaderEventKind::ReaderAttached | ReaderEventKind::ReaderDetached
+                | ReaderEventKind::CardInserted { .. } | ReaderEventKind::CardRemoved))
+            .collect();
+        let Some(last) = events.last() else { return };
+        
+        self.refresh_readers();
+        self.status_message = last.to_string();
+        
+        for event in events.iter().filter(|event| event.kind == ReaderEventKind::ReaderDetached) {
+            let lost: Vec<&str> = self.session_list.iter()
+                .filter(|session| session.reader_name == event.reader)
+                .map(|session| session.alias.as_str())
+                .collect();
+            if !lost.is_empty() {
+                self.error_message = format!("Reader {} disconnected (session {})", event.reader, lost.join(", "));
             }
         }
     }
     
     fn connect_to_reader(&mut self) {
-        if let (Some(ref mut reader), Some(idx)) = (&mut self.pcsc_reader, self.selected_reader_idx) {
+        if let Some(idx) = self.selected_reader_idx {
             if idx < self.available_readers.len() {
-                let reader_name = &self.available_readers[idx].name;
+                let reader_name = self.available_readers[idx].name.clone();
+                let alias = self.session_alias.trim().to_string();
+                let (share_mode, protocol) = (self.share_mode.into(), self.protocol);
                 self.connection_status = ConnectionStatus::Connecting;
                 
-                match reader.connect(reader_name, self.share_mode.into()) {
-                    Ok(()) => {
-                        self.connected_reader = Some(reader_name.clone());
-                        self.connection_status = ConnectionStatus::Connected;
-                        self.status_message = format!("Connected to {}", reader_name);
-                        self.error_message.clear();
-                    }
-                    Err(e) => {
-                        self.connection_status = ConnectionStatus::Error;
-                        self.error_message = format!("Failed to connect: {}", e);
-                    }
-                }
+                self.request(move |sessions, _| {
+                    let session = sessions.connect(&alias, &reader_name, share_mode, protocol)
+                        .context("Failed to connect")?;
+                    let protocol = session.reader().protocol();
+                    Ok(Outcome::Connected { alias, reader_name, protocol })
+                });
             }
         }
     }
     
     fn disconnect_from_reader(&mut self) {
-        if let Some(ref mut reader) = &mut self.pcsc_reader {
-            match reader.disconnect() {
-                Ok(()) => {
-                    self.connected_reader = None;
-                    self.connection_status = ConnectionStatus::Disconnected;
-                    self.status_message = "Disconnected".to_string();
-                }
-                Err(e) => {
-                    self.error_message = format!("Disconnect error: {}", e);
-                }
-            }
+        if let Some(alias) = self.active_session.clone() {
+            self.request(move |sessions, _| {
+                sessions.disconnect(&alias, Disposition::LeaveCard)
+                    .context("Disconnect error")?;
+                Ok(Outcome::Disconnected { alias })
+            });
+        }
+    }
+    
+    fn reset_card(&mut self, cold: bool) {
+        if let Some(alias) = self.active_session.clone() {
+            let kind = if cold { "Cold" } else { "Warm" };
+            self.request(move |sessions, _| {
+                let atr = sessions.session_mut(&alias)
+                    .and_then(|session| session.reader_mut().reset(cold))
+                    .with_context(|| format!("{} reset failed", kind))?;
+                Ok(Outcome::Reset { cold, atr })
+            });
         }
     }
     
     fn send_transmit_command(&mut self) {
-        if let Some(ref mut reader) = &mut self.pcsc_reader {
-            if self.connection_status == ConnectionStatus::Connected {
-                // Validate hex input
-                if let Err(e) = validate_hex_string(&self.command_input) {
-                    self.error_message = format!("Invalid hex string: {}", e);
+        if let (ConnectionStatus::Connected, Some(alias)) = (self.connection_status, self.active_session.clone()) {
+            // Validate hex input
+            if let Err(e) = validate_hex_string(&self.command_input) {
+                self.error_message = format!("Invalid hex string: {}", e);
+                return;
+            }
+            
+            let apdu = self.command_input.clone();
+            self.request(move |sessions, executor| {
+                sessions.transmit(executor, &alias, &apdu).map(Outcome::Transmit)
+            });
+        } else {
+            self.error_message = "Not connected to a reader".to_string();
+        }
+    }
+    
+    fn send_control_command(&mut self) {
+        if let (ConnectionStatus::Connected, Some(alias)) = (self.connection_status, self.active_session.clone()) {
+            // Parse control code
+            let code = match parse_control_code(&self.control_code_input) {
+                Ok(code) => code,
+                Err(e) => {
+                    self.error_message = format!("Invalid control code: {}", e);
                     return;
                 }
-                
-                match self.command_executor.transmit(reader, &self.command_input) {
-                    Ok(result) => {
-                        self.last_response = result.response.clone();
-                        self.status_message = format!("Transmit successful - SW: {:02X} {:02X} ({}ms)", 
-                                                       result.sw1, result.sw2, result.duration_ms);
-                        self.error_message.clear();
-                    }
-                    Err(e) => {
-                        self.error_message = format!("Transmit failed: {}", e);
-                    }
+            };
+            
+            // Validate data hex input if provided
+            if !self.control_data_input.trim().is_empty() {
+                if let Err(e) = validate_hex_string(&self.control_data_input) {
+                    self.error_message = format!("Invalid data hex string: {}", e);
+                    return;
                 }
-            } else {
-                self.error_message = "Not connected to a reader".to_string();
             }
+            
+            let data = self.control_data_input.clone();
+            self.request(move |sessions, executor| {
+                sessions.control(executor, &alias, code, &data).map(Outcome::Control)
+            });
+        } else {
+            self.error_message = "Not connected to a reader".to_string();
         }
     }
     
-    fn send_control_command(&mut self) {
-        if let Some(ref mut reader) = &mut self.pcsc_reader {
-            if self.connection_status == ConnectionStatus::Connected {
-                // Parse control code
-                let code = match parse_control_code(&self.control_code_input) {
-                    Ok(code) => code,
-                    Err(e) => {
-                        self.error_message = format!("Invalid control code: {}", e);
-                        return;
-                    }
-                };
-                
-                // Validate data hex input if provided
-                if !self.control_data_input.trim().is_empty() {
-                    if let Err(e) = validate_hex_string(&self.control_data_input) {
-                        self.error_message = format!("Invalid data hex string: {}", e);
-                        return;
-                    }
+    fn send_pin_command(&mut self) {
+        let (ConnectionStatus::Connected, Some(alias)) = (self.connection_status, self.active_session.clone()) else {
+            self.error_message = "Not connected to a reader".to_string();
+            return;
+        };
+        let apdu_hex = if self.pin_change { &self.pin_modify_apdu } else { &self.pin_verify_apdu };
+        let apdu = match parse_hex(apdu_hex) {
+            Ok(apdu) => apdu,
+            Err(e) => {
+                self.error_message = format!("Invalid hex string: {}", e);
+                return;
+            }
+        };
+        
+        let entry = self.pin_entry.clone();
+        if self.pin_change {
+            let mut request = PinModify::new(apdu);
+            request.new_pin_offset = entry.format.block_size;
+            request.entry = entry;
+            self.request(move |sessions, executor| {
+                let reader = sessions.session_mut(&alias)?.reader_mut();
+                executor.modify_pin(reader, &request).map(Outcome::Pin)
+            });
+        } else {
+            let mut request = PinVerify::new(apdu);
+            request.entry = entry;
+            self.request(move |sessions, executor| {
+                let reader = sessions.session_mut(&alias)?.reader_mut();
+                executor.verify_pin(reader, &request).map(Outcome::Pin)
+            });
+        }
+    }
+    
+    fn send_escape_command(&mut self) {
+        let (ConnectionStatus::Connected, Some(alias)) = (self.connection_status, self.active_session.clone()) else {
+            self.error_message = "Not connected to a reader".to_string();
+            return;
+        };
+        let command = &ESCAPE_COMMANDS[self.escape_index];
+        let args: Vec<String> = self.escape_args.split_whitespace().map(str::to_string).collect();
+        if let Err(e) = command.encode(&args) {
+            self.error_message = format!("{:#}", e);
+            return;
+        }
+        self.request(move |sessions, executor| {
+            let reader = sessions.session_mut(&alias)?.reader_mut();
+            executor.escape(reader, command, &args).map(Outcome::Control)
+        });
+    }
+    
+    fn read_reader_details(&mut self) {
+        let (ConnectionStatus::Connected, Some(alias)) = (self.connection_status, self.active_session.clone()) else {
+            self.error_message = "Not connected to a reader".to_string();
+            return;
+        };
+        self.request(move |sessions, _| {
+            let session = sessions.session(&alias)?;
+            let reader_name = session.reader_name().to_string();
+            let reader = session.reader();
+            let features = reader.features()?;
+            let properties = reader.properties()?;
+            Ok(Outcome::Details(ReaderDetails { reader_name, features, properties }))
+        });
+    }
+    
+    /// Apply the reply to the pending request, if it arrived
+    fn poll_pending(&mut self, ctx: &egui::Context) {
+        let Some(pending) = &self.pending else { return };
+        let Some(reply) = pending.try_take() else {
+            // The worker does not know about the window; check again shortly
+            ctx.request_repaint_after(Duration::from_millis(50));
+            return;
+        };
+        self.pending = None;
+        
+        let reply = match reply {
+            Ok(reply) => reply,
+            Err(e) => {
+                self.error_message = format!("{:#}", e);
+                self.connection_status = ConnectionStatus::Error;
+                self.worker = None;
+                return;
+            }
+        };
+        self.session_list = reply.sessions;
+        self.history = reply.history;
+        self.statistics = Some(reply.statistics);
+        // Fall back to another open session if the active one went away
+        if !self.session_list.iter().any(|s| Some(&s.alias) == self.active_session.as_ref()) {
+            self.active_session = self.session_list.first().map(|s| s.alias.clone());
+        }
+        self.connection_status = if self.active_session.is_some() {
+            ConnectionStatus::Connected
+        } else if self.connection_status == ConnectionStatus::Connecting {
+            ConnectionStatus::Error
+        } else {
+            ConnectionStatus::Disconnected
+        };
+        
+        let outcome = match reply.outcome {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                self.error_message = format!("{:#}", e);
+                return;
+            }
+        };
+        match outcome {
+            Outcome::Readers { groups, readers } => {
+                let selected = self.selected_reader_idx
+                    .and_then(|idx| self.available_readers.get(idx))
+                    .map(|reader| reader.name.clone());
+                self.reader_groups = groups;
+                self.available_readers = readers;
+                self.selected_reader_idx = selected
+                    .and_then(|name| self.available_readers.iter().position(|reader| reader.name == name));
+                if self.available_readers.is_empty() {
+                    self.status_message = "No readers found".to_string();
                 }
-                
-                match self.command_executor.control(reader, code, &self.control_data_input) {
-                    Ok(result) => {
-                        self.last_response = result.output.clone();
-                        self.status_message = format!("Control successful - Code: 0x{:X} ({}ms)", 
-                                                       result.code, result.duration_ms);
-                        self.error_message.clear();
-                    }
-                    Err(e) => {
-                        self.error_message = format!("Control failed: {}", e);
-                    }
+                return;
+            }
+            Outcome::Connected { alias, reader_name, protocol } => {
+                self.status_message = match protocol {
+                    Some(protocol) => format!("Connected to {} as {} ({})", reader_name, alias, protocol),
+                    None => format!("Connected to {} as {}", reader_name, alias),
+                };
+                self.active_session = Some(alias);
+                self.connection_status = ConnectionStatus::Connected;
+            }
+            Outcome::Disconnected { alias } => {
+                self.status_message = format!("Disconnected {}", alias);
+            }
+            Outcome::Reset { cold, atr } => {
+                let kind = if cold { "Cold" } else { "Warm" };
+                self.status_message = format!("{} reset done - ATR: {}", kind, format_hex_spaced(&atr));
+            }
+            Outcome::Transmit(result) => {
+                self.last_response = result.response.clone();
+                self.status_message = format!("Transmit successful - SW: {:02X} {:02X} ({}ms)", 
+                                               result.sw1, result.sw2, result.duration_ms);
+            }
+            Outcome::Control(result) => {
+                self.last_response = result.output.clone();
+                self.status_message = format!("Control successful - Code: {} ({}ms)", 
+                                               format_control_code(result.code), result.duration_ms);
+            }
+            Outcome::Pin(result) => {
+                self.last_response = result.control.output.clone();
+                let message = format!("PIN entry - SW: {:02X} {:02X} ({}) ({}ms)",
+                                      result.sw1, result.sw2, result.description(), result.control.duration_ms);
+                if !result.is_success() {
+                    self.error_message = message;
+                    return;
                 }
-            } else {
-                self.error_message = "Not connected to a reader".to_string();
+                self.status_message = message;
+            }
+            Outcome::Details(details) => {
+                self.status_message = format!("Read the properties of {}", details.reader_name);
+                self.reader_details = Some(details);
+            }
+            Outcome::Exported(json) => {
+                // In a real app, you'd use a file dialog here
+                log::info!("History exported: {} bytes", json.len());
+                self.status_message = "History exported to log".to_string();
             }
         }
+        self.error_message.clear();
     }
     
     fn format_response(&self, data: &[u8]) -> String {
@@ -243,19 +548,23 @@ impl PcscTesterApp {
 
 impl eframe::App for PcscTesterApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.poll_pending(ctx);
+        // One request at a time: reader events wait until the worker is idle
+        let idle = self.pending.is_none();
+        if idle {
+            self.process_reader_events();
+        }
+        let idle = self.pending.is_none();
+        
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             egui::menu::bar(ui, |ui| {
                 ui.menu_button("File", |ui| {
-                    if ui.button("Refresh Readers").clicked() {
+                    if ui.add_enabled(idle, egui::Button::new("Refresh Readers")).clicked() {
                         self.refresh_readers();
                         ui.close_menu();
                     }
-                    if ui.button("Export History").clicked() {
-                        if let Ok(json) = self.command_executor.export_history() {
-                            // In a real app, you'd use a file dialog here
-                            log::info!("History exported: {} bytes", json.len());
-                            self.status_message = "History exported to log".to_string();
-                        }
+                    if ui.add_enabled(idle, egui::Button::new("Export History")).clicked() {
+                        self.request(|_, executor| executor.export_history().map(Outcome::Exported));
                         ui.close_menu();
                     }
                     ui.separator();
@@ -267,6 +576,9 @@ impl eframe::App for PcscTesterApp {
                 ui.menu_button("View", |ui| {
                     ui.checkbox(&mut self.show_history, "Show History");
                     ui.checkbox(&mut self.show_settings, "Show Settings");
+                    ui.checkbox(&mut self.show_pin_pad, "Show PIN Pad");
+                    ui.checkbox(&mut self.show_reader_info, "Show Reader Info");
+                    ui.checkbox(&mut self.show_escape, "Show Escape Commands");
                 });
                 
                 ui.menu_button("Help", |ui| {
@@ -292,8 +604,10 @@ impl eframe::App for PcscTesterApp {
         
         // Settings panel
         if self.show_settings {
+            // The window needs the whole app to send settings to the worker
+            let mut show_settings = true;
             egui::Window::new("Settings")
-                .open(&mut self.show_settings)
+                .open(&mut show_settings)
                 .show(ctx, |ui| {
                     ui.horizontal(|ui| {
                         ui.label("Max history items:");
@@ -301,12 +615,201 @@ impl eframe::App for PcscTesterApp {
                     });
                     
                     ui.checkbox(&mut self.auto_scroll_history, "Auto-scroll history");
-                    
+
+                    // Reconnect and retry on card reset/removal or reader loss
+                    let mut policy = self.recovery.clone();
+                    let mut delay_ms = policy.backoff.as_millis() as u64;
+                    ui.horizontal(|ui| {
+                        ui.label("Recovery retries:");
+                        ui.add(egui::DragValue::new(&mut policy.max_retries).range(0..=20));
+                        ui.label("First delay:");
+                        ui.add(egui::DragValue::new(&mut delay_ms).range(0..=10000).suffix(" ms"));
+                    });
+                    policy.backoff = Duration::from_millis(delay_ms);
+                    if policy != self.recovery {
+                        self.recovery = policy.clone();
+                        self.configure(move |executor| executor.set_recovery_policy(policy));
+                    }
+
+                    // Give up on a mute card instead of waiting forever; 0 for no timeout
+                    let mut timeout_ms = self.command_timeout_ms;
+                    ui.horizontal(|ui| {
+                        ui.label("Command timeout:");
+                        ui.add(egui::DragValue::new(&mut timeout_ms).range(0..=600_000).suffix(" ms"));
+                    });
+                    if timeout_ms != self.command_timeout_ms {
+                        self.command_timeout_ms = timeout_ms;
+                        let timeout = (timeout_ms > 0).then(|| Duration::from_millis(timeout_ms));
+                        self.configure(move |executor| executor.set_timeout(timeout));
+                    }
+
                     if ui.button("Clear History").clicked() {
-                        self.command_executor.clear_history();
+                        self.configure(|executor| executor.clear_history());
+                        self.history.clear();
+                        self.statistics = None;
                         self.status_message = "History cleared".to_string();
                     }
                 });
+            self.show_settings = show_settings;
+        }
+        
+        // PIN pad panel
+        if self.show_pin_pad {
+            let mut show_pin_pad = true;
+            egui::Window::new("PIN Pad")
+                .open(&mut show_pin_pad)
+                .show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.radio_value(&mut self.pin_change, false, "Verify PIN");
+                        ui.radio_value(&mut self.pin_change, true, "Change PIN");
+                    });
+                    
+                    ui.horizontal(|ui| {
+                        ui.label("APDU:");
+                        let apdu = if self.pin_change { &mut self.pin_modify_apdu } else { &mut self.pin_verify_apdu };
+                        ui.add(egui::TextEdit::singleline(apdu)
+                            .desired_width(360.0)
+                            .font(egui::TextStyle::Monospace));
+                    });
+                    
+                    let entry = &mut self.pin_entry;
+                    ui.horizontal(|ui| {
+                        ui.label("Encoding:");
+                        ui.radio_value(&mut entry.format.encoding, PinEncoding::Ascii, "ASCII");
+                        ui.radio_value(&mut entry.format.encoding, PinEncoding::Bcd, "BCD");
+                        ui.radio_value(&mut entry.format.encoding, PinEncoding::Binary, "Binary");
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("PIN block:");
+                        ui.add(egui::DragValue::new(&mut entry.format.block_size).range(1..=15).suffix(" bytes"));
+                        ui.label("Length:");
+                        ui.add(egui::DragValue::new(&mut entry.min_length).range(1..=entry.max_length));
+                        ui.label("to");
+                        ui.add(egui::DragValue::new(&mut entry.max_length).range(entry.min_length..=32));
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Entry timeout:");
+                        ui.add(egui::DragValue::new(&mut entry.timeout).range(0..=255).suffix(" s"));
+                    });
+                    entry.first_key_timeout = entry.timeout;
+                    let mut auto_submit = entry.validation & VALIDATE_MAX_LENGTH != 0;
+                    ui.checkbox(&mut auto_submit, "Submit when the maximum length is reached");
+                    entry.validation = if auto_submit {
+                        entry.validation | VALIDATE_MAX_LENGTH
+                    } else {
+                        entry.validation & !VALIDATE_MAX_LENGTH
+                    };
+                    
+                    let label = if self.pin_change { "Change PIN" } else { "Verify PIN" };
+                    if ui.add_enabled(idle && self.connection_status == ConnectionStatus::Connected,
+                                      egui::Button::new(label)).clicked() {
+                        self.send_pin_command();
+                    }
+                    if !idle {
+                        ui.label("Enter the PIN on the reader's PIN pad...");
+                    }
+                });
+            self.show_pin_pad = show_pin_pad;
+        }
+        
+        // Reader info panel
+        if self.show_reader_info {
+            let mut show_reader_info = true;
+            egui::Window::new("Reader Info")
+                .open(&mut show_reader_info)
+                .show(ctx, |ui| {
+                    match &self.reader_details {
+                        Some(details) => {
+                            ui.strong(&details.reader_name);
+                            egui::Grid::new("reader_properties").striped(true).show(ui, |ui| {
+                                for (label, value) in details.properties.describe() {
+                                    ui.label(label);
+                                    ui.label(value);
+                                    ui.end_row();
+                                }
+                            });
+                            if details.properties.is_empty() {
+                                ui.label("No properties reported");
+                            }
+                            ui.separator();
+                            ui.label("Features:");
+                            for feature in details.features.iter() {
+                                ui.monospace(format!("0x{:02X} {:<26} 0x{:08X}", feature.tag, feature.name, feature.control_code));
+                            }
+                            if details.features.is_empty() {
+                                ui.label("No features reported");
+                            }
+                        }
+                        None => {
+                            ui.label("Connect to a reader, in direct mode if it has no card, and read its properties.");
+                        }
+                    }
+                    
+                    if ui.add_enabled(idle && self.connection_status == ConnectionStatus::Connected,
+                                      egui::Button::new("Read Properties")).clicked() {
+                        self.read_reader_details();
+                    }
+                });
+            self.show_reader_info = show_reader_info;
+        }
+        
+        // Escape command panel
+        if self.show_escape {
+            let mut show_escape = true;
+            egui::Window::new("Escape Commands")
+                .open(&mut show_escape)
+                .show(ctx, |ui| {
+                    let previous = self.escape_index;
+                    let selected = &ESCAPE_COMMANDS[self.escape_index];
+                    egui::ComboBox::from_id_salt("escape_command")
+                        .selected_text(format!("{} ({})", selected.name, selected.vendor))
+                        .width(300.0)
+                        .show_ui(ui, |ui| {
+                            for (i, command) in ESCAPE_COMMANDS.iter().enumerate() {
+                                ui.selectable_value(&mut self.escape_index, i, format!("{} ({})", command.name, command.vendor));
+                            }
+                        });
+                    if self.escape_index != previous {
+                        self.escape_args.clear();
+                    }
+                    
+                    let command = &ESCAPE_COMMANDS[self.escape_index];
+                    ui.label(command.description);
+                    ui.label(format!("Readers: {}", command.readers));
+                    for param in command.params {
+                        match param.default {
+                            Some(default) => ui.label(format!("{}: {} (default 0x{:02X})", param.name, param.description, default)),
+                            None => ui.label(format!("{}: {}", param.name, param.description)),
+                        };
+                    }
+                    if !command.params.is_empty() {
+                        ui.horizontal(|ui| {
+                            ui.label("Arguments:");
+                            ui.add(egui::TextEdit::singleline(&mut self.escape_args)
+                                .hint_text(command.usage())
+                                .font(egui::TextStyle::Monospace));
+                        });
+                    }
+                    
+                    let args: Vec<&str> = self.escape_args.split_whitespace().collect();
+                    match command.encode(&args) {
+                        Ok(payload) => {
+                            ui.horizontal(|ui| {
+                                ui.label("Payload:");
+                                ui.code(format_hex_spaced(&payload));
+                            });
+                        }
+                        Err(e) => {
+                            ui.colored_label(egui::Color32::from_rgb(200, 0, 0), format!("{:#}", e));
+                        }
+                    }
+                    
+                    if ui.add_enabled(idle && self.connection_status == ConnectionStatus::Connected,
+                                      egui::Button::new("Send Escape")).clicked() {
+                        self.send_escape_command();
+                    }
+                });
+            self.show_escape = show_escape;
         }
         
         // History panel
@@ -315,7 +818,10 @@ impl eframe::App for PcscTesterApp {
                 .open(&mut self.show_history)
                 .default_width(600.0)
                 .show(ctx, |ui| {
-                    let history = self.command_executor.history();
+                    if !idle {
+                        ui.label("Command in progress...");
+                    }
+                    let history = &self.history;
                     
                     egui::ScrollArea::vertical()
                         .auto_shrink([false; 2])
@@ -329,13 +835,17 @@ impl eframe::App for PcscTesterApp {
                                 
                                 let cmd_type = match &cmd.command_type {
                                     CommandType::Transmit => "TRANSMIT".to_string(),
-                                    CommandType::Control { code } => format!("CONTROL(0x{:X})", code),
+                                    CommandType::Control { code } => format!("CONTROL({})", format_control_code(*code)),
+                                    CommandType::Recovery { attempt, cause } => format!("RECOVERY #{} after {}", attempt, cause),
                                 };
                                 
                                 ui.horizontal(|ui| {
                                     ui.colored_label(status_color, format!("[{}]", i + 1));
                                     ui.label(cmd.timestamp.format("%H:%M:%S").to_string());
                                     ui.label(cmd_type);
+                                    if let Some(protocol) = cmd.protocol {
+                                        ui.label(protocol.to_string());
+                                    }
                                     ui.label(format!("{}ms", cmd.duration_ms));
                                 });
                                 
@@ -366,14 +876,15 @@ impl eframe::App for PcscTesterApp {
                             }
                         });
                     
-                    let stats = self.command_executor.get_statistics();
-                    ui.separator();
-                    ui.horizontal(|ui| {
-                        ui.label(format!("Total: {}", stats.total_commands));
-                        ui.label(format!("Success: {}", stats.successful_commands));
-                        ui.label(format!("Failed: {}", stats.failed_commands));
-                        ui.label(format!("Avg: {}ms", stats.average_duration_ms));
-                    });
+                    if let Some(stats) = &self.statistics {
+                        ui.separator();
+                        ui.horizontal(|ui| {
+                            ui.label(format!("Total: {}", stats.total_commands));
+                            ui.label(format!("Success: {}", stats.successful_commands));
+                            ui.label(format!("Failed: {}", stats.failed_commands));
+                            ui.label(format!("Avg: {}ms", stats.average_duration_ms));
+                        });
+                    }
                 });
         }
         
@@ -383,6 +894,25 @@ impl eframe::App for PcscTesterApp {
             
             // Reader selection
             ui.group(|ui| {
+                if !self.reader_groups.is_empty() {
+                    ui.horizontal(|ui| {
+                        ui.label("Group:");
+                        let previous = self.group_filter.clone();
+                        egui::ComboBox::from_id_salt("group_filter")
+                            .selected_text(self.group_filter.as_deref().unwrap_or("All readers"))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut self.group_filter, None, "All readers");
+                                for group in &self.reader_groups {
+                                    ui.selectable_value(&mut self.group_filter, Some(group.clone()), group);
+                                }
+                            });
+                        if self.group_filter != previous {
+                            self.selected_reader_idx = None;
+                            self.refresh_readers();
+                        }
+                    });
+                }
+                
                 ui.horizontal(|ui| {
                     ui.label("Reader:");
                     
@@ -390,7 +920,15 @@ impl eframe::App for PcscTesterApp {
                         .iter()
                         .enumerate()
                         .map(|(i, r)| {
-                            let status = if r.is_connected { " [CARD]" } else { "" };
+                            let mut flags: Vec<String> = r.status.flags()
+                                .into_iter()
+                                .filter(|f| *f != "present" && *f != "empty")
+                                .map(str::to_uppercase)
+                                .collect();
+                            if r.is_connected {
+                                flags.insert(0, "CARD".to_string());
+                            }
+                            let status = if flags.is_empty() { String::new() } else { format!(" [{}]", flags.join(", ")) };
                             format!("[{}] {}{}", i, r.name, status)
                         })
                         .collect();
@@ -407,7 +945,7 @@ impl eframe::App for PcscTesterApp {
                             }
                         });
                     
-                    if ui.button("Refresh").clicked() {
+                    if ui.add_enabled(idle, egui::Button::new("Refresh")).clicked() {
                         self.refresh_readers();
                     }
                 });
@@ -420,31 +958,61 @@ impl eframe::App for PcscTesterApp {
                 });
                 
                 ui.horizontal(|ui| {
-                    let connection_text = match self.connection_status {
-                        ConnectionStatus::Disconnected => "Connect",
-                        ConnectionStatus::Connecting => "Connecting...",
-                        ConnectionStatus::Connected => "Disconnect",
-                        ConnectionStatus::Error => "Connect",
-                    };
+                    ui.label("Protocol:");
+                    ui.radio_value(&mut self.protocol, ProtocolSelection::T0T1, "T=0/T=1");
+                    ui.radio_value(&mut self.protocol, ProtocolSelection::T0, "T=0");
+                    ui.radio_value(&mut self.protocol, ProtocolSelection::T1, "T=1");
+                    ui.radio_value(&mut self.protocol, ProtocolSelection::Raw, "RAW");
+                });
+                
+                ui.horizontal(|ui| {
+                    ui.label("Alias:");
+                    ui.add(egui::TextEdit::singleline(&mut self.session_alias)
+                        .desired_width(80.0)
+                        .hint_text("e.g., sam"));
                     
                     let connect_button = ui.add_enabled(
-                        self.selected_reader_idx.is_some() && 
+                        idle && self.selected_reader_idx.is_some() && 
                         self.connection_status != ConnectionStatus::Connecting,
-                        egui::Button::new(connection_text)
+                        egui::Button::new("Connect")
                     );
-                    
                     if connect_button.clicked() {
-                        match self.connection_status {
-                            ConnectionStatus::Connected => self.disconnect_from_reader(),
-                            _ => self.connect_to_reader(),
-                        }
+                        self.connect_to_reader();
                     }
                     
-                    if let Some(ref reader_name) = self.connected_reader {
-                        ui.colored_label(egui::Color32::from_rgb(0, 150, 0), 
-                                         format!("Connected to: {}", reader_name));
+                    let connected = idle && self.connection_status == ConnectionStatus::Connected;
+                    if ui.add_enabled(connected, egui::Button::new("Disconnect")).clicked() {
+                        self.disconnect_from_reader();
+                    }
+                    if ui.add_enabled(connected, egui::Button::new("Warm Reset")).clicked() {
+                        self.reset_card(false);
+                    }
+                    if ui.add_enabled(connected, egui::Button::new("Cold Reset")).clicked() {
+                        self.reset_card(true);
                     }
                 });
+                
+                if !self.session_list.is_empty() {
+                    ui.horizontal(|ui| {
+                        ui.label("Session:");
+                        let sessions = &self.session_list;
+                        let describe = |alias: &str| {
+                            sessions.iter().find(|session| session.alias == alias).map(|session| {
+                                let protocol = session.protocol
+                                    .map(|p| format!(" ({})", p))
+                                    .unwrap_or_default();
+                                format!("{}: {}{}", alias, session.reader_name, protocol)
+                            }).unwrap_or_default()
+                        };
+                        egui::ComboBox::from_id_salt("active_session")
+                            .selected_text(self.active_session.as_deref().map(describe).unwrap_or_default())
+                            .show_ui(ui, |ui| {
+                                for session in sessions {
+                                    ui.selectable_value(&mut self.active_session, Some(session.alias.clone()), describe(&session.alias));
+                                }
+                            });
+                    });
+                }
             });
             
             ui.separator();
@@ -461,7 +1029,7 @@ impl eframe::App for PcscTesterApp {
                         });
                         
                         if ui.add_enabled(
-                            self.connection_status == ConnectionStatus::Connected && 
+                            idle && self.connection_status == ConnectionStatus::Connected && 
                             !self.command_input.trim().is_empty(),
                             egui::Button::new("Send Transmit")
                         ).clicked() {
@@ -475,7 +1043,7 @@ impl eframe::App for PcscTesterApp {
                         ui.horizontal(|ui| {
                             ui.label("Control Code:");
                             ui.add(egui::TextEdit::singleline(&mut self.control_code_input)
-                                .hint_text("e.g., 0x42000C00 or 1107296256")
+                                .hint_text("e.g., 0x42000C00 or ctl:ESCAPE")
                                 .font(egui::TextStyle::Monospace));
                         });
                         
@@ -487,7 +1055,7 @@ impl eframe::App for PcscTesterApp {
                         });
                         
                         if ui.add_enabled(
-                            self.connection_status == ConnectionStatus::Connected && 
+                            idle && self.connection_status == ConnectionStatus::Connected && 
                             !self.control_code_input.trim().is_empty(),
                             egui::Button::new("Send Control")
                         ).clicked() {
@@ -495,6 +1063,18 @@ impl eframe::App for PcscTesterApp {
                         }
                     });
                 });
+                
+                if !idle {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Waiting for the card...");
+                        if ui.button("Cancel").clicked() {
+                            if let Some(worker) = &self.worker {
+                                worker.cancel();
+                            }
+                        }
+                    });
+                }
             });
             
             ui.separator();
@@ -535,6 +1115,18 @@ impl eframe::App for PcscTesterApp {
                     self.show_settings = true;
                 }
                 
+                if ui.button("PIN Pad").clicked() {
+                    self.show_pin_pad = true;
+                }
+                
+                if ui.button("Reader Info").clicked() {
+                    self.show_reader_info = true;
+                }
+                
+                if ui.button("Escape Commands").clicked() {
+                    self.show_escape = true;
+                }
+                
                 if ui.button("Clear Response").clicked() {
                     self.last_response.clear();
                 }
@@ -543,7 +1135,7 @@ impl eframe::App for PcscTesterApp {
     }
 }
 
-pub fn run_gui() -> Result<()> {
+pub fn run_gui(backend: BackendSpec) -> Result<()> {
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([1000.0, 700.0])
@@ -555,6 +1147,6 @@ pub fn run_gui() -> Result<()> {
     eframe::run_native(
         "PCSC Tester",  
         options,
-        Box::new(|cc| Ok(Box::new(PcscTesterApp::new(cc)))),
+        Box::new(|cc| Ok(Box::new(PcscTesterApp::new(cc, backend)))),
     ).map_err(|e| anyhow::anyhow!("GUI error: {}", e))
 }
\ No newline at end of file
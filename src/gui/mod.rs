@@ -1,3 +1,3 @@
-/// GUI interface module using egui
+//! GUI interface module using egui
 
-pub mod app;
\ No newline at end of file
+pub mod app;
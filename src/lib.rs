@@ -1,7 +1,7 @@
-/// PCSC Tester - Cross-platform tool for testing smart card readers
-/// 
-/// This library provides both CLI and GUI interfaces for interacting with 
-/// PCSC-compatible smart card readers.
+//! PCSC Tester - Cross-platform tool for testing smart card readers
+//! 
+//! This library provides both CLI and GUI interfaces for interacting with 
+//! PCSC-compatible smart card readers.
 
 pub mod cli;
 pub mod gui;
@@ -9,10 +9,12 @@ pub mod core;
 
 // Re-export commonly used types
 pub use core::{
+    backend::{PcscBackend, CardBackend, SystemBackend},
     reader::{PcscReader, ReaderInfo},
+    events::{ReaderEvent, ReaderEventKind},
     commands::{CommandResult, TransmitResult, ControlResult},
     utils::{parse_hex, format_hex},
 };
 
 // Common error type
-pub type Result<T> = anyhow::Result<T>;
\ No newline at end of file
+pub type Result<T> = anyhow::Result<T>;
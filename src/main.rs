@@ -1,32 +1,58 @@
 use std::env;
+use std::process::ExitCode;
 use anyhow::Result;
 
-mod cli;
-mod gui;
-mod core;
+use pcsc_tester::cli::commands::run_cli;
+use pcsc_tester::core::backend::BackendSpec;
+use pcsc_tester::core::error::ExitStatus;
+use pcsc_tester::gui::app::run_gui;
 
-use cli::commands::run_cli;
-use gui::app::run_gui;
-
-fn main() -> Result<()> {
+fn main() -> ExitCode {
     // Check if we have command line arguments (excluding program name)
     let args: Vec<String> = env::args().collect();
     
-    // If no arguments provided or only "--gui" flag, start GUI
-    if args.len() == 1 || (args.len() == 2 && args[1] == "--gui") {
+    // If no arguments provided or only "--gui" flag (optionally with a backend), start GUI
+    let result = if let Some(backend) = parse_gui_args(&args[1..]) {
         // GUI mode
         println!("Starting PCSC Tester GUI...");
-        run_gui()
+        backend.and_then(run_gui)
     } else {
         // CLI mode - pass arguments to CLI parser
         run_cli()
+    };
+    
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            ExitCode::from(ExitStatus::of(&e).code())
+        }
     }
 }
 
+/// Recognize `[--gui] [--backend <spec>]` command lines, which start the GUI
+fn parse_gui_args(args: &[String]) -> Option<Result<BackendSpec>> {
+    let args = match args {
+        [] => return Some(Ok(BackendSpec::default())),
+        [first, rest @ ..] if first == "--gui" => rest,
+        _ => return None,
+    };
+    
+    let spec = match args {
+        [] => return Some(Ok(BackendSpec::default())),
+        [flag, spec] if flag == "--backend" || flag == "-b" => spec.as_str(),
+        [flag] => flag.strip_prefix("--backend=")?,
+        _ => return None,
+    };
+    
+    Some(spec.parse().map_err(anyhow::Error::msg))
+}
+
 #[cfg(test)]
 mod tests {
-    use pcsc_tester::core::utils::{parse_hex, parse_control_code};
-    use crate::core::utils::*;
+    use pcsc_tester::core::utils::*;
+    use pcsc_tester::core::backend::BackendSpec;
+    use super::parse_gui_args;
 
     #[test]
     fn test_hex_parsing() {
@@ -53,4 +79,19 @@ mod tests {
         assert_eq!(describe_status_word(0x90, 0x00), "Success");
         assert_eq!(describe_status_word(0x6A, 0x82), "Error: File not found");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_gui_args() {
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        
+        assert_eq!(parse_gui_args(&args(&[])).unwrap().unwrap(), BackendSpec::System);
+        assert_eq!(parse_gui_args(&args(&["--gui"])).unwrap().unwrap(), BackendSpec::System);
+        assert_eq!(parse_gui_args(&args(&["--gui", "--backend", "sim:r.json"])).unwrap().unwrap(),
+                   BackendSpec::Sim("r.json".into()));
+        assert_eq!(parse_gui_args(&args(&["--gui", "--backend=sim:r.json"])).unwrap().unwrap(),
+                   BackendSpec::Sim("r.json".into()));
+        assert!(parse_gui_args(&args(&["--gui", "--backend", "bogus"])).unwrap().is_err());
+        assert!(parse_gui_args(&args(&["list"])).is_none());
+        assert!(parse_gui_args(&args(&["--backend", "sim:r.json", "list"])).is_none());
+    }
+}
@@ -1,4 +1,4 @@
-/// Integration tests for the CLI interface
+//! Integration tests for the CLI interface
 
 use assert_cmd::Command;
 use predicates::prelude::*;
@@ -7,6 +7,10 @@ use serial_test::serial;
 use tempfile::NamedTempFile;
 use std::io::Write;
 
+/// Exit codes of commands needing a real reader: success, no reader, no
+/// card or another PC/SC error (e.g. no PC/SC service)
+const HARDWARE_DEPENDENT_CODES: [i32; 4] = [0, 4, 5, 6];
+
 /// Helper function to create a command for testing
 fn pcsc_cmd() -> Command {
     Command::cargo_bin("pcsc-tester").expect("Failed to find pcsc-tester binary")
@@ -21,7 +25,8 @@ fn test_help_command() {
         .stdout(predicate::str::contains("Cross-platform PCSC tool"))
         .stdout(predicate::str::contains("list"))
         .stdout(predicate::str::contains("transmit"))
-        .stdout(predicate::str::contains("control"));
+        .stdout(predicate::str::contains("control"))
+        .stdout(predicate::str::contains("replay:<session.json>"));
 }
 
 #[test]
@@ -131,7 +136,7 @@ fn test_script_valid_file() {
         .arg(temp_file.path())
         .arg("0") // May fail if no reader exists, but should parse the file
         .assert()
-        .code(predicate::in_iter([0, 1])); // Allow both success and failure
+        .code(predicate::in_iter(HARDWARE_DEPENDENT_CODES)); // Allow both success and failure
 }
 
 #[test]
@@ -174,7 +179,7 @@ fn test_transmit_different_formats() {
         .arg("--format")
         .arg("hex")
         .assert()
-        .code(predicate::in_iter([0, 1])); // May fail if no reader
+        .code(predicate::in_iter(HARDWARE_DEPENDENT_CODES)); // May fail if no reader
         
     let mut cmd2 = pcsc_cmd();
     cmd2.arg("transmit")
@@ -183,7 +188,7 @@ fn test_transmit_different_formats() {
         .arg("--format")
         .arg("dump")
         .assert()
-        .code(predicate::in_iter([0, 1]));
+        .code(predicate::in_iter(HARDWARE_DEPENDENT_CODES));
 }
 
 #[test]
@@ -195,7 +200,7 @@ fn test_transmit_different_modes() {
         .arg("--mode")
         .arg("shared")
         .assert()
-        .code(predicate::in_iter([0, 1]));
+        .code(predicate::in_iter(HARDWARE_DEPENDENT_CODES));
         
     let mut cmd2 = pcsc_cmd();
     cmd2.arg("transmit")
@@ -204,7 +209,7 @@ fn test_transmit_different_modes() {
         .arg("--mode")
         .arg("exclusive")
         .assert()
-        .code(predicate::in_iter([0, 1]));
+        .code(predicate::in_iter(HARDWARE_DEPENDENT_CODES));
 }
 
 #[test]
@@ -219,7 +224,7 @@ fn test_control_different_modes() {
         .arg("--mode")
         .arg("direct")
         .assert()
-        .code(predicate::in_iter([0, 1]));
+        .code(predicate::in_iter(HARDWARE_DEPENDENT_CODES));
 }
 
 #[test]
@@ -233,7 +238,7 @@ fn test_interactive_help() {
         .arg("0")
         .timeout(std::time::Duration::from_secs(2))
         .assert()
-        .code(predicate::in_iter([0, 1, 124])); // 124 is timeout code
+        .code(predicate::in_iter(HARDWARE_DEPENDENT_CODES.into_iter().chain([124]))); // 124 is timeout code
 }
 
 #[test]
@@ -251,7 +256,7 @@ fn test_complex_script() {
         .arg("0")
         .arg("--continue-on-error")
         .assert()
-        .code(predicate::in_iter([0, 1]));
+        .code(predicate::in_iter(HARDWARE_DEPENDENT_CODES));
 }
 
 #[test]
@@ -280,6 +285,202 @@ fn test_invalid_mode() {
         .stderr(predicate::str::contains("invalid"));
 }
 
+/// Simulator rules shared by the end-to-end tests below
+const SIM_RULES: &str = r#"{
+    "reader_name": "Sim Reader",
+    "atr": "3B 02 14 50",
+    "groups": ["Station 1"],
+    "rules": [
+        { "apdu": "00A40400 *", "sw": "9000" },
+        { "apdu": "00B0 ???? *", "response": "48656C6C6F", "sw": "9000" },
+        { "apdu": "00CA ????", "sw": "6A88" }
+    ],
+    "controls": [
+        { "code": "0x42000C00", "response": "01020304" }
+    ]
+}"#;
+
+/// Write the simulator rules to a temp file and return the backend argument
+fn sim_backend() -> (NamedTempFile, String) {
+    let mut rules = NamedTempFile::new().expect("Failed to create temp file");
+    rules.write_all(SIM_RULES.as_bytes()).expect("Failed to write rules");
+    let arg = format!("sim:{}", rules.path().display());
+    (rules, arg)
+}
+
+#[test]
+fn test_sim_list() {
+    let (_rules, backend) = sim_backend();
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[0] Sim Reader [CARD - ATR: 3B 02 14 50]"));
+}
+
+#[test]
+fn test_sim_transmit() {
+    let (_rules, backend) = sim_backend();
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("transmit")
+        .arg("0")
+        .arg("00B0000005")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Response: 48 65 6C 6C 6F 90 00"))
+        .stdout(predicate::str::contains("Status: 90 00 (Success)"));
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("transmit")
+        .arg("Sim Reader")
+        .arg("00CA9F7F")
+        .arg("--backend").arg(&backend)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Error: Referenced data not found"));
+}
+
+#[test]
+fn test_sim_control() {
+    let (_rules, backend) = sim_backend();
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("control")
+        .arg("0")
+        .arg("0x42000C00")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Response: 01 02 03 04"));
+}
+
+#[test]
+fn test_sim_symbolic_control() {
+    let mut rules = NamedTempFile::new().expect("Failed to create temp file");
+    write!(rules, r#"{{
+        "reader_name": "Sim Reader",
+        "controls": [
+            {{ "code": "ctl:ESCAPE", "response": "9000" }}
+        ]
+    }}"#).unwrap();
+    let backend = format!("sim:{}", rules.path().display());
+
+    // The same code on every platform, shown with its symbolic form
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("control").arg("0").arg("ctl:3500").arg("01")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("Control Code: 0x{:X} (ctl:ESCAPE, ", pcsc::ctl_code(3500))))
+        .stdout(predicate::str::contains("Response: 90 00"));
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("control").arg("0").arg("ctl:5000")
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("Control function out of range"));
+}
+
+#[test]
+fn test_sim_script() {
+    let (_rules, backend) = sim_backend();
+    let mut script = NamedTempFile::new().expect("Failed to create temp file");
+    writeln!(script, "transmit 00A4040002A000").expect("Failed to write");
+    writeln!(script, "transmit 00B0000005").expect("Failed to write");
+    writeln!(script, "control 0x42000C00").expect("Failed to write");
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("script")
+        .arg(script.path())
+        .arg("0")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Response: 48 65 6C 6C 6F 90 00"))
+        .stdout(predicate::str::contains("Script execution completed"));
+}
+
+#[test]
+fn test_sim_interactive() {
+    let (_rules, backend) = sim_backend();
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("interactive")
+        .arg("0")
+        .write_stdin("transmit 00B0000005\nhistory\nquit\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Response: 48 65 6C 6C 6F 90 00"))
+        .stdout(predicate::str::contains("TRANSMIT - OK (T=1, "));
+}
+
+#[test]
+fn test_sim_invalid_rules() {
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg("sim:nonexistent-rules.json")
+        .arg("list")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Failed to read simulator rules"));
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg("bogus")
+        .arg("list")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid"));
+}
+
+#[test]
+fn test_sim_record_and_replay() {
+    let (_rules, backend) = sim_backend();
+    let session = NamedTempFile::new().expect("Failed to create temp file");
+    let mut script = NamedTempFile::new().expect("Failed to create temp file");
+    writeln!(script, "transmit 00A4040002A000").expect("Failed to write");
+    writeln!(script, "transmit 00B0000005").expect("Failed to write");
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("--record").arg(session.path())
+        .arg("script")
+        .arg(script.path())
+        .arg("0")
+        .assert()
+        .success();
+
+    let recorded = std::fs::read_to_string(session.path()).expect("Failed to read session");
+    assert!(recorded.contains("Sim Reader"));
+
+    // Replay in order, then by lookup
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(format!("replay:{}", session.path().display()))
+        .arg("script")
+        .arg(script.path())
+        .arg("0")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Response: 48 65 6C 6C 6F 90 00"));
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(format!("replay-lookup:{}", session.path().display()))
+        .arg("transmit")
+        .arg("Sim Reader")
+        .arg("00B0000005")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Response: 48 65 6C 6C 6F 90 00"));
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(format!("replay:{}", session.path().display()))
+        .arg("transmit")
+        .arg("0")
+        .arg("00B0000005")
+        .assert()
+        .failure();
+}
+
 // Helper function for checking if PCSC is available
 fn is_pcsc_available() -> bool {
     StdCommand::new("pcscd")
@@ -311,7 +512,7 @@ fn test_with_real_readers() {
             .arg("00A40400") // SELECT command
             .timeout(std::time::Duration::from_secs(5))
             .assert()
-            .code(predicate::in_iter([0, 1])); // Allow success or failure
+            .code(predicate::in_iter(HARDWARE_DEPENDENT_CODES)); // Allow success or failure
     }
 }
 
@@ -341,4 +542,667 @@ mod performance_tests {
         // List should complete within reasonable time (less than 5 seconds)
         assert!(duration.as_secs() < 5, "List command took too long: {:?}", duration);
     }
-}
\ No newline at end of file
+}
+#[test]
+fn test_sim_monitor() {
+    let (_rules, backend) = sim_backend();
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("monitor").arg("--timeout").arg("1")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Sim Reader: card present (ATR: 3B 02 14 50)"))
+        .stdout(predicate::str::contains("Monitoring reader events"));
+
+    // Nothing changes on the simulated reader, so no JSON lines are printed
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("monitor").arg("--json").arg("--timeout").arg("1")
+        .assert()
+        .success()
+        .stdout(predicate::str::is_empty());
+}
+
+#[test]
+fn test_sim_wait() {
+    let (_rules, backend) = sim_backend();
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("transmit").arg("--wait=1").arg("0").arg("00A40400")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Waiting for card in Sim Reader"))
+        .stdout(predicate::str::contains("Status: 90 00"));
+
+    let mut script = NamedTempFile::new().expect("Failed to create temp file");
+    writeln!(script, "wait-card 1").unwrap();
+    writeln!(script, "transmit 00A40400").unwrap();
+    writeln!(script, "wait-removal 1").unwrap();
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("script").arg(script.path()).arg("0")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("Card present (ATR: 3B 02 14 50)"))
+        .stdout(predicate::str::contains("Timed out waiting for card removal"));
+}
+
+#[test]
+fn test_sim_wait_without_card() {
+    let mut rules = NamedTempFile::new().expect("Failed to create temp file");
+    rules.write_all(br#"{ "reader_name": "Empty Reader", "card_present": false }"#).unwrap();
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(format!("sim:{}", rules.path().display()))
+        .arg("transmit").arg("--wait=1").arg("0").arg("00A40400")
+        .assert()
+        .code(9)
+        .stderr(predicate::str::contains("Timed out waiting for card"));
+}
+
+#[test]
+fn test_sim_list_detailed_state() {
+    let (_rules, backend) = sim_backend();
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("list").arg("--detailed")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Status: Card present"))
+        .stdout(predicate::str::contains("State: present"))
+        .stdout(predicate::str::contains("Events: 0"))
+        .stdout(predicate::str::contains("ATR: 3B 02 14 50"));
+}
+
+#[test]
+fn test_sim_attrib() {
+    let mut rules = NamedTempFile::new().expect("Failed to create temp file");
+    rules.write_all(br#"{
+        "reader_name": "Sim Reader",
+        "attributes": { "vendor_ifd_serial_no": "SN-0042", "vendor_ifd_version": "00000201" }
+    }"#).unwrap();
+    let backend = format!("sim:{}", rules.path().display());
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("attrib").arg("0")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Vendor name: PCSC Tester"))
+        .stdout(predicate::str::contains("IFD serial number: SN-0042"))
+        .stdout(predicate::str::contains("IFD version: 1.2.0 [00 00 02 01]"))
+        .stdout(predicate::str::contains("Channel ID").not());
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("attrib").arg("0").arg("--all")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Channel ID: not supported"));
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("attrib").arg("0").arg("vendor_ifd_serial_no").arg("--json")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"decoded\": \"SN-0042\""));
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("attrib").arg("0").arg("firmware")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown attribute: firmware"));
+}
+
+#[test]
+fn test_sim_features() {
+    let mut rules = NamedTempFile::new().expect("Failed to create temp file");
+    write!(rules, r#"{{
+        "reader_name": "Sim Pinpad",
+        "controls": [
+            {{ "code": "0x{:X}", "response": "06 04 42330006 12 04 42330012 13 04 42000DAC" }}
+        ]
+    }}"#, pcsc_tester::core::features::GET_FEATURE_REQUEST).unwrap();
+    let backend = format!("sim:{}", rules.path().display());
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("features").arg("0")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Reader features: Sim Pinpad"))
+        .stdout(predicate::str::contains("0x06 VERIFY_PIN_DIRECT"))
+        .stdout(predicate::str::contains("GET_TLV_PROPERTIES         0x42330012  Reader properties"))
+        .stdout(predicate::str::contains("0x13 CCID_ESC_COMMAND"));
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("features").arg("0").arg("--json")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"control_code\": 1110638610"));
+
+    // Readers without Part 10 support have no features
+    let (_rules, backend) = sim_backend();
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("features").arg("0")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("(no features reported)"));
+}
+
+#[test]
+fn test_sim_list_properties() {
+    let mut rules = NamedTempFile::new().expect("Failed to create temp file");
+    write!(rules, r#"{{
+        "reader_name": "Sim Pinpad",
+        "controls": [
+            {{ "code": "0x{:X}", "response": "12 04 42330012" }},
+            {{ "code": "0x42330012", "response": "01 02 1002 06 01 04 07 01 08 08 04 76312E30 0A 04 05010000 0B 02 2F07" }}
+        ]
+    }}"#, pcsc_tester::core::features::GET_FEATURE_REQUEST).unwrap();
+    let backend = format!("sim:{}", rules.path().display());
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("list").arg("--detailed")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Firmware: v1.0"))
+        .stdout(predicate::str::contains("Vendor ID: 0x072F"))
+        .stdout(predicate::str::contains("Max APDU data: 261 bytes (extended APDUs)"))
+        .stdout(predicate::str::contains("Display: 2 line(s) of 16 characters"))
+        .stdout(predicate::str::contains("PIN length: 4 to 8 digits"));
+
+    // Readers without properties only show their state
+    let (_rules, backend) = sim_backend();
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("list").arg("--detailed")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Status:"))
+        .stdout(predicate::str::contains("Max APDU data").not());
+}
+
+#[test]
+fn test_sim_escape() {
+    let mut rules = NamedTempFile::new().expect("Failed to create temp file");
+    write!(rules, r#"{{
+        "reader_name": "Sim ACR122U",
+        "controls": [
+            {{ "code": "0x{:X}", "response": "41435231323255323135" }}
+        ]
+    }}"#, pcsc::ctl_code(3500)).unwrap();
+    let backend = format!("sim:{}", rules.path().display());
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("escape").arg("0").arg("acr122u-firmware").arg("--format").arg("ascii")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Escape: acr122u-firmware (ACS ACR122U)"))
+        .stdout(predicate::str::contains("Input: FF 00 48 00 00"))
+        .stdout(predicate::str::contains("Response (ASCII): ACR122U215"));
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("escape").arg("0").arg("acr122u-led-buzzer").arg("0x0F").arg("5")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Input: FF 00 40 0F 04 05 01 01 01"));
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("escape").arg("0").arg("acr1252u-led")
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("Missing leds for acr1252u-led"));
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("escape").arg("0").arg("beep")
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("Unknown escape command: beep"));
+}
+
+#[test]
+fn test_escape_list() {
+    let mut cmd = pcsc_cmd();
+    cmd.arg("escape").arg("--list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("HID Omnikey:"))
+        .stdout(predicate::str::contains("acr122u-led-buzzer <leds> [on_time] [off_time] [repeat] [buzzer]"))
+        .stdout(predicate::str::contains("identiv-firmware"))
+        .stdout(predicate::str::contains("feitian-firmware"));
+}
+
+#[test]
+fn test_sim_contactless() {
+    let mut rules = NamedTempFile::new().expect("Failed to create temp file");
+    write!(rules, r#"{{
+        "reader_name": "Sim NFC",
+        "rules": [
+            {{ "apdu": "FFCA000000", "response": "04A23B1C", "sw": "9000" }},
+            {{ "apdu": "FFCA010000", "sw": "6A81" }},
+            {{ "apdu": "FF820000 06 FFFFFFFFFFFF", "sw": "9000" }},
+            {{ "apdu": "FF860000 05 01 0004 60 00", "sw": "9000" }},
+            {{ "apdu": "FF860000 *", "sw": "6300" }},
+            {{ "apdu": "FFB00004 10", "response": "00112233445566778899AABBCCDDEEFF", "sw": "9000" }},
+            {{ "apdu": "FFD60004 *", "sw": "9000" }}
+        ]
+    }}"#).unwrap();
+    let backend = format!("sim:{}", rules.path().display());
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("contactless").arg("0").arg("uid")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("UID: 04 A2 3B 1C"));
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("contactless").arg("0").arg("read").arg("4").arg("--key").arg("FFFFFFFFFFFF")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Block 4 (16 bytes)"))
+        .stdout(predicate::str::contains("00 11 22 33 44 55 66 77 88 99 AA BB CC DD EE FF"));
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("contactless").arg("0").arg("write").arg("4").arg("0102")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Wrote 2 byte(s) to block 4"));
+
+    // Reader-specific status words fail with an explanation
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("contactless").arg("0").arg("ats")
+        .assert()
+        .code(7)
+        .stderr(predicate::str::contains("Get ATS failed"))
+        .stderr(predicate::str::contains("6A81 (Function not supported by the reader or the card)"));
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("contactless").arg("0").arg("auth").arg("4").arg("--key-type").arg("b")
+        .assert()
+        .code(7)
+        .stderr(predicate::str::contains("General Authenticate failed"))
+        .stderr(predicate::str::contains("card returned 6300 (Operation failed on the card"));
+}
+
+#[test]
+fn test_sim_pin_pad() {
+    let mut rules = NamedTempFile::new().expect("Failed to create temp file");
+    write!(rules, r#"{{
+        "reader_name": "Sim Pinpad",
+        "controls": [
+            {{ "code": "0x{:X}", "response": "06 04 42330006" }},
+            {{ "code": "0x42330006", "response": "9000" }}
+        ]
+    }}"#, pcsc_tester::core::features::GET_FEATURE_REQUEST).unwrap();
+    let backend = format!("sim:{}", rules.path().display());
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("pin-verify").arg("0").arg("00200081 08 FFFFFFFFFFFFFFFF")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Enter the PIN on the reader's PIN pad"))
+        .stdout(predicate::str::contains("Status: 90 00 (Success)"));
+
+    // The PIN block must fit in the APDU
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("pin-verify").arg("0").arg("00200081 04 FFFFFFFF")
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("PIN block ends at byte 8"));
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("pin-modify").arg("0").arg("00240081 10 FFFFFFFFFFFFFFFF FFFFFFFFFFFFFFFF")
+        .assert()
+        .code(6)
+        .stderr(predicate::str::contains("Reader does not support MODIFY_PIN_DIRECT"));
+}
+
+#[test]
+fn test_sim_protocol_selection() {
+    let (_rules, backend) = sim_backend();
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("transmit").arg("--protocol").arg("t0").arg("0").arg("00A40400")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Protocol: T=0"));
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("transmit").arg("0").arg("00A40400")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Protocol: T=1"));
+
+    // The simulated card only speaks T=0 and T=1
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("transmit").arg("--protocol").arg("raw").arg("0").arg("00A40400")
+        .assert()
+        .failure();
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("transmit").arg("--protocol").arg("t2").arg("0").arg("00A40400")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid protocol"));
+}
+
+#[test]
+fn test_sim_reset() {
+    let (_rules, backend) = sim_backend();
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("reset").arg("--cold").arg("0")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Cold reset done"))
+        .stdout(predicate::str::contains("ATR: 3B 02 14 50"))
+        .stdout(predicate::str::contains("Protocol: T=1"));
+
+    let mut script = NamedTempFile::new().expect("Failed to create temp file");
+    writeln!(script, "reset").unwrap();
+    writeln!(script, "reconnect").unwrap();
+    writeln!(script, "transmit 00B0000005").unwrap();
+    writeln!(script, "reset lukewarm").unwrap();
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("script").arg(script.path()).arg("0")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("ATR: 3B 02 14 50"))
+        .stdout(predicate::str::contains("Response: 48 65 6C 6C 6F 90 00"))
+        .stdout(predicate::str::contains("Invalid reset type: lukewarm"));
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("interactive").arg("0")
+        .write_stdin("reset cold\nquit\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("ATR: 3B 02 14 50"));
+}
+
+#[test]
+fn test_sim_transactions() {
+    let (_rules, backend) = sim_backend();
+    let mut script = NamedTempFile::new().expect("Failed to create temp file");
+    writeln!(script, "begin").unwrap();
+    writeln!(script, "transmit 00A4040002A000").unwrap();
+    writeln!(script, "transmit 00B0000005").unwrap();
+    writeln!(script, "end reset").unwrap();
+    writeln!(script, "begin").unwrap();
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("script").arg(script.path()).arg("0")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Transaction started"))
+        .stdout(predicate::str::contains("Response: 48 65 6C 6C 6F 90 00"))
+        .stdout(predicate::str::contains("Transaction ended"));
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("interactive").arg("0")
+        .write_stdin("transaction begin\ntransaction\ntransaction begin\ntransaction end eject\ntransaction end\nquit\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Transaction: in progress"))
+        .stdout(predicate::str::contains("Error: Transaction already in progress"))
+        .stdout(predicate::str::contains("Error: No transaction in progress"));
+}
+
+#[test]
+fn test_sim_reader_groups() {
+    let (_rules, backend) = sim_backend();
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("groups")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("SCard$DefaultReaders (1 reader)"))
+        .stdout(predicate::str::contains("Station 1 (1 reader)"));
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("--group").arg("Station 1")
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[0] Sim Reader"));
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("--group").arg("Other")
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No PCSC readers found in group Other."));
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("--group").arg("Other")
+        .arg("transmit").arg("0").arg("00B0000005")
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_sim_sessions() {
+    let (_rules, backend) = sim_backend();
+    let mut script = NamedTempFile::new().expect("Failed to create temp file");
+    writeln!(script, "@sam transmit 00B0000005").unwrap();
+    writeln!(script, "use sam").unwrap();
+    writeln!(script, "control 0x42000C00").unwrap();
+    writeln!(script, "open card2 0").unwrap();
+    writeln!(script, "@card2 transmit 00A4040002A000").unwrap();
+    writeln!(script, "close sam").unwrap();
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("script").arg(script.path()).arg("0")
+        .arg("--open").arg("sam=Sim Reader")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("Session sam: Sim Reader"))
+        .stdout(predicate::str::contains("Response: 48 65 6C 6C 6F 90 00"))
+        .stdout(predicate::str::contains("Using session sam"))
+        .stdout(predicate::str::contains("Response: 01 02 03 04"))
+        .stdout(predicate::str::contains("Session card2 opened on Sim Reader"))
+        .stdout(predicate::str::contains("Cannot close the active session: sam"));
+
+    // Unknown aliases are script errors, whichever command names them
+    for line in ["@unknown 00A4040000", "close unknown"] {
+        let mut script = NamedTempFile::new().expect("Failed to create temp file");
+        writeln!(script, "{}", line).unwrap();
+        let mut cmd = pcsc_cmd();
+        cmd.arg("--backend").arg(&backend)
+            .arg("script").arg(script.path()).arg("0")
+            .assert()
+            .code(8)
+            .stderr(predicate::str::contains("Unknown session: unknown"));
+    }
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("interactive").arg("0")
+        .write_stdin("open sam 0\nsessions\nuse sam\n@default transmit 00B0000005\n@nope transmit 00B0000005\nclose default\nquit\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Session sam opened on Sim Reader"))
+        .stdout(predicate::str::contains("* default: Sim Reader"))
+        .stdout(predicate::str::contains("Response: 48 65 6C 6C 6F 90 00"))
+        .stdout(predicate::str::contains("Error: Unknown session: nope"))
+        .stdout(predicate::str::contains("Session default closed"));
+}
+
+#[test]
+fn test_sim_reader_selectors() {
+    let (_rules, backend) = sim_backend();
+    for selector in ["name~sim", "name:Sim*", "re:^Sim Reader$", "atr:3B02..", "first-with-card"] {
+        let mut cmd = pcsc_cmd();
+        cmd.arg("--backend").arg(&backend)
+            .arg("reset").arg(selector)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("Reader: Sim Reader"));
+    }
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("transmit").arg("name~Omnikey").arg("00B0000005")
+        .assert()
+        .code(4)
+        .stderr(predicate::str::contains("No reader matches name~omnikey"));
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("reset").arg("atr:3BZZ")
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("Invalid ATR selector"));
+}
+
+#[test]
+fn test_sim_exit_codes() {
+    let (_rules, backend) = sim_backend();
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("transmit").arg("0").arg("00B0000005").arg("--expect-sw").arg("90XX")
+        .assert()
+        .success();
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("transmit").arg("0").arg("00CA9F7F").arg("--expect-sw").arg("9000")
+        .assert()
+        .code(7)
+        .stderr(predicate::str::contains("Expected status word 9000, card returned 6A88"));
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("transmit").arg("0").arg("00A4ZZ")
+        .assert()
+        .code(3);
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("transmit").arg("Other Reader").arg("00A40400")
+        .assert()
+        .code(4);
+
+    let mut rules = NamedTempFile::new().expect("Failed to create temp file");
+    rules.write_all(br#"{ "reader_name": "Empty Reader", "card_present": false }"#).unwrap();
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(format!("sim:{}", rules.path().display()))
+        .arg("transmit").arg("0").arg("00A40400")
+        .assert()
+        .code(5);
+}
+
+#[test]
+fn test_sim_extended_apdus() {
+    let certificate = "30".repeat(4000);
+    let rules = format!(r#"{{
+        "reader_name": "PIV Reader",
+        "rules": [{{ "apdu": "00CB3FFF *", "response": "{}" }}]
+    }}"#, certificate);
+    let mut file = NamedTempFile::new().expect("Failed to create temp file");
+    file.write_all(rules.as_bytes()).unwrap();
+    let backend = format!("sim:{}", file.path().display());
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("transmit").arg("0").arg("00CB3FFF 00 0005 5C035FC105 0000").arg("--format").arg("hex")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("{}9000", "30".repeat(4000))));
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("transmit").arg("0").arg("00CB3FFF 00 0005 5C03")
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("Invalid extended-length APDU"));
+
+    let mut file = NamedTempFile::new().expect("Failed to create temp file");
+    file.write_all(rules.replace("\"rules\"", "\"extended_length\": false, \"rules\"").as_bytes()).unwrap();
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(format!("sim:{}", file.path().display()))
+        .arg("transmit").arg("0").arg("00CB3FFF 00 0005 5C035FC105 0000")
+        .assert()
+        .code(6)
+        .stderr(predicate::str::contains("may not support extended APDUs"));
+}
+
+#[test]
+fn test_sim_command_timeout() {
+    let rules = r#"{
+        "rules": [
+            { "apdu": "80CA0001", "response": "9000", "delay_ms": 20000 },
+            { "apdu": "80CA0002", "response": "9000", "delay_ms": 50 }
+        ]
+    }"#;
+    let mut file = NamedTempFile::new().expect("Failed to create temp file");
+    file.write_all(rules.as_bytes()).unwrap();
+    let backend = format!("sim:{}", file.path().display());
+
+    let start = std::time::Instant::now();
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend).arg("--command-timeout").arg("200")
+        .arg("transmit").arg("0").arg("80CA0001")
+        .assert()
+        .code(9)
+        .stderr(predicate::str::contains("Transmit failed"));
+    assert!(start.elapsed() < std::time::Duration::from_secs(10));
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend).arg("--command-timeout").arg("5000")
+        .arg("transmit").arg("0").arg("80CA0002")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("90 00"));
+}
+
+#[test]
+fn test_sim_script_exit_codes() {
+    let (_rules, backend) = sim_backend();
+    let mut script = NamedTempFile::new().expect("Failed to create temp file");
+    writeln!(script, "transmit 00B0000005").unwrap();
+    writeln!(script, "expect 9000").unwrap();
+    writeln!(script, "transmit 00CA9F7F").unwrap();
+    writeln!(script, "expect 90 00").unwrap();
+
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("script").arg(script.path()).arg("0")
+        .assert()
+        .code(7)
+        .stdout(predicate::str::contains("Status word matches 9000"))
+        .stderr(predicate::str::contains("stopped due to error on line 4"));
+
+    let mut script = NamedTempFile::new().expect("Failed to create temp file");
+    writeln!(script, "transmitt 00B0000005").unwrap();
+    let mut cmd = pcsc_cmd();
+    cmd.arg("--backend").arg(&backend)
+        .arg("script").arg(script.path()).arg("0")
+        .assert()
+        .code(8)
+        .stderr(predicate::str::contains("Unknown command: transmitt"));
+}
@@ -1,4 +1,4 @@
-/// Unit tests for GUI components that don't require window creation
+//! Unit tests for GUI components that don't require window creation
 
 use pcsc_tester::core::{
     reader::ReaderInfo,
@@ -14,12 +14,14 @@ fn test_gui_reader_info_display() {
         name: "Test Reader 1".to_string(),
         is_connected: true,
         atr: Some(vec![0x3B, 0x75, 0x13, 0x00]),
+        ..Default::default()
     };
 
     let reader_without_card = ReaderInfo {
         name: "Test Reader 2".to_string(),
         is_connected: false,
         atr: None,
+        ..Default::default()
     };
 
     // Test display formatting for GUI
@@ -45,6 +47,7 @@ fn test_gui_command_history_formatting() {
         success: true,
         error: None,
         duration_ms: 25,
+        protocol: None,
     });
 
     executor.add_to_history(CommandResult {
@@ -55,6 +58,7 @@ fn test_gui_command_history_formatting() {
         success: false,
         error: Some("Connection failed".to_string()),
         duration_ms: 15,
+        protocol: None,
     });
 
     let history = executor.history();
@@ -112,7 +116,7 @@ fn test_gui_response_formatting() {
         if response.is_empty() {
             assert_eq!(hex_output, "");
         } else {
-            assert!(hex_output.len() > 0);
+            assert!(!hex_output.is_empty());
             assert!(!hex_output.contains("x")); // Should not contain 0x prefix
             
             // Should be space-separated pairs
@@ -143,6 +147,7 @@ fn test_gui_statistics_display() {
             success: i < 7,
             error: if i >= 7 { Some(format!("Error {}", i)) } else { None },
             duration_ms: (i + 1) * 10,
+            protocol: None,
         });
     }
 
@@ -166,6 +171,7 @@ fn test_gui_export_import_functionality() {
         success: true,
         error: None,
         duration_ms: 42,
+        protocol: None,
     });
 
     // Export (like GUI save function would do)
@@ -203,21 +209,24 @@ fn test_gui_control_code_parsing() {
 
 #[test]
 fn test_gui_reader_selection_logic() {
-    let readers = vec![
+    let readers = [
         ReaderInfo {
             name: "Reader 1".to_string(),
             is_connected: false,
             atr: None,
+            ..Default::default()
         },
         ReaderInfo {
             name: "Reader 2".to_string(),
             is_connected: true,
             atr: Some(vec![0x3B, 0xAC, 0x00]),
+            ..Default::default()
         },
         ReaderInfo {
             name: "Reader 3".to_string(),
             is_connected: true,
             atr: Some(vec![0x3B, 0x75, 0x13, 0x00]),
+            ..Default::default()
         },
     ];
 
@@ -259,6 +268,7 @@ fn test_gui_error_handling() {
         success: false,
         error: Some("Card not present".to_string()),
         duration_ms: 5,
+        protocol: None,
     });
 
     executor.add_to_history(CommandResult {
@@ -269,6 +279,7 @@ fn test_gui_error_handling() {
         success: false,
         error: Some("Reader not connected".to_string()),
         duration_ms: 1,
+        protocol: None,
     });
 
     let stats = executor.get_statistics();
@@ -300,6 +311,7 @@ fn test_gui_performance_characteristics() {
             success: true,
             error: None,
             duration_ms: i as u64,
+            protocol: None,
         });
     }
 
@@ -1,11 +1,26 @@
-/// Mock PCSC tests for testing without real hardware
+//! Mock PCSC tests for testing without real hardware
 
 use pcsc_tester::core::{
-    reader::ReaderInfo,
+    backend::{self, BackendResult, CardBackend, CardStatus, Canceller, PcscBackend, ReaderState},
+    cancel::CancelToken,
+    reader::{PcscReader, ReaderInfo},
+    manager::SessionManager,
+    events::ReaderEventKind,
+    features,
+    pinpad::{self, PinModify, PinVerify},
     commands::{CommandExecutor, CommandResult, CommandType},
+    error::ExitStatus,
+    escape,
+    contactless::StorageCommand,
+    recovery::RecoveryPolicy,
+    worker::ReaderWorker,
     utils::{parse_hex, format_hex_spaced},
 };
+use pcsc::{Attribute, Disposition, Protocol, Protocols, ShareMode, State};
 use chrono::Utc;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 /// Mock PCSC reader for testing
 #[derive(Debug, Clone)]
@@ -25,16 +40,19 @@ impl MockPcscReader {
                     name: "Mock Reader 1".to_string(),
                     is_connected: true,
                     atr: Some(vec![0x3B, 0xAC, 0x00, 0x40, 0x2A, 0x00, 0x12, 0x25, 0x00, 0x64, 0x80, 0x00, 0x03, 0x10, 0x00, 0x90, 0x00]),
+                    ..Default::default()
                 },
                 ReaderInfo {
                     name: "Mock Reader 2".to_string(),
                     is_connected: false,
                     atr: None,
+                    ..Default::default()
                 },
                 ReaderInfo {
                     name: "Mock Reader 3".to_string(),
                     is_connected: true,
                     atr: Some(vec![0x3B, 0x75, 0x13, 0x00, 0x00, 0x47, 0x09, 0xEA, 0x90, 0x00]),
+                    ..Default::default()
                 },
             ],
             current_reader: None,
@@ -288,6 +306,7 @@ fn test_mock_command_executor_integration() {
         success: true,
         error: None,
         duration_ms: duration.as_millis() as u64,
+        protocol: None,
     });
     
     let history = executor.history();
@@ -376,6 +395,7 @@ fn test_mock_cli_workflow() {
         success: true,
         error: None,
         duration_ms: 25,
+        protocol: None,
     });
     
     // Step 4: Send control command
@@ -388,6 +408,7 @@ fn test_mock_cli_workflow() {
         success: true,
         error: None,
         duration_ms: 15,
+        protocol: None,
     });
     
     // Verify workflow
@@ -395,4 +416,935 @@ fn test_mock_cli_workflow() {
     assert_eq!(stats.total_commands, 2);
     assert_eq!(stats.successful_commands, 2);
     assert_eq!(stats.failed_commands, 0);
-}
\ No newline at end of file
+}
+
+/// Mock backend plugged into the real `PcscReader`/`CommandExecutor`
+struct MockBackend {
+    readers: Mutex<Vec<ReaderInfo>>,
+    /// Successive reader configurations, applied one per status change call
+    timeline: Mutex<VecDeque<Vec<ReaderInfo>>>,
+    connect_error: Option<pcsc::Error>,
+    transmit_error: Option<pcsc::Error>,
+    /// Error of GET_FEATURE_REQUEST on readers without Part 10 features
+    feature_error: pcsc::Error,
+    /// Errors returned by the next transmits, shared with the cards
+    transient_errors: Arc<Mutex<VecDeque<pcsc::Error>>>,
+    /// Whether the plug-and-play pseudo-reader is supported
+    hotplug: bool,
+    /// Number of changes of the reader list, reported by the pseudo-reader
+    plug_count: Mutex<u32>,
+}
+
+impl MockBackend {
+    fn new() -> Self {
+        Self {
+            readers: Mutex::new(MockPcscReader::new().readers),
+            timeline: Mutex::new(VecDeque::new()),
+            connect_error: None,
+            transmit_error: None,
+            feature_error: pcsc::Error::UnsupportedFeature,
+            transient_errors: Arc::new(Mutex::new(VecDeque::new())),
+            hotplug: true,
+            plug_count: Mutex::new(0),
+        }
+    }
+
+    fn readers(&self) -> Vec<ReaderInfo> {
+        self.readers.lock().unwrap().clone()
+    }
+
+    fn update_states(&self, states: &mut [ReaderState]) -> bool {
+        let readers = self.readers();
+        let mut any_changed = false;
+        for state in states.iter_mut() {
+            if self.hotplug && state.name == backend::PNP_NOTIFICATION {
+                let plug_count = *self.plug_count.lock().unwrap();
+                state.event_count = plug_count;
+                state.event_state = State::EMPTY;
+                if state.current_state == State::UNAWARE || state.current_count != plug_count {
+                    state.event_state |= State::CHANGED;
+                    any_changed = true;
+                }
+                continue;
+            }
+            let (observed, atr) = match readers.iter().find(|r| r.name == state.name) {
+                Some(ReaderInfo { atr: Some(atr), .. }) => (State::PRESENT, atr.clone()),
+                Some(_) => (State::EMPTY, Vec::new()),
+                None => (State::UNKNOWN, Vec::new()),
+            };
+            state.atr = atr;
+            state.event_count = state.current_count;
+            if observed != state.current_state {
+                state.event_state = observed | State::CHANGED;
+                any_changed = true;
+            } else {
+                state.event_state = observed;
+            }
+        }
+        any_changed
+    }
+}
+
+struct MockCard {
+    reader_name: String,
+    atr: Vec<u8>,
+    transmit_error: Option<pcsc::Error>,
+    feature_error: pcsc::Error,
+    transient_errors: Arc<Mutex<VecDeque<pcsc::Error>>>,
+    /// Initializations requested through `reconnect`
+    reconnects: Vec<Disposition>,
+    in_transaction: bool,
+    /// Dispositions of the transactions ended so far
+    transactions: Vec<Disposition>,
+    /// Interrupts an APDU the card does not answer
+    interrupt: CancelToken,
+}
+
+impl PcscBackend for MockBackend {
+    type Card = MockCard;
+
+    fn list_readers(&self) -> BackendResult<Vec<String>> {
+        Ok(self.readers().iter().map(|r| r.name.clone()).collect())
+    }
+
+    fn list_reader_groups(&self) -> BackendResult<Vec<String>> {
+        Ok(vec!["SCard$DefaultReaders".to_string(), "Station A".to_string(), "Station B".to_string()])
+    }
+
+    fn list_readers_in_group(&self, group: &str) -> BackendResult<Vec<String>> {
+        let readers = self.list_readers()?;
+        let members: Vec<String> = match group {
+            "SCard$DefaultReaders" => readers,
+            "Station A" => readers.into_iter().filter(|r| r == "Mock Reader 1").collect(),
+            "Station B" => readers.into_iter().filter(|r| r != "Mock Reader 1").collect(),
+            _ => Vec::new(),
+        };
+        if members.is_empty() {
+            return Err(pcsc::Error::NoReadersAvailable);
+        }
+        Ok(members)
+    }
+
+    fn connect(&self, reader_name: &str, _share_mode: ShareMode, _protocols: Protocols) -> BackendResult<MockCard> {
+        if let Some(e) = self.connect_error {
+            return Err(e);
+        }
+        let reader = self.readers().into_iter()
+            .find(|r| r.name == reader_name)
+            .ok_or(pcsc::Error::UnknownReader)?;
+        let atr = reader.atr.clone().ok_or(pcsc::Error::NoSmartcard)?;
+        Ok(MockCard {
+            reader_name: reader.name.clone(),
+            atr,
+            transmit_error: self.transmit_error,
+            feature_error: self.feature_error,
+            transient_errors: Arc::clone(&self.transient_errors),
+            reconnects: Vec::new(),
+            in_transaction: false,
+            transactions: Vec::new(),
+            interrupt: CancelToken::new(),
+        })
+    }
+
+    fn get_status_change(&self, timeout: Option<Duration>, readers: &mut [ReaderState]) -> BackendResult<()> {
+        if self.update_states(readers) {
+            return Ok(());
+        }
+        if let Some(next) = self.timeline.lock().unwrap().pop_front() {
+            let names = |readers: &[ReaderInfo]| readers.iter().map(|r| r.name.clone()).collect::<Vec<_>>();
+            if names(&next) != names(&self.readers()) {
+                *self.plug_count.lock().unwrap() += 1;
+            }
+            *self.readers.lock().unwrap() = next;
+            if self.update_states(readers) {
+                return Ok(());
+            }
+        }
+        std::thread::sleep(timeout.unwrap_or_default().min(Duration::from_millis(10)));
+        Err(pcsc::Error::Timeout)
+    }
+
+    fn canceller(&self) -> Canceller {
+        // Status change calls return within 10 ms anyway
+        Arc::new(|| {})
+    }
+}
+
+impl CardBackend for MockCard {
+    fn status(&self) -> BackendResult<CardStatus> {
+        Ok(CardStatus {
+            reader_name: self.reader_name.clone(),
+            protocol: Some(Protocol::T1),
+            atr: self.atr.clone(),
+        })
+    }
+
+    fn transmit(&self, apdu: &[u8]) -> BackendResult<Vec<u8>> {
+        if let Some(e) = self.transmit_error.or_else(|| self.transient_errors.lock().unwrap().pop_front()) {
+            return Err(e);
+        }
+        match apdu {
+            [0x00, 0xB0, ..] => Ok(vec![0x48, 0x65, 0x6C, 0x6C, 0x6F, 0x90, 0x00]),
+            // A MIFARE Classic card: it has a UID but no ATS
+            [0xFF, 0xCA, 0x00, ..] => Ok(vec![0x04, 0xA2, 0x3B, 0x1C, 0x90, 0x00]),
+            [0xFF, 0xCA, 0x01, ..] => Ok(vec![0x6A, 0x81]),
+            // GET DATA of a PIV certificate, answered in one extended response
+            [0x00, 0xCB, 0x3F, 0xFF, ..] => Ok([vec![0x30; 3000], vec![0x90, 0x00]].concat()),
+            // A card that never answers, until the wait is abandoned
+            [0x00, 0xFF, ..] => {
+                self.interrupt.reset();
+                self.interrupt.sleep(Duration::from_secs(30)).map_err(|_| pcsc::Error::Cancelled)?;
+                Ok(vec![0x90, 0x00])
+            }
+            _ => Ok(vec![0x90, 0x00]),
+        }
+    }
+
+    fn control(&self, code: u32, data: &[u8]) -> BackendResult<Vec<u8>> {
+        match code {
+            0x42000C00 => Ok(vec![0x01, 0x02, 0x03, 0x04]),
+            // Reader 3 has a PIN pad
+            features::GET_FEATURE_REQUEST if self.reader_name == "Mock Reader 3" => Ok(vec![
+                0x06, 0x04, 0x42, 0x33, 0x00, 0x06,
+                0x07, 0x04, 0x42, 0x33, 0x00, 0x07,
+                0x0A, 0x04, 0x42, 0x33, 0x00, 0x0A,
+                0x13, 0x04, 0x42, 0x00, 0x0D, 0xAC,
+            ]),
+            features::GET_FEATURE_REQUEST => Err(self.feature_error),
+            // The user enters a wrong PIN, then cancels the change
+            0x42330006 => Ok(vec![0x63, 0xC2]),
+            0x42330007 => Ok(vec![0x64, 0x01]),
+            // No display, validation key, 30 s timeout
+            0x4233000A => Ok(vec![0x00, 0x00, 0x02, 0x1E]),
+            _ => Ok(data.to_vec()),
+        }
+    }
+
+    fn get_attribute(&self, attribute: Attribute) -> BackendResult<Vec<u8>> {
+        match attribute {
+            Attribute::VendorName => Ok(b"Mock Vendor\0".to_vec()),
+            Attribute::VendorIfdSerialNo => Ok(b"MOCK-0001".to_vec()),
+            Attribute::VendorIfdVersion => Ok(vec![0x00, 0x00, 0x03, 0x01]),
+            Attribute::AtrString => Ok(self.atr.clone()),
+            _ => Err(pcsc::Error::UnsupportedFeature),
+        }
+    }
+
+    fn set_attribute(&self, _attribute: Attribute, _data: &[u8]) -> BackendResult<()> {
+        Err(pcsc::Error::UnsupportedFeature)
+    }
+
+    fn reconnect(&mut self, _share_mode: ShareMode, _protocols: Protocols, initialization: Disposition) -> BackendResult<()> {
+        self.reconnects.push(initialization);
+        Ok(())
+    }
+
+    fn begin_transaction(&mut self) -> BackendResult<()> {
+        if self.in_transaction {
+            return Err(pcsc::Error::InvalidValue);
+        }
+        self.in_transaction = true;
+        Ok(())
+    }
+
+    fn end_transaction(&mut self, disposition: Disposition) -> BackendResult<()> {
+        if !self.in_transaction {
+            return Err(pcsc::Error::NotTransacted);
+        }
+        self.in_transaction = false;
+        self.transactions.push(disposition);
+        Ok(())
+    }
+
+    fn disconnect(self: Box<Self>, _disposition: Disposition) -> BackendResult<()> {
+        Ok(())
+    }
+
+    fn canceller(&self) -> Canceller {
+        self.interrupt.canceller()
+    }
+}
+
+#[test]
+fn test_backend_list_readers() {
+    let reader = PcscReader::with_backend(MockBackend::new());
+    let readers = reader.list_readers().unwrap();
+
+    assert_eq!(readers.len(), 3);
+    assert!(readers[0].is_connected);
+    assert_eq!(readers[0].atr, MockBackend::new().readers()[0].atr);
+    assert!(!readers[1].is_connected);
+    assert!(readers[1].atr.is_none());
+    assert!(readers[0].status.present && !readers[0].status.empty);
+    assert!(readers[1].status.empty && !readers[1].status.present);
+}
+
+#[test]
+fn test_backend_list_readers_held_exclusively() {
+    // Listing must not depend on being able to connect to the card
+    let mut backend = MockBackend::new();
+    backend.connect_error = Some(pcsc::Error::SharingViolation);
+    let reader = PcscReader::with_backend(backend);
+    let readers = reader.list_readers().unwrap();
+
+    assert!(readers[0].is_connected);
+    assert!(readers[0].atr.is_some());
+}
+
+#[test]
+fn test_backend_executor_transmit_and_control() {
+    let mut reader = PcscReader::with_backend(MockBackend::new());
+    let mut executor = CommandExecutor::new();
+
+    reader.connect("Mock Reader 1", ShareMode::Shared).unwrap();
+    assert_eq!(reader.current_reader(), Some("Mock Reader 1"));
+
+    let result = executor.transmit(&mut reader, "00B0000005").unwrap();
+    assert_eq!(result.response, vec![0x48, 0x65, 0x6C, 0x6C, 0x6F, 0x90, 0x00]);
+    assert_eq!((result.sw1, result.sw2), (0x90, 0x00));
+
+    let result = executor.control(&mut reader, 0x42000C00, "").unwrap();
+    assert_eq!(result.output, vec![0x01, 0x02, 0x03, 0x04]);
+
+    let stats = executor.get_statistics();
+    assert_eq!(stats.total_commands, 2);
+    assert_eq!(stats.successful_commands, 2);
+}
+
+#[test]
+fn test_backend_executor_records_failures() {
+    let mut backend = MockBackend::new();
+    backend.transmit_error = Some(pcsc::Error::RemovedCard);
+    let mut reader = PcscReader::with_backend(backend);
+    let mut executor = CommandExecutor::new();
+
+    reader.connect("Mock Reader 1", ShareMode::Shared).unwrap();
+    assert!(executor.transmit(&mut reader, "00A40400").is_err());
+
+    let history = executor.history();
+    assert_eq!(history.len(), 1);
+    assert!(!history[0].success);
+    assert!(history[0].error.as_ref().unwrap().contains("Transmit failed"));
+}
+
+#[test]
+fn test_backend_extended_apdus() {
+    let mut reader = PcscReader::with_backend(MockBackend::new());
+    let mut executor = CommandExecutor::new();
+    reader.connect("Mock Reader 1", ShareMode::Shared).unwrap();
+
+    let result = executor.transmit(&mut reader, "00CB3FFF 00 0005 5C035FC105 0000").unwrap();
+    assert_eq!(result.response.len(), 3002);
+    assert_eq!((result.sw1, result.sw2), (0x90, 0x00));
+
+    // Lc announces more data than the APDU carries
+    let error = executor.transmit(&mut reader, "00CB3FFF 00 0010 5C035FC105 0000").unwrap_err();
+    assert_eq!(ExitStatus::of(&error), ExitStatus::InvalidInput);
+    assert!(format!("{:#}", error).contains("Extended Lc announces 16 data bytes"), "{:#}", error);
+    assert_eq!(executor.history().len(), 1);
+
+    let mut backend = MockBackend::new();
+    backend.transmit_error = Some(pcsc::Error::InsufficientBuffer);
+    let mut reader = PcscReader::with_backend(backend);
+    reader.connect("Mock Reader 1", ShareMode::Shared).unwrap();
+    let error = executor.transmit(&mut reader, "00CB3FFF 00 0005 5C035FC105 0000").unwrap_err();
+    assert!(format!("{:#}", error).contains("extended-length APDU (14 bytes) was rejected"), "{:#}", error);
+    let error = executor.transmit(&mut reader, "00B0000005").unwrap_err();
+    assert!(!format!("{:#}", error).contains("extended"));
+}
+
+#[test]
+fn test_backend_command_timeout() {
+    let mut reader = PcscReader::with_backend(MockBackend::new());
+    let mut executor = CommandExecutor::new();
+    executor.set_timeout(Some(Duration::from_millis(100)));
+    reader.connect("Mock Reader 1", ShareMode::Shared).unwrap();
+
+    let start = std::time::Instant::now();
+    let error = executor.transmit(&mut reader, "00FF0000").unwrap_err();
+    assert!(start.elapsed() < Duration::from_secs(10));
+    assert_eq!(ExitStatus::of(&error), ExitStatus::Timeout);
+    assert!(!executor.history()[0].success);
+
+    // The connection is still usable
+    assert!(executor.transmit(&mut reader, "00A40400").is_ok());
+}
+
+#[test]
+fn test_backend_cancel() {
+    let mut reader = PcscReader::with_backend(MockBackend::new());
+    let mut executor = CommandExecutor::new();
+    reader.connect("Mock Reader 1", ShareMode::Shared).unwrap();
+
+    let token = reader.cancel_token().clone();
+    let canceller = token.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(100));
+        canceller.cancel();
+    });
+    let error = executor.transmit(&mut reader, "00FF0000").unwrap_err();
+    assert_eq!(ExitStatus::of(&error), ExitStatus::Cancelled);
+
+    // A cancelled token fails later operations until reset
+    let error = executor.transmit(&mut reader, "00A40400").unwrap_err();
+    assert_eq!(ExitStatus::of(&error), ExitStatus::Cancelled);
+    let error = reader.watch(None, |_| true).unwrap_err();
+    assert_eq!(ExitStatus::of(&error), ExitStatus::Cancelled);
+    let error = reader.wait_for_card("Mock Reader 2", None).unwrap_err();
+    assert!(!format!("{:#}", error).contains("Timed out"), "{:#}", error);
+
+    token.reset();
+    assert!(executor.transmit(&mut reader, "00A40400").is_ok());
+}
+
+#[test]
+fn test_backend_cancel_watch() {
+    let reader = PcscReader::with_backend(MockBackend::new());
+    let canceller = reader.cancel_token().clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(100));
+        canceller.cancel();
+    });
+    let error = reader.watch(None, |_| true).unwrap_err();
+    assert_eq!(ExitStatus::of(&error), ExitStatus::Cancelled);
+}
+
+#[test]
+fn test_backend_connect_without_card() {
+    let mut reader = PcscReader::with_backend(MockBackend::new());
+    assert!(reader.connect("Mock Reader 2", ShareMode::Shared).is_err());
+    assert!(!reader.is_connected());
+
+    let mut executor = CommandExecutor::new();
+    assert!(executor.transmit(&mut reader, "00A40400").is_err());
+}
+
+#[test]
+fn test_backend_boxed() {
+    let mut reader = PcscReader::with_backend(backend::boxed(MockBackend::new()));
+    let mut executor = CommandExecutor::new();
+
+    reader.connect("Mock Reader 3", ShareMode::Shared).unwrap();
+    let result = executor.transmit(&mut reader, "00A40400").unwrap();
+    assert_eq!(result.response, vec![0x90, 0x00]);
+    reader.disconnect(Disposition::LeaveCard).unwrap();
+    assert!(!reader.is_connected());
+}
+
+#[test]
+fn test_backend_recording_and_replay() {
+    let mut reader = PcscReader::with_backend(MockBackend::new());
+    let mut executor = CommandExecutor::new();
+    executor.start_recording();
+
+    reader.connect("Mock Reader 3", ShareMode::Shared).unwrap();
+    executor.transmit(&mut reader, "00B0000005").unwrap();
+    executor.control(&mut reader, 0x42000C00, "").unwrap();
+
+    let session = executor.stop_recording().unwrap();
+    assert!(executor.recording().is_none());
+    assert_eq!(session.reader_name, "Mock Reader 3");
+    assert_eq!(session.atr, vec![0x3B, 0x75, 0x13, 0x00, 0x00, 0x47, 0x09, 0xEA, 0x90, 0x00]);
+    assert_eq!(session.protocol, Some(backend::CardProtocol::T1));
+    assert_eq!(session.exchanges.len(), 2);
+
+    // Replay the session without the mock card
+    let replay = backend::ReplayBackend::new(session, backend::ReplayMode::Sequential);
+    let mut reader = PcscReader::with_backend(replay);
+    let mut executor = CommandExecutor::new();
+
+    let readers = reader.list_readers().unwrap();
+    assert_eq!(readers[0].name, "Mock Reader 3");
+    assert!(readers[0].is_connected);
+
+    reader.connect("Mock Reader 3", ShareMode::Shared).unwrap();
+    let result = executor.transmit(&mut reader, "00B0000005").unwrap();
+    assert_eq!(result.response, vec![0x48, 0x65, 0x6C, 0x6C, 0x6F, 0x90, 0x00]);
+    let result = executor.control(&mut reader, 0x42000C00, "").unwrap();
+    assert_eq!(result.output, vec![0x01, 0x02, 0x03, 0x04]);
+    assert!(executor.transmit(&mut reader, "00B0000005").is_err());
+}
+
+/// Watch a timeline with a card insertion, a removal and readers swapped
+fn watch_timeline(hotplug: bool) -> Vec<(String, ReaderEventKind)> {
+    let mut backend = MockBackend::new();
+    backend.hotplug = hotplug;
+    let mut readers = backend.readers();
+
+    // Card inserted in reader 2
+    readers[1].atr = Some(vec![0x3B, 0x02, 0x14, 0x50]);
+    backend.timeline.lock().unwrap().push_back(readers.clone());
+
+    // Card removed from reader 1, reader 3 unplugged, reader 4 plugged with a card
+    readers[0].atr = None;
+    readers.remove(2);
+    readers.push(ReaderInfo {
+        name: "Mock Reader 4".to_string(),
+        is_connected: false,
+        atr: Some(vec![0x3B, 0x00]),
+        ..Default::default()
+    });
+    backend.timeline.lock().unwrap().push_back(readers);
+
+    let reader = PcscReader::with_backend(backend);
+    assert_eq!(reader.supports_hotplug(), hotplug);
+    let mut events = Vec::new();
+    reader.watch(Some(Duration::from_millis(300)), |event| {
+        events.push((event.reader.clone(), event.kind.clone()));
+        true
+    }).unwrap();
+    events
+}
+
+#[test]
+fn test_backend_watch_events() {
+    let expected = vec![
+        ("Mock Reader 2".to_string(), ReaderEventKind::CardInserted { atr: vec![0x3B, 0x02, 0x14, 0x50] }),
+        ("Mock Reader 1".to_string(), ReaderEventKind::CardRemoved),
+        ("Mock Reader 3".to_string(), ReaderEventKind::ReaderDetached),
+        ("Mock Reader 4".to_string(), ReaderEventKind::ReaderAttached),
+        ("Mock Reader 4".to_string(), ReaderEventKind::CardInserted { atr: vec![0x3B, 0x00] }),
+    ];
+    // Plug-and-play notification, and periodic listing without it
+    assert_eq!(watch_timeline(true), expected);
+    assert_eq!(watch_timeline(false), expected);
+}
+
+#[test]
+fn test_backend_watch_stops_on_callback() {
+    let backend = MockBackend::new();
+    let mut readers = backend.readers();
+    readers[0].atr = None;
+    backend.timeline.lock().unwrap().push_back(readers.clone());
+    readers[0].atr = Some(vec![0x3B, 0x00]);
+    backend.timeline.lock().unwrap().push_back(readers);
+
+    let reader = PcscReader::with_backend(backend);
+    let mut count = 0;
+    reader.watch(None, |_| {
+        count += 1;
+        false
+    }).unwrap();
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn test_backend_wait_for_card() {
+    let backend = MockBackend::new();
+    let mut readers = backend.readers();
+    readers[1].atr = Some(vec![0x3B, 0x00]);
+    backend.timeline.lock().unwrap().push_back(readers);
+    let reader = PcscReader::with_backend(backend);
+
+    // Already present
+    let atr = reader.wait_for_card("Mock Reader 1", Some(Duration::ZERO)).unwrap();
+    assert_eq!(atr, MockBackend::new().readers()[0].atr.clone().unwrap());
+
+    // Inserted while waiting
+    let atr = reader.wait_for_card("Mock Reader 2", Some(Duration::from_secs(1))).unwrap();
+    assert_eq!(atr, vec![0x3B, 0x00]);
+
+    assert!(reader.wait_for_card("Unknown Reader", Some(Duration::from_secs(1))).is_err());
+}
+
+#[test]
+fn test_backend_wait_for_removal() {
+    let backend = MockBackend::new();
+    let mut readers = backend.readers();
+    readers[0].atr = None;
+    backend.timeline.lock().unwrap().push_back(readers);
+    let reader = PcscReader::with_backend(backend);
+
+    reader.wait_for_removal("Mock Reader 2", Some(Duration::ZERO)).unwrap();
+    reader.wait_for_removal("Mock Reader 1", Some(Duration::from_secs(1))).unwrap();
+
+    let err = reader.wait_for_removal("Mock Reader 3", Some(Duration::from_millis(100))).unwrap_err();
+    assert!(err.to_string().contains("Timed out waiting for card removal"));
+}
+
+#[test]
+fn test_backend_attributes() {
+    let mut reader = PcscReader::with_backend(MockBackend::new());
+    assert!(reader.get_attribute(Attribute::VendorName).is_err());
+
+    reader.connect("Mock Reader 1", ShareMode::Direct).unwrap();
+    assert_eq!(reader.get_attribute(Attribute::VendorIfdSerialNo).unwrap(), b"MOCK-0001".to_vec());
+    assert!(reader.get_attribute(Attribute::ChannelId).is_err());
+    assert!(reader.set_attribute(Attribute::ChannelId, &[0x01]).is_err());
+
+    let values = reader.read_attributes().unwrap();
+    let names: Vec<&str> = values.iter().map(|v| v.name.as_str()).collect();
+    assert_eq!(names, vec!["vendor_name", "vendor_ifd_version", "vendor_ifd_serial_no", "atr_string"]);
+    assert_eq!(values[0].decoded, "Mock Vendor");
+    assert_eq!(values[1].decoded, "1.3.0");
+}
+
+#[test]
+fn test_backend_features() {
+    let mut reader = PcscReader::with_backend(MockBackend::new());
+    assert!(reader.features().is_err());
+
+    reader.connect("Mock Reader 1", ShareMode::Direct).unwrap();
+    assert!(reader.features().unwrap().is_empty());
+
+    reader.connect("Mock Reader 3", ShareMode::Direct).unwrap();
+    let features = reader.features().unwrap();
+    let names: Vec<&str> = features.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, vec!["VERIFY_PIN_DIRECT", "MODIFY_PIN_DIRECT", "IFD_PIN_PROPERTIES", "CCID_ESC_COMMAND"]);
+    assert_eq!(features.control_code(features::FEATURE_VERIFY_PIN_DIRECT), Some(0x42330006));
+}
+
+#[test]
+fn test_backend_properties() {
+    let mut reader = PcscReader::with_backend(MockBackend::new());
+    assert!(reader.properties().is_err());
+
+    reader.connect("Mock Reader 1", ShareMode::Direct).unwrap();
+    assert!(reader.properties().unwrap().is_empty());
+
+    // Reader 3 only has IFD_PIN_PROPERTIES
+    reader.connect("Mock Reader 3", ShareMode::Direct).unwrap();
+    let properties = reader.properties().unwrap();
+    assert_eq!(properties.lcd_size(), None);
+    assert_eq!(properties.entry_validation_condition, Some(pinpad::VALIDATE_KEY));
+    assert_eq!(properties.timeout2, Some(30));
+    assert_eq!(properties.max_apdu_data_size, None);
+}
+
+#[test]
+fn test_backend_storage_card() {
+    let mut reader = PcscReader::with_backend(MockBackend::new());
+    let mut executor = CommandExecutor::new();
+    reader.connect("Mock Reader 1", ShareMode::Shared).unwrap();
+
+    let uid = executor.storage(&mut reader, StorageCommand::GetUid).unwrap();
+    assert!(uid.is_success());
+    assert_eq!(format_hex_spaced(uid.data()), "04 A2 3B 1C");
+
+    let ats = executor.storage(&mut reader, StorageCommand::GetAts).unwrap();
+    assert_eq!(ats.description(), "Function not supported by the reader or the card");
+    assert_eq!(ExitStatus::of(&ats.check().unwrap_err()), ExitStatus::StatusWord);
+
+    let read = executor.storage(&mut reader, StorageCommand::ReadBinary { block: 4, length: 16 }).unwrap();
+    assert!(read.is_success());
+    let history = executor.history();
+    assert_eq!(history.len(), 3);
+    assert_eq!(format_hex_spaced(&history[2].input), "FF B0 00 04 10");
+}
+
+#[test]
+fn test_backend_escape() {
+    let mut reader = PcscReader::with_backend(MockBackend::new());
+    let mut executor = CommandExecutor::new();
+    let command = escape::escape_def("acr1252u-buzzer").unwrap();
+
+    // Reader 1 reports no features: the escape goes to SCARD_CTL_CODE(3500)
+    reader.connect("Mock Reader 1", ShareMode::Direct).unwrap();
+    let result = executor.escape(&mut reader, command, &["0x05"]).unwrap();
+    assert_eq!(result.code, pcsc::ctl_code(3500) as u32);
+    assert_eq!(format_hex_spaced(&result.input), "E0 00 00 28 01 05");
+
+    // Reader 3 reports its CCID_ESC_COMMAND code
+    reader.connect("Mock Reader 3", ShareMode::Direct).unwrap();
+    executor.escape::<_, &str>(&mut reader, command, &[]).unwrap();
+    let history = executor.history();
+    assert!(matches!(history[1].command_type, CommandType::Control { code: 0x42000DAC }));
+    assert_eq!(format_hex_spaced(&history[1].input), "E0 00 00 28 01 0A");
+
+    // Bad parameters never reach the reader
+    let error = executor.escape(&mut reader, command, &["0x100"]).unwrap_err();
+    assert_eq!(ExitStatus::of(&error), ExitStatus::InvalidInput);
+    assert_eq!(executor.history().len(), 2);
+}
+
+#[test]
+fn test_backend_escape_without_features() {
+    // Like many drivers, reject GET_FEATURE_REQUEST with another error than
+    // "unsupported feature"
+    let mut backend = MockBackend::new();
+    backend.feature_error = pcsc::Error::NotTransacted;
+    let mut reader = PcscReader::with_backend(backend);
+    let mut executor = CommandExecutor::new();
+    reader.connect("Mock Reader 1", ShareMode::Direct).unwrap();
+    assert!(reader.features().is_err());
+
+    let command = escape::escape_def("acr122u-firmware").unwrap();
+    let result = executor.escape::<_, &str>(&mut reader, command, &[]).unwrap();
+    assert_eq!(result.code, pcsc::ctl_code(3500) as u32);
+    assert_eq!(executor.history().len(), 1);
+}
+
+#[test]
+fn test_backend_pin_pad() {
+    let mut reader = PcscReader::with_backend(MockBackend::new());
+    let mut executor = CommandExecutor::new();
+    let verify = PinVerify::new(parse_hex("00 20 00 81 08 FFFFFFFFFFFFFFFF").unwrap());
+
+    // Reader 1 has no PIN pad
+    reader.connect("Mock Reader 1", ShareMode::Shared).unwrap();
+    let error = executor.verify_pin(&mut reader, &verify).unwrap_err();
+    assert_eq!(error.to_string(), "Reader does not support VERIFY_PIN_DIRECT");
+    assert_eq!(ExitStatus::of(&error), ExitStatus::Pcsc);
+    assert!(executor.history().is_empty());
+
+    reader.connect("Mock Reader 3", ShareMode::Shared).unwrap();
+    let result = executor.verify_pin(&mut reader, &verify).unwrap();
+    assert_eq!((result.sw1, result.sw2), (0x63, 0xC2));
+    assert_eq!(result.description(), "Wrong PIN, 2 tries left");
+    assert_eq!(ExitStatus::of(&result.check().unwrap_err()), ExitStatus::StatusWord);
+
+    let modify = PinModify::new(parse_hex("00 24 00 81 10 FFFFFFFFFFFFFFFF FFFFFFFFFFFFFFFF").unwrap());
+    let result = executor.modify_pin(&mut reader, &modify).unwrap();
+    assert_eq!(result.description(), "PIN entry cancelled on the reader");
+
+    // The structures go to the history as control commands
+    let history = executor.history();
+    assert!(matches!(history[0].command_type, CommandType::Control { code: 0x42330006 }));
+    assert_eq!(history[0].input, verify.encode().unwrap());
+    assert_eq!(history[1].input, modify.encode().unwrap());
+}
+
+#[test]
+fn test_backend_protocol_recorded() {
+    let mut reader = PcscReader::with_backend(MockBackend::new());
+    let mut executor = CommandExecutor::new();
+    assert_eq!(reader.protocol(), None);
+
+    reader.connect_with_protocol("Mock Reader 1", ShareMode::Shared, backend::ProtocolSelection::T1).unwrap();
+    assert_eq!(reader.protocol(), Some(backend::CardProtocol::T1));
+
+    executor.transmit(&mut reader, "00A40400").unwrap();
+    assert_eq!(executor.history()[0].protocol, Some(backend::CardProtocol::T1));
+
+    reader.disconnect(Disposition::ResetCard).unwrap();
+    assert_eq!(reader.protocol(), None);
+}
+
+#[test]
+fn test_backend_reset_and_reconnect() {
+    let mut reader = PcscReader::with_backend(MockBackend::new());
+    assert!(reader.reset(false).is_err());
+    assert!(reader.reconnect(ShareMode::Shared, backend::ProtocolSelection::T0T1, Disposition::LeaveCard).is_err());
+
+    reader.connect("Mock Reader 1", ShareMode::Shared).unwrap();
+    let atr = reader.reset(false).unwrap();
+    assert_eq!(atr, MockPcscReader::new().readers[0].atr.clone().unwrap());
+    reader.reset(true).unwrap();
+    reader.reconnect(ShareMode::Exclusive, backend::ProtocolSelection::T1, Disposition::LeaveCard).unwrap();
+
+    assert_eq!(reader.card().unwrap().reconnects,
+               vec![Disposition::ResetCard, Disposition::UnpowerCard, Disposition::LeaveCard]);
+    assert_eq!(reader.protocol(), Some(backend::CardProtocol::T1));
+    assert!(reader.is_connected());
+}
+
+#[test]
+fn test_backend_transactions() {
+    let mut reader = PcscReader::with_backend(MockBackend::new());
+    let mut executor = CommandExecutor::new();
+    assert!(reader.begin_transaction().is_err());
+
+    reader.connect("Mock Reader 1", ShareMode::Shared).unwrap();
+    let error = reader.end_transaction(Disposition::LeaveCard).unwrap_err();
+    assert_eq!(ExitStatus::of(&error), ExitStatus::Script);
+
+    {
+        let mut transaction = reader.transaction().unwrap();
+        assert!(transaction.in_transaction());
+        executor.transmit(&mut transaction, "00A40400").unwrap();
+        executor.transmit(&mut transaction, "00B0000005").unwrap();
+        assert_eq!(ExitStatus::of(&transaction.begin_transaction().unwrap_err()), ExitStatus::Script);
+        assert_eq!(ExitStatus::of(&transaction.reset(false).unwrap_err()), ExitStatus::Script);
+    }
+    assert!(!reader.in_transaction());
+
+    reader.transaction().unwrap().end(Disposition::ResetCard).unwrap();
+    assert_eq!(reader.card().unwrap().transactions, vec![Disposition::LeaveCard, Disposition::ResetCard]);
+    assert_eq!(executor.history().len(), 2);
+
+    reader.begin_transaction().unwrap();
+    reader.disconnect(Disposition::LeaveCard).unwrap();
+    assert!(!reader.in_transaction());
+}
+
+#[test]
+fn test_backend_reader_groups() {
+    let mut reader = PcscReader::with_backend(MockBackend::new());
+    assert_eq!(reader.list_reader_groups().unwrap(), vec!["SCard$DefaultReaders", "Station A", "Station B"]);
+    assert_eq!(reader.list_readers().unwrap().len(), 3);
+
+    reader.set_group(Some("Station B".to_string()));
+    assert_eq!(reader.group(), Some("Station B"));
+    let readers = reader.list_readers().unwrap();
+    let names: Vec<&str> = readers.iter().map(|r| r.name.as_str()).collect();
+    assert_eq!(names, vec!["Mock Reader 2", "Mock Reader 3"]);
+    assert!(readers[1].is_connected);
+
+    reader.set_group(Some("Station C".to_string()));
+    assert!(reader.list_readers().unwrap().is_empty());
+
+    reader.set_group(None);
+    assert_eq!(reader.list_readers().unwrap().len(), 3);
+}
+
+#[test]
+fn test_backend_multi_reader_sessions() {
+    let mut sessions = SessionManager::with_backend(MockBackend::new());
+    let mut executor = CommandExecutor::new();
+    assert_eq!(sessions.readers().list_readers().unwrap().len(), 3);
+
+    // Card and SAM connected at the same time
+    sessions.connect("host", "Mock Reader 1", ShareMode::Shared, backend::ProtocolSelection::T0T1).unwrap();
+    sessions.connect("sam", "Mock Reader 3", ShareMode::Exclusive, backend::ProtocolSelection::T0T1).unwrap();
+    assert!(sessions.connect("empty", "Mock Reader 2", ShareMode::Shared, backend::ProtocolSelection::T0T1).is_err());
+    assert!(sessions.connect("sam", "Mock Reader 1", ShareMode::Shared, backend::ProtocolSelection::T0T1).is_err());
+    assert!(sessions.connect("1st", "Mock Reader 1", ShareMode::Shared, backend::ProtocolSelection::T0T1).is_err());
+    assert_eq!(sessions.aliases().collect::<Vec<_>>(), vec!["host", "sam"]);
+
+    let challenge = sessions.transmit(&mut executor, "host", "00B0000005").unwrap();
+    assert_eq!(challenge.response, vec![0x48, 0x65, 0x6C, 0x6C, 0x6F, 0x90, 0x00]);
+    let control = sessions.control(&mut executor, "sam", 0x42000C00, "").unwrap();
+    assert_eq!(control.output, vec![0x01, 0x02, 0x03, 0x04]);
+    assert!(sessions.transmit(&mut executor, "other", "00A40400").is_err());
+    assert_eq!(executor.history().len(), 2);
+
+    let sam = sessions.session("sam").unwrap();
+    assert_eq!(sam.reader_name(), "Mock Reader 3");
+    assert_eq!(sam.share_mode(), ShareMode::Exclusive);
+    assert_eq!(sam.reader().current_reader(), Some("Mock Reader 3"));
+
+    sessions.disconnect("host", Disposition::LeaveCard).unwrap();
+    assert!(sessions.transmit(&mut executor, "host", "00A40400").is_err());
+    assert!(sessions.transmit(&mut executor, "sam", "00A40400").is_ok());
+}
+
+#[test]
+fn test_backend_worker() {
+    let worker = ReaderWorker::spawn(SessionManager::with_backend(MockBackend::new()), CommandExecutor::new());
+    assert_eq!(worker.list_readers().wait().unwrap().len(), 3);
+    worker.connect("host", "Mock Reader 1", ShareMode::Shared, backend::ProtocolSelection::T0T1).wait().unwrap();
+    worker.connect("sam", "Mock Reader 3", ShareMode::Shared, backend::ProtocolSelection::T0T1).wait().unwrap();
+
+    // Each thread drives its own session through a shared handle
+    let threads: Vec<_> = ["host", "sam"].into_iter().map(|alias| {
+        let worker = worker.clone();
+        std::thread::spawn(move || {
+            for _ in 0..5 {
+                let result = worker.transmit(alias, "00B0000005").wait().unwrap();
+                assert_eq!(result.response, vec![0x48, 0x65, 0x6C, 0x6C, 0x6F, 0x90, 0x00]);
+            }
+        })
+    }).collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(worker.history().wait().unwrap().len(), 10);
+
+    // A mute card is cancelled from another thread without blocking it
+    let pending = worker.transmit("host", "00FF0000");
+    assert!(pending.wait_timeout(Duration::from_millis(100)).is_none());
+    worker.cancel();
+    let error = pending.wait().unwrap_err();
+    assert_eq!(ExitStatus::of(&error), ExitStatus::Cancelled);
+    assert!(worker.transmit("host", "00A40400").wait().is_ok());
+}
+
+#[test]
+fn test_backend_recovery_mute_preamble() {
+    let backend = MockBackend::new();
+    let glitches = Arc::clone(&backend.transient_errors);
+    let mut reader = PcscReader::with_backend(backend);
+    let mut executor = CommandExecutor::new();
+    reader.connect("Mock Reader 1", ShareMode::Shared).unwrap();
+    executor.set_recovery_policy(RecoveryPolicy {
+        max_retries: 1,
+        backoff: Duration::from_millis(1),
+        preamble: vec![parse_hex("00FF0000").unwrap()],
+        ..Default::default()
+    });
+
+    // The command timeout also bounds the preamble, and the retry goes on
+    executor.set_timeout(Some(Duration::from_millis(100)));
+    glitches.lock().unwrap().push_back(pcsc::Error::ResetCard);
+    let start = std::time::Instant::now();
+    executor.transmit(&mut reader, "00B0000005").unwrap();
+    assert!(start.elapsed() < Duration::from_secs(10));
+    let history = executor.history();
+    assert_eq!(history.len(), 4);
+    assert_eq!(history[2].error, Some(pcsc::Error::Timeout.to_string()));
+    assert!(history[3].success);
+
+    // Cancelling during the preamble stops the command
+    executor.set_timeout(None);
+    executor.clear_history();
+    let token = reader.cancel_token().clone();
+    let canceller = token.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(100));
+        canceller.cancel();
+    });
+    glitches.lock().unwrap().push_back(pcsc::Error::ResetCard);
+    let error = executor.transmit(&mut reader, "00B0000005").unwrap_err();
+    assert_eq!(ExitStatus::of(&error), ExitStatus::Cancelled);
+    assert_eq!(executor.history().len(), 3);
+}
+
+#[test]
+fn test_backend_recovery() {
+    let backend = MockBackend::new();
+    let glitches = Arc::clone(&backend.transient_errors);
+    let mut reader = PcscReader::with_backend(backend);
+    let mut executor = CommandExecutor::new();
+    reader.connect("Mock Reader 1", ShareMode::Shared).unwrap();
+
+    // Without a policy the first glitch is fatal
+    glitches.lock().unwrap().push_back(pcsc::Error::ResetCard);
+    assert!(executor.transmit(&mut reader, "00B0000005").is_err());
+    assert_eq!(executor.history().len(), 1);
+    executor.clear_history();
+
+    executor.set_recovery_policy(RecoveryPolicy {
+        max_retries: 3,
+        backoff: Duration::from_millis(1),
+        preamble: vec![parse_hex("00A4040002A000").unwrap()],
+        ..Default::default()
+    });
+    glitches.lock().unwrap().push_back(pcsc::Error::ResetCard);
+    let result = executor.transmit(&mut reader, "00B0000005").unwrap();
+    assert_eq!(result.response, vec![0x48, 0x65, 0x6C, 0x6C, 0x6F, 0x90, 0x00]);
+    assert_eq!(reader.card().unwrap().reconnects, vec![Disposition::LeaveCard]);
+
+    // Failed attempt, reconnection, preamble, then the command
+    let history = executor.history();
+    assert_eq!(history.len(), 4);
+    assert!(!history[0].success);
+    assert!(matches!(&history[1].command_type, CommandType::Recovery { attempt: 1, cause } if cause.contains("has been reset")));
+    assert!(history[1].success);
+    assert_eq!(history[2].input, vec![0x00, 0xA4, 0x04, 0x00, 0x02, 0xA0, 0x00]);
+    assert!(history[3].success);
+
+    // A glitch during the preamble does not stop the retry
+    executor.clear_history();
+    glitches.lock().unwrap().extend([pcsc::Error::RemovedCard, pcsc::Error::RemovedCard]);
+    executor.transmit(&mut reader, "00B0000005").unwrap();
+    let history = executor.history();
+    assert_eq!(history.len(), 4);
+    assert!(matches!(history[1].command_type, CommandType::Recovery { attempt: 1, .. }));
+    assert!(!history[2].success);
+    assert!(history[3].success);
+    assert_eq!(reader.card().unwrap().reconnects.len(), 2);
+
+    // Errors recovery cannot help with, and exhausted retries, still fail
+    executor.clear_history();
+    glitches.lock().unwrap().push_back(pcsc::Error::InvalidValue);
+    assert!(executor.transmit(&mut reader, "00B0000005").is_err());
+    assert_eq!(executor.history().len(), 1);
+    executor.set_recovery_policy(RecoveryPolicy {
+        max_retries: 3,
+        backoff: Duration::from_millis(1),
+        ..Default::default()
+    });
+    glitches.lock().unwrap().extend([pcsc::Error::ReaderUnavailable; 4]);
+    assert!(executor.transmit(&mut reader, "00B0000005").is_err());
+    assert!(glitches.lock().unwrap().is_empty());
+}